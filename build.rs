@@ -8,6 +8,8 @@ fn main() {
     println!("cargo:rerun-if-changed=cbindgen.toml");
     println!("cargo:rerun-if-changed=src/");
 
+    set_soname();
+
     let gen_header =env::var("GEN_FOXDBG_HEADER").is_ok() || env::var("CARGO_FEATURE_GEN_FOXDBG_HEADER").is_ok();
     if !gen_header {
         println!("cargo:warning=Skipping foxdbg header generation (build with feature `gen_foxdbg_header` or `GEN_FOXDBG_HEADER=1` to enable)");
@@ -24,3 +26,22 @@ fn main() {
         .expect("Unable to generate bindings")
         .write_to_file("include/foxdbg.h");
 }
+
+/// Names the shared library `libfoxdbg`, like the packaged static library. On Linux it
+/// gets a versioned soname, so programs linked against one major release keep loading a
+/// compatible one. Cargo names the file after the crate, so `release.sh` renames it to match.
+fn set_soname() {
+    match env::var("CARGO_CFG_TARGET_OS").as_deref() {
+        Ok("linux") => {
+            let major = env::var("CARGO_PKG_VERSION_MAJOR").unwrap();
+            println!(
+                "cargo:rustc-cdylib-link-arg=-Wl,-soname,libfoxdbg.so.{}",
+                major
+            );
+        }
+        Ok("macos") => {
+            println!("cargo:rustc-cdylib-link-arg=-Wl,-install_name,@rpath/libfoxdbg.dylib");
+        }
+        _ => {}
+    }
+}
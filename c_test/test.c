@@ -34,6 +34,28 @@ void signal_handler(int signal)
     is_running = 0;
 }
 
+/* Called on the foxdbg dispatch thread for every setpoint published from Foxglove */
+static void setpoint_callback(const void *data, size_t size, void *user_data)
+{
+    (void)size;
+    (void)user_data;
+    printf("Setpoint received: %f\n", *(const float *)data);
+}
+
+/* Called from the Service Call panel in Foxglove to save the tuned parameters */
+static bool save_params_service(const char *request, size_t request_size, char *response,
+                                size_t response_capacity, size_t *response_size, void *user_data)
+{
+    (void)request;
+    (void)request_size;
+    if (foxdbg_save_params((const char *)user_data) != FOXDBG_RESULT_OK)
+    {
+        *response_size = (size_t)snprintf(response, response_capacity, "%s", foxdbg_last_error());
+        return false;
+    }
+    return true;
+}
+
 /* Cross-platform high-resolution timer */
 static double get_time_seconds(void)
 {
@@ -51,16 +73,29 @@ static double get_time_seconds(void)
 
 int main(int argc, char *argv[])
 {
-    foxdbg_init();
-
-    foxdbg_add_channel("/sensors/banana",  FOXDBG_CHANNEL_TYPE_IMAGE, 30);   
-    foxdbg_add_channel("/sensors/banana2", FOXDBG_CHANNEL_TYPE_IMAGE, 30);
-    foxdbg_add_channel("/waves/sin",  FOXDBG_CHANNEL_TYPE_FLOAT, 30);
-    foxdbg_add_channel("/waves/bool", FOXDBG_CHANNEL_TYPE_BOOLEAN, 30);
-    foxdbg_add_channel("/waves/int",  FOXDBG_CHANNEL_TYPE_INTEGER, 30);
-    foxdbg_add_channel("/pointclouds/test", FOXDBG_CHANNEL_TYPE_POINTCLOUD, 30);
+    if (foxdbg_init() != FOXDBG_RESULT_OK)
+    {
+        printf("Failed to initialise foxdbg: %s\n", foxdbg_last_error());
+        return 1;
+    }
 
-    // int rx_channel = foxdbg_add_rx_channel("/rx/system_state", FOXDBG_CHANNEL_TYPE_BOOLEAN);
+    foxdbg_add_channel("/sensors/banana",  FOXDBG_CHANNEL_TYPE_IMAGE, 30, NULL);
+    foxdbg_add_channel("/sensors/banana2", FOXDBG_CHANNEL_TYPE_IMAGE, 30, NULL);
+    int sin_channel;
+    foxdbg_add_channel("/waves/sin",  FOXDBG_CHANNEL_TYPE_FLOAT, 30, &sin_channel);
+    foxdbg_add_param_float("waves/sin_frequency", 0.1f, 0.0f, 10.0f);
+    foxdbg_add_service("params/save", NULL, save_params_service, "params.json");
+    int bool_channel;
+    foxdbg_add_channel("/waves/bool", FOXDBG_CHANNEL_TYPE_BOOLEAN, 30, &bool_channel);
+    int int_channel;
+    foxdbg_add_channel("/waves/int",  FOXDBG_CHANNEL_TYPE_INTEGER, 30, &int_channel);
+    int pointcloud_channel;
+    foxdbg_add_channel("/pointclouds/test", FOXDBG_CHANNEL_TYPE_POINTCLOUD, 30, &pointcloud_channel);
+
+    int rx_channel;
+    foxdbg_add_rx_channel("/rx/system_state", FOXDBG_CHANNEL_TYPE_BOOLEAN, &rx_channel);
+    int setpoint_subscription;
+    foxdbg_subscribe_rx("/rx/setpoint", FOXDBG_CHANNEL_TYPE_FLOAT, setpoint_callback, NULL, &setpoint_subscription);
 
     signal(SIGINT,  signal_handler);
     signal(SIGTERM, signal_handler);
@@ -70,50 +105,57 @@ int main(int argc, char *argv[])
     channels = 3;
 
     foxdbg_image_info_t image_info = { width, height, channels };
-    foxdbg_write_channel_info("/sensors/banana", &image_info, sizeof(image_info));
+    foxdbg_write_topic_info("/sensors/banana", &image_info, sizeof(image_info));
 
     int width2, height2, channels2;
     uint8_t* data2 = stbi_load("c_test/banana.png", &width2, &height2, &channels2, 3);
     channels2 = 3;
 
     foxdbg_image_info_t image_info2 = { width2, height2, channels2 };
-    foxdbg_write_channel_info("/sensors/banana2", &image_info2, sizeof(image_info2));
+    foxdbg_write_topic_info("/sensors/banana2", &image_info2, sizeof(image_info2));
 
     const int num_points = 10000;
     foxdbg_vector4_t* pointcloud = (foxdbg_vector4_t*)malloc(num_points * sizeof(foxdbg_vector4_t));
 
-    foxdbg_add_channel("/cubes/test", FOXDBG_CHANNEL_TYPE_CUBES, 30);
+    int cubes_channel;
+    foxdbg_add_channel("/cubes/test", FOXDBG_CHANNEL_TYPE_CUBES, 30, &cubes_channel);
     const int num_cubes = 1;
     foxdbg_cube_t* cubes = (foxdbg_cube_t*)malloc(num_cubes * sizeof(foxdbg_cube_t));
 
-    foxdbg_add_channel("/lines/test", FOXDBG_CHANNEL_TYPE_LINES, 30);
+    int lines_channel;
+    foxdbg_add_channel("/lines/test", FOXDBG_CHANNEL_TYPE_LINES, 30, &lines_channel);
     const int num_lines = 2;
     foxdbg_line_t* lines = (foxdbg_line_t*)malloc(num_lines * sizeof(foxdbg_line_t));
 
-    foxdbg_add_channel("/poses/test", FOXDBG_CHANNEL_TYPE_POSE, 30);
+    int pose_channel;
+    foxdbg_add_channel("/poses/test", FOXDBG_CHANNEL_TYPE_POSE, 30, &pose_channel);
     foxdbg_pose_t* pose = (foxdbg_pose_t*)malloc(sizeof(foxdbg_pose_t));
 
-    foxdbg_add_channel("/tf", FOXDBG_CHANNEL_TYPE_TRANSFORM, 30);
+    int tf_channel;
+    foxdbg_add_channel("/tf", FOXDBG_CHANNEL_TYPE_TRANSFORM, 30, &tf_channel);
     foxdbg_transform_t* transform = (foxdbg_transform_t*)malloc(sizeof(foxdbg_transform_t));
 
-    foxdbg_add_channel("/location/test", FOXDBG_CHANNEL_TYPE_LOCATION, 1);
+    int location_channel;
+    foxdbg_add_channel("/location/test", FOXDBG_CHANNEL_TYPE_LOCATION, 1, &location_channel);
     foxdbg_location_t* location = (foxdbg_location_t*)malloc(sizeof(foxdbg_location_t));
 
     while (is_running)
     {
-        foxdbg_write_channel("/sensors/banana",  data,  width * height * channels);
-        foxdbg_write_channel("/sensors/banana2", data2, width2 * height2 * channels2);
+        foxdbg_write_topic("/sensors/banana",  data,  width * height * channels);
+        foxdbg_write_topic("/sensors/banana2", data2, width2 * height2 * channels2);
 
         double t = get_time_seconds();
-        float sin_value = sinf((float)(t * 2.0f * 3.14159f * 0.1f));
-        foxdbg_write_channel("/waves/sin", &sin_value, sizeof(sin_value));
+        float sin_frequency = 0.1f;
+        foxdbg_get_param_float("waves/sin_frequency", &sin_frequency);
+        float sin_value = sinf((float)(t * 2.0f * 3.14159f * sin_frequency));
+        foxdbg_write_channel(sin_channel, &sin_value, sizeof(sin_value));
 
         bool is_true = (sin_value > 0.0f);
-        foxdbg_write_channel("/waves/bool", &is_true, sizeof(bool));
+        foxdbg_write_channel(bool_channel, &is_true, sizeof(bool));
 
         static int int_value = 0;
         int_value++;
-        foxdbg_write_channel("/waves/int", &int_value, sizeof(int_value));
+        foxdbg_write_channel(int_channel, &int_value, sizeof(int_value));
 
         for (int i = 0; i < num_points; i++) {
             float time_offset = (float)t * 2.0f;
@@ -122,7 +164,7 @@ int main(int argc, char *argv[])
             pointcloud[i].z = i * 0.1f - 5.0f;
             pointcloud[i].w = 255.f;
         }
-        foxdbg_write_channel("/pointclouds/test", pointcloud, num_points * sizeof(foxdbg_vector4_t));
+        foxdbg_write_channel(pointcloud_channel, pointcloud, num_points * sizeof(foxdbg_vector4_t));
 
         cubes[0].position.x = sinf((float)t * 0.5f) * 5.0f;
         cubes[0].position.y = cosf((float)t * 0.5f) * 5.0f;
@@ -141,7 +183,7 @@ int main(int argc, char *argv[])
         cubes[0].color.b = 0.0f;
         cubes[0].color.a = 1.0f;
 
-        foxdbg_write_channel("/cubes/test", cubes, num_cubes * sizeof(foxdbg_cube_t));
+        foxdbg_write_channel(cubes_channel, cubes, num_cubes * sizeof(foxdbg_cube_t));
 
         // Create a rotating line
         lines[0].start.x = 0;
@@ -169,7 +211,7 @@ int main(int argc, char *argv[])
         lines[1].color.b = 1.0f;
         lines[1].color.a = 1.0f;
 
-        foxdbg_write_channel("/lines/test", lines, num_lines * sizeof(foxdbg_line_t));
+        foxdbg_write_channel(lines_channel, lines, num_lines * sizeof(foxdbg_line_t));
 
         // Create a moving and rotating pose
         pose->position.x = sinf((float)t * 0.5f) * 2.0f;
@@ -183,7 +225,7 @@ int main(int argc, char *argv[])
         pose->color.b = 0.0f;
         pose->color.a = 1.0f;
 
-        foxdbg_write_channel("/poses/test", pose, sizeof(foxdbg_pose_t));
+        foxdbg_write_channel(pose_channel, pose, sizeof(foxdbg_pose_t));
 
         // Create a transform
         transform->id = "child_frame";
@@ -195,7 +237,7 @@ int main(int argc, char *argv[])
         transform->orientation.y = 0.0f;
         transform->orientation.z = (float)t; // Rotating
 
-        foxdbg_write_channel("/tf", transform, sizeof(foxdbg_transform_t));
+        foxdbg_write_channel(tf_channel, transform, sizeof(foxdbg_transform_t));
 
         // Create a location
         location->timestamp_sec = (uint32_t)t;
@@ -204,7 +246,16 @@ int main(int argc, char *argv[])
         location->longitude = -0.1278 + (double)sinf((float)t * 0.1f) * 0.1; // Moving back and forth
         location->altitude = 11.0;
 
-        foxdbg_write_channel("/location/test", location, sizeof(foxdbg_location_t));
+        foxdbg_write_channel(location_channel, location, sizeof(foxdbg_location_t));
+
+        // Report system state changes published from Foxglove
+        bool system_state;
+        bool is_new = false;
+        foxdbg_get_rx_channel(rx_channel, &system_state, sizeof(system_state), &is_new);
+        if (is_new)
+        {
+            printf("System state set to %s\n", system_state ? "true" : "false");
+        }
 
         YIELD_CPU();
     }
@@ -215,6 +266,7 @@ int main(int argc, char *argv[])
     free(pose);
     free(transform);
     free(location);
+    foxdbg_unsubscribe_rx(setpoint_subscription);
     foxdbg_shutdown();
     return 0;
 }
@@ -1,6 +1,8 @@
 get_filename_component(_FOXDBG_PKGDIR "${CMAKE_CURRENT_LIST_DIR}/.." ABSOLUTE)
 set(_FOXDBG_INC "${_FOXDBG_PKGDIR}/include")
 
+option(FOXDBG_SHARED "Link foxdbg::foxdbg against the shared foxdbg library" OFF)
+
 if (WIN32)
   set(_FOXDBG_LIB "${_FOXDBG_PKGDIR}/lib/foxdbg.lib")
 else()
@@ -11,8 +13,43 @@ if (NOT EXISTS "${_FOXDBG_LIB}")
   message(FATAL_ERROR "foxdbg library not found at ${_FOXDBG_LIB}")
 endif()
 
-add_library(foxdbg::foxdbg STATIC IMPORTED GLOBAL)
-set_target_properties(foxdbg::foxdbg PROPERTIES
+add_library(foxdbg::foxdbg_static STATIC IMPORTED GLOBAL)
+set_target_properties(foxdbg::foxdbg_static PROPERTIES
   IMPORTED_LOCATION "${_FOXDBG_LIB}"
   INTERFACE_INCLUDE_DIRECTORIES "${_FOXDBG_INC}"
-)
\ No newline at end of file
+)
+
+# The shared library is not packaged with an import library on Windows, so it can only be
+# loaded at runtime there, as the Python bindings do
+if (APPLE)
+  set(_FOXDBG_SHARED_LIB "${_FOXDBG_PKGDIR}/lib/libfoxdbg.dylib")
+  set(_FOXDBG_SONAME "@rpath/libfoxdbg.dylib")
+elseif (NOT WIN32)
+  set(_FOXDBG_SHARED_LIB "${_FOXDBG_PKGDIR}/lib/libfoxdbg.so.0")
+  set(_FOXDBG_SONAME "libfoxdbg.so.0")
+endif()
+
+if (DEFINED _FOXDBG_SHARED_LIB AND EXISTS "${_FOXDBG_SHARED_LIB}")
+  add_library(foxdbg::foxdbg_shared SHARED IMPORTED GLOBAL)
+  set_target_properties(foxdbg::foxdbg_shared PROPERTIES
+    IMPORTED_LOCATION "${_FOXDBG_SHARED_LIB}"
+    IMPORTED_SONAME "${_FOXDBG_SONAME}"
+    INTERFACE_INCLUDE_DIRECTORIES "${_FOXDBG_INC}"
+  )
+elseif (FOXDBG_SHARED)
+  message(FATAL_ERROR "foxdbg shared library not found for this platform")
+endif()
+
+add_library(foxdbg::foxdbg INTERFACE IMPORTED GLOBAL)
+if (FOXDBG_SHARED)
+  set_target_properties(foxdbg::foxdbg PROPERTIES INTERFACE_LINK_LIBRARIES foxdbg::foxdbg_shared)
+else()
+  set_target_properties(foxdbg::foxdbg PROPERTIES INTERFACE_LINK_LIBRARIES foxdbg::foxdbg_static)
+endif()
+
+# C++ wrapper (include/foxdbg.hpp), which needs C++20 for std::span
+add_library(foxdbg::foxdbg_cpp INTERFACE IMPORTED GLOBAL)
+set_target_properties(foxdbg::foxdbg_cpp PROPERTIES
+  INTERFACE_LINK_LIBRARIES foxdbg::foxdbg
+  INTERFACE_COMPILE_FEATURES cxx_std_20
+)
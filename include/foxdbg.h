@@ -22,6 +22,254 @@ typedef enum foxdbg_channel_type_t {
   FOXDBG_CHANNEL_TYPE_BOOLEAN,
 } foxdbg_channel_type_t;
 
+/**
+ * Compression applied to chunks in MCAP recordings.
+ */
+typedef enum foxdbg_compression_t {
+  FOXDBG_COMPRESSION_NONE,
+  FOXDBG_COMPRESSION_LZ4,
+  FOXDBG_COMPRESSION_ZSTD,
+} foxdbg_compression_t;
+
+/**
+ * What happens to a message received while the rx dispatch queue is full.
+ */
+typedef enum foxdbg_overflow_policy_t {
+  /**
+   * Drop the oldest queued message to make room, so callbacks catch up with the latest.
+   */
+  FOXDBG_OVERFLOW_DROP_OLDEST,
+  /**
+   * Drop the message just received, so callbacks see an unbroken run of older messages.
+   */
+  FOXDBG_OVERFLOW_DROP_NEWEST,
+} foxdbg_overflow_policy_t;
+
+/**
+ * Result of a foxdbg call. On anything other than `FOXDBG_RESULT_OK`, a description of
+ * the error can be read with `foxdbg_last_error`.
+ */
+typedef enum foxdbg_result_t {
+  FOXDBG_RESULT_OK = 0,
+  /**
+   * A NULL pointer or otherwise invalid argument was passed in.
+   */
+  FOXDBG_RESULT_INVALID_ARGUMENT,
+  /**
+   * foxdbg has not been initialised.
+   */
+  FOXDBG_RESULT_NOT_INITIALISED,
+  /**
+   * The feature was not enabled in the configuration.
+   */
+  FOXDBG_RESULT_NOT_ENABLED,
+  /**
+   * No channel has the given id or topic.
+   */
+  FOXDBG_RESULT_UNKNOWN_CHANNEL,
+  /**
+   * The size of the data does not match the channel's type.
+   */
+  FOXDBG_RESULT_SIZE_MISMATCH,
+  /**
+   * An image was written before its channel info was set.
+   */
+  FOXDBG_RESULT_MISSING_CHANNEL_INFO,
+  /**
+   * The data could not be encoded.
+   */
+  FOXDBG_RESULT_ENCODE_FAILED,
+  /**
+   * A file could not be read or written.
+   */
+  FOXDBG_RESULT_IO_ERROR,
+  /**
+   * The MCAP recording could not be written.
+   */
+  FOXDBG_RESULT_RECORDING_FAILED,
+  /**
+   * The WebSocket server could not be started.
+   */
+  FOXDBG_RESULT_SERVER_FAILED,
+  /**
+   * An unexpected internal error occurred.
+   */
+  FOXDBG_RESULT_INTERNAL_ERROR,
+  /**
+   * The topic is already registered with a different channel type.
+   */
+  FOXDBG_RESULT_CHANNEL_CONFLICT,
+  /**
+   * No parameter has the given name.
+   */
+  FOXDBG_RESULT_UNKNOWN_PARAMETER,
+} foxdbg_result_t;
+
+/**
+ * Options controlling how MCAP recordings are written.
+ */
+typedef struct foxdbg_mcap_options_t {
+  /**
+   * Compression applied to each chunk. Defaults to zstd.
+   */
+  enum foxdbg_compression_t compression;
+  /**
+   * Compression level passed to the compressor. 0 selects the compressor's default.
+   */
+  uint32_t compression_level;
+  /**
+   * Target uncompressed size of each chunk in bytes. 0 selects the default of 768 KiB.
+   */
+  uint64_t chunk_size;
+  /**
+   * Whether to write message index records after each chunk. Disabling them produces
+   * smaller files that are slower to seek in.
+   */
+  bool emit_message_indexes;
+} foxdbg_mcap_options_t;
+
+/**
+ * Configuration passed to `foxdbg_init_with_config`.
+ *
+ * Use `foxdbg_config_default` to obtain a configuration populated with the defaults and
+ * override only the fields you need. String fields may be NULL to select their default.
+ *
+ * When either rotation limit is set, recordings are split into a series of files named
+ * `{session}_{index}.mcap`, where the session is the UNIX time recording started.
+ */
+typedef struct foxdbg_config_t {
+  /**
+   * Address the WebSocket server binds to. NULL selects `127.0.0.1`.
+   */
+  const char *host;
+  /**
+   * Port the WebSocket server listens on. 0 selects any free port.
+   */
+  uint16_t port;
+  /**
+   * Server name advertised to Foxglove clients. NULL advertises no name.
+   */
+  const char *server_name;
+  /**
+   * Directory MCAP recordings are written to. NULL selects the working directory.
+   */
+  const char *recording_dir;
+  /**
+   * Whether to start the live WebSocket server.
+   */
+  bool enable_server;
+  /**
+   * Whether to record to an MCAP file.
+   */
+  bool enable_recording;
+  /**
+   * Roll over to a new MCAP file once the current one reaches this many bytes.
+   * 0 disables size-based rotation.
+   */
+  uint64_t max_file_size;
+  /**
+   * Roll over to a new MCAP file once the current one has been open for this many
+   * seconds. 0 disables time-based rotation.
+   */
+  uint32_t max_file_duration_sec;
+  /**
+   * Compression and chunking options for MCAP recordings and blackbox dumps.
+   */
+  struct foxdbg_mcap_options_t mcap;
+  /**
+   * Whether to keep the most recent messages of every channel in an in-memory ring
+   * buffer that can be written out with `foxdbg_dump_blackbox`.
+   */
+  bool enable_blackbox;
+  /**
+   * Seconds of history kept by the blackbox. 0 removes the time limit.
+   */
+  uint32_t blackbox_duration_sec;
+  /**
+   * Maximum number of message bytes kept by the blackbox. 0 removes the size limit.
+   */
+  uint64_t blackbox_max_bytes;
+  /**
+   * Whether to close the recording when the process panics or exits without calling
+   * `foxdbg_shutdown`.
+   */
+  bool enable_crash_safety;
+  /**
+   * Whether to install SIGINT and SIGTERM handlers that close the recording before the
+   * process terminates. These replace any handlers installed by the application.
+   */
+  bool install_signal_handlers;
+  /**
+   * Write buffered MCAP data to disk at least this often, in milliseconds, bounding the
+   * data lost if the process is killed. 0 only writes data out as chunks fill up.
+   */
+  uint32_t flush_interval_ms;
+  /**
+   * Number of received messages queued for `foxdbg_subscribe_rx` callbacks before the
+   * overflow policy applies. 0 selects the default of 256.
+   */
+  uint32_t rx_queue_depth;
+  /**
+   * What happens to messages received while the rx dispatch queue is full.
+   */
+  enum foxdbg_overflow_policy_t rx_overflow_policy;
+  /**
+   * Milliseconds a service call may take before its client is sent an error. 0 selects
+   * the default of 5000.
+   */
+  uint32_t service_timeout_ms;
+} foxdbg_config_t;
+
+/**
+ * Clock passed to `foxdbg_set_clock_callback`. Returns the current time in nanoseconds
+ * since the UNIX epoch, and is called with the `user_data` it was registered with. It may
+ * be called from any thread that writes to foxdbg.
+ */
+typedef uint64_t (*foxdbg_clock_callback_t)(void *user_data);
+
+typedef struct foxdbg_image_info_t {
+  int32_t width;
+  int32_t height;
+  int32_t channels;
+} foxdbg_image_info_t;
+
+/**
+ * Write counters of a channel, filled in by `foxdbg_get_channel_stats`.
+ */
+typedef struct foxdbg_channel_stats_t {
+  /**
+   * Number of writes published to the channel.
+   */
+  uint64_t written;
+  /**
+   * Number of writes dropped for arriving faster than the channel's target rate.
+   */
+  uint64_t dropped;
+} foxdbg_channel_stats_t;
+
+/**
+ * Callback passed to `foxdbg_subscribe_rx`, called with each message received on the
+ * subscribed topic. `data` points to `size` bytes laid out as the subscription's type, and
+ * is only valid for the duration of the call. Callbacks run one at a time on a dedicated
+ * dispatch thread, in the order the messages were received.
+ */
+typedef void (*foxdbg_rx_callback_t)(const void *data, size_t size, void *user_data);
+
+/**
+ * Callback passed to `foxdbg_add_service`, called with each request a Foxglove client sends
+ * to the service. `request` points to `request_size` bytes of JSON followed by a NUL, so
+ * it can be read as a string. On success, the callback writes its JSON response of up to
+ * `response_capacity` bytes to `response`, stores its length in `response_size` and
+ * returns true; an empty response is sent as `{}`. To fail the call, it returns false,
+ * optionally writing an error message to `response` the same way.
+ */
+typedef bool (*foxdbg_service_callback_t)(const char *request,
+                                          size_t request_size,
+                                          char *response,
+                                          size_t response_capacity,
+                                          size_t *response_size,
+                                          void *user_data);
+
 typedef struct foxdbg_color_t {
   float r;
   float g;
@@ -80,43 +328,498 @@ typedef struct foxdbg_location_t {
   double altitude;
 } foxdbg_location_t;
 
-typedef struct foxdbg_image_info_t {
-  int32_t width;
-  int32_t height;
-  int32_t channels;
-} foxdbg_image_info_t;
+/**
+ * Display options of a channel, set by passing this struct to `foxdbg_write_channel_info`.
+ * Every channel type accepts it, and options that do not apply to a type are ignored.
+ */
+typedef struct foxdbg_channel_options_t {
+  /**
+   * Coordinate frame the channel's data is expressed in, used by scene, point cloud,
+   * image and location channels. NULL selects `world`.
+   */
+  const char *frame_id;
+  /**
+   * Prepended to the topic to form the id of the scene entity a cubes, lines or pose
+   * channel publishes. NULL adds no prefix, so the entity id is the topic.
+   */
+  const char *entity_id_prefix;
+  /**
+   * How long scene entities are shown after being written, in milliseconds. 0 shows
+   * them until they are replaced.
+   */
+  uint32_t lifetime_ms;
+  /**
+   * Whether scene entities move with their frame when the frame moves after they were
+   * written, rather than staying where they were first shown.
+   */
+  bool frame_locked;
+} foxdbg_channel_options_t;
 
 #ifdef __cplusplus
 extern "C" {
 #endif // __cplusplus
 
+/**
+ * Returns a configuration populated with the default values used by `foxdbg_init`.
+ */
+struct foxdbg_config_t foxdbg_config_default(void);
+
+/**
+ * Returns a description of the most recent error on the calling thread, or NULL if no
+ * call has failed on this thread. The string remains valid until the next call on this
+ * thread fails.
+ */
+const char *foxdbg_last_error(void);
+
 /**
  * Initialises the debugging system and starts the server thread.
  */
-void foxdbg_init(void);
+enum foxdbg_result_t foxdbg_init(void);
+
+/**
+ * Initialises the debugging system using the supplied configuration. Passing NULL is
+ * equivalent to calling `foxdbg_init`.
+ *
+ * # Safety
+ *
+ * `config` must be NULL or point to a valid `foxdbg_config_t` whose string fields are
+ * NULL or valid NUL-terminated C strings.
+ */
+enum foxdbg_result_t foxdbg_init_with_config(const struct foxdbg_config_t *config);
 
 /**
  * Shuts down the server and cleans up resources.
  */
-void foxdbg_shutdown(void);
+enum foxdbg_result_t foxdbg_shutdown(void);
+
+/**
+ * Starts recording to a new MCAP file at `path`, replacing any recording in progress.
+ * Passing NULL for `path` names the file with the current UNIX time in the configured
+ * recording directory. Passing NULL for `options` uses the options given at
+ * initialisation. Channels created before the recording started are included in it.
+ *
+ * # Safety
+ *
+ * `path` must be NULL or a valid NUL-terminated C string, and `options` must be NULL or
+ * point to a valid `foxdbg_mcap_options_t`.
+ */
+enum foxdbg_result_t foxdbg_start_recording(const char *path,
+                                            const struct foxdbg_mcap_options_t *options);
+
+/**
+ * Stops the recording in progress, closing its MCAP file.
+ */
+enum foxdbg_result_t foxdbg_stop_recording(void);
+
+/**
+ * Adds a metadata record named `name` to the session, made of `count` key/value pairs
+ * taken from the `keys` and `values` arrays. The record is written into the current
+ * recording and into every recording file opened afterwards, so it can be called before
+ * recording starts.
+ *
+ * # Safety
+ *
+ * `name` must be a valid NUL-terminated C string, and `keys` and `values` must each point
+ * to `count` valid NUL-terminated C strings.
+ */
+enum foxdbg_result_t foxdbg_write_metadata(const char *name,
+                                           const char *const *keys,
+                                           const char *const *values,
+                                           size_t count);
+
+/**
+ * Attaches the file at `path`, such as a YAML config or calibration file, to the session
+ * under its file name. Like metadata, it is written into the current recording and every
+ * recording file opened afterwards.
+ *
+ * # Safety
+ *
+ * `path` and `media_type` must be valid NUL-terminated C strings.
+ */
+enum foxdbg_result_t foxdbg_attach_file(const char *path, const char *media_type);
+
+/**
+ * Attaches `size` bytes from `data` to the session under `name`. See
+ * `foxdbg_attach_file`.
+ *
+ * # Safety
+ *
+ * `name` and `media_type` must be valid NUL-terminated C strings, and `data` must point
+ * to at least `size` readable bytes.
+ */
+enum foxdbg_result_t foxdbg_attach_data(const char *name,
+                                        const char *media_type,
+                                        const void *data,
+                                        size_t size);
+
+/**
+ * Writes the contents of the blackbox ring buffer to a new MCAP file at `path`. The
+ * file must not already exist. Returns `FOXDBG_RESULT_NOT_ENABLED` if the blackbox is
+ * not enabled.
+ *
+ * # Safety
+ *
+ * `path` must be a valid NUL-terminated C string.
+ */
+enum foxdbg_result_t foxdbg_dump_blackbox(const char *path);
+
+/**
+ * Replaces the system clock with `callback`, which every message, attachment and server
+ * time broadcast is then stamped with, such as a simulator's or a log replay's clock.
+ * `user_data` is passed back to each call. Passing NULL for `callback` returns to the
+ * system clock. Set the clock before `foxdbg_init` so the server advertises its time to
+ * Foxglove clients.
+ *
+ * # Safety
+ *
+ * `callback` must remain safe to call with `user_data`, from any thread, until the clock
+ * is replaced.
+ */
+enum foxdbg_result_t foxdbg_set_clock_callback(foxdbg_clock_callback_t callback, void *user_data);
+
+/**
+ * Sets the current time, which every message is stamped with until it is set again. Use
+ * it to step time manually, for example once per simulation tick. Like the clock
+ * callback, it replaces the system clock; see `foxdbg_set_clock_callback`. `nsec` may
+ * exceed one second, in which case the excess is carried into `sec`.
+ */
+enum foxdbg_result_t foxdbg_set_time(uint32_t sec, uint32_t nsec);
+
+/**
+ * Creates a new channel (topic) to publish data to Foxglove. The channel ID for use with
+ * other functions is stored in `channel_id`, which may be NULL if the channel is only
+ * written to by topic.
+ *
+ * Adding a topic that already exists with the same type returns the existing channel.
+ * If the existing channel has a different type, `FOXDBG_RESULT_CHANNEL_CONFLICT` is
+ * returned instead.
+ *
+ * Writes arriving faster than `target_hz` are dropped before any encoding or compression
+ * takes place, and counted in the channel's stats. Pass 0 to publish every write.
+ *
+ * # Safety
+ *
+ * `topic_name` must be a valid NUL-terminated C string, and `channel_id` must be NULL or
+ * point to writable memory for an `int`.
+ */
+enum foxdbg_result_t foxdbg_add_channel(const char *topic_name,
+                                        enum foxdbg_channel_type_t channel_type,
+                                        int target_hz,
+                                        int *channel_id);
 
 /**
- * Creates a new channel (topic) to publish data to Foxglove. It returns a channel ID for
- * use with other functions.
+ * Removes a channel, unadvertising it to connected Foxglove clients and no longer
+ * recording it. Writing to the removed id afterwards returns
+ * `FOXDBG_RESULT_UNKNOWN_CHANNEL`, and its topic may be added again with any type.
  */
-int foxdbg_add_channel(const char *topic_name,
-                       enum foxdbg_channel_type_t channel_type,
-                       int _target_hz);
+enum foxdbg_result_t foxdbg_remove_channel(int channel_id);
 
 /**
  * Writes a data payload to a specified channel.
+ *
+ * # Safety
+ *
+ * `data` must point to at least `size` readable bytes laid out as the channel's type.
+ */
+enum foxdbg_result_t foxdbg_write_channel(int channel_id, const void *data, size_t size);
+
+/**
+ * Writes a data payload to a specified channel, stamped with the time the data was
+ * captured instead of the time of the call. The stamp is used both as the timestamp
+ * inside the message and as its log time in recordings. `nsec` may exceed one second, in
+ * which case the excess is carried into `sec`.
+ *
+ * # Safety
+ *
+ * `data` must point to at least `size` readable bytes laid out as the channel's type.
+ */
+enum foxdbg_result_t foxdbg_write_channel_stamped(int channel_id,
+                                                  uint32_t sec,
+                                                  uint32_t nsec,
+                                                  const void *data,
+                                                  size_t size);
+
+/**
+ * Writes metadata for a channel. Any channel takes a `foxdbg_channel_options_t` setting
+ * its display options, and image channels take a `foxdbg_image_info_t` giving the
+ * dimensions of their images. The struct passed is identified by `size`.
+ *
+ * # Safety
+ *
+ * `data` must point to at least `size` readable bytes laid out as the channel's info
+ * type, and the strings in a `foxdbg_channel_options_t` must be NULL or valid
+ * NUL-terminated C strings. They are copied, so need not outlive the call.
+ */
+enum foxdbg_result_t foxdbg_write_channel_info(int channel_id, const void *data, size_t size);
+
+/**
+ * Writes an image to an image channel together with its dimensions. Unlike a call to
+ * `foxdbg_write_channel_info` followed by `foxdbg_write_channel`, no write from another
+ * thread can change the dimensions before the image is written.
+ *
+ * # Safety
+ *
+ * `info` must point to a valid `foxdbg_image_info_t`, and `data` must point to at least
+ * `size` readable bytes of pixel data.
+ */
+enum foxdbg_result_t foxdbg_write_image(int channel_id,
+                                        const struct foxdbg_image_info_t *info,
+                                        const void *data,
+                                        size_t size);
+
+/**
+ * Writes an image like `foxdbg_write_image`, stamped with the time it was captured. See
+ * `foxdbg_write_channel_stamped`.
+ *
+ * # Safety
+ *
+ * `info` must point to a valid `foxdbg_image_info_t`, and `data` must point to at least
+ * `size` readable bytes of pixel data.
+ */
+enum foxdbg_result_t foxdbg_write_image_stamped(int channel_id,
+                                                uint32_t sec,
+                                                uint32_t nsec,
+                                                const struct foxdbg_image_info_t *info,
+                                                const void *data,
+                                                size_t size);
+
+/**
+ * Writes a data payload to the channel with the given topic, like the original foxdbg
+ * library. Prefer `foxdbg_write_channel` in hot loops, which skips the topic lookup.
+ *
+ * # Safety
+ *
+ * `topic_name` must be a valid NUL-terminated C string, and `data` must point to at
+ * least `size` readable bytes laid out as the channel's type.
+ */
+enum foxdbg_result_t foxdbg_write_topic(const char *topic_name, const void *data, size_t size);
+
+/**
+ * Writes metadata for the channel with the given topic. See `foxdbg_write_channel_info`.
+ *
+ * # Safety
+ *
+ * `topic_name` must be a valid NUL-terminated C string, and `data` must point to at
+ * least `size` readable bytes laid out as the channel's info type.
+ */
+enum foxdbg_result_t foxdbg_write_topic_info(const char *topic_name, const void *data, size_t size);
+
+/**
+ * Opens a frame on the calling thread, so that the writes it makes until
+ * `foxdbg_frame_commit` are published together. Writes in the frame are stamped with the
+ * given time unless made with `foxdbg_write_channel_stamped`, and their data is copied
+ * when they are made. Writes from other threads are not part of the frame.
+ */
+enum foxdbg_result_t foxdbg_frame_begin(uint32_t sec, uint32_t nsec);
+
+/**
+ * Publishes the writes queued since `foxdbg_frame_begin` on the calling thread, in the
+ * order they were made and without writes from other threads in between. Errors in
+ * individual writes were reported when they were made, and do not stop the commit.
+ */
+enum foxdbg_result_t foxdbg_frame_commit(void);
+
+/**
+ * Closes the frame opened by `foxdbg_frame_begin` on the calling thread without publishing
+ * the writes queued in it, such as when the code producing them failed part way through.
+ */
+enum foxdbg_result_t foxdbg_frame_abort(void);
+
+/**
+ * Stores the number of writes published to and dropped from a channel in `stats`. Writes
+ * are dropped when they arrive faster than the channel's target rate.
+ *
+ * # Safety
+ *
+ * `stats` must point to writable memory for a `foxdbg_channel_stats_t`.
+ */
+enum foxdbg_result_t foxdbg_get_channel_stats(int channel_id, struct foxdbg_channel_stats_t *stats);
+
+/**
+ * Creates a receive (rx) channel, which Foxglove clients can publish to on `topic_name`,
+ * for example from the Publish panel. The channel ID for use with `foxdbg_get_rx_channel`
+ * is stored in `channel_id`. Rx channel IDs are separate from the IDs of the channels
+ * written to.
+ *
+ * Boolean, float, integer and pose channels can be received. Clients publish JSON, either
+ * a bare value or one in a `value` or `data` field for the scalar types, and an object
+ * with a `position` and an `orientation` for poses. Adding a topic that already exists
+ * with the same type returns the existing channel.
+ *
+ * # Safety
+ *
+ * `topic_name` must be a valid NUL-terminated C string, and `channel_id` must be NULL or
+ * point to writable memory for an `int`.
+ */
+enum foxdbg_result_t foxdbg_add_rx_channel(const char *topic_name,
+                                           enum foxdbg_channel_type_t channel_type,
+                                           int *channel_id);
+
+/**
+ * Copies the latest value received on an rx channel into `data`, without waiting for one.
+ * `size` must be the size of the channel's type: a `bool`, `float`, `int32_t` or
+ * `foxdbg_pose_t`. `data` is left untouched until the first value is received.
+ *
+ * `is_new`, which may be NULL, is set to whether the value was received since the channel
+ * was last read.
+ *
+ * # Safety
+ *
+ * `data` must point to at least `size` writable bytes, and `is_new` must be NULL or point
+ * to writable memory for a `bool`.
+ */
+enum foxdbg_result_t foxdbg_get_rx_channel(int channel_id, void *data, size_t size, bool *is_new);
+
+/**
+ * Calls `callback` with every message Foxglove clients publish on `topic_name`, in the
+ * order they were received, registering the rx channel like `foxdbg_add_rx_channel` if it
+ * does not exist yet. The ID to unsubscribe with is stored in `subscription_id`.
+ *
+ * Callbacks run one at a time on a dedicated dispatch thread, never on the WebSocket
+ * server's threads, so they may block or call back into foxdbg. Messages wait for the
+ * dispatch thread in a queue whose depth and overflow policy are set by `rx_queue_depth`
+ * and `rx_overflow_policy` in `foxdbg_config_t`. Polling the channel with
+ * `foxdbg_get_rx_channel` keeps working alongside subscriptions.
+ *
+ * # Safety
+ *
+ * `topic_name` must be a valid NUL-terminated C string, and `subscription_id` must be NULL
+ * or point to writable memory for an `int`. `callback` must be safe to call with
+ * `user_data` from another thread until it is unsubscribed.
+ */
+enum foxdbg_result_t foxdbg_subscribe_rx(const char *topic_name,
+                                         enum foxdbg_channel_type_t channel_type,
+                                         foxdbg_rx_callback_t callback,
+                                         void *user_data,
+                                         int *subscription_id);
+
+/**
+ * Removes a subscription made with `foxdbg_subscribe_rx`. Once this returns, its callback
+ * is not running and will not be called again, so `user_data` may be freed. A callback may
+ * unsubscribe itself, in which case this returns without waiting for it.
+ */
+enum foxdbg_result_t foxdbg_unsubscribe_rx(int subscription_id);
+
+/**
+ * Registers a float parameter that can be tuned from the Parameters panel in Foxglove,
+ * starting at `default_value`. Values set from Foxglove are clamped to `min..=max`; pass
+ * `-INFINITY` and `INFINITY` to leave the parameter unbounded. Read the current value with
+ * `foxdbg_get_param_float`.
+ *
+ * Registering a name that already exists with the same type keeps its current value.
+ * Every change made from Foxglove is logged on `/foxdbg/parameters`, so recordings show
+ * when each parameter was tuned.
+ *
+ * # Safety
+ *
+ * `name` must be a valid NUL-terminated C string.
+ */
+enum foxdbg_result_t foxdbg_add_param_float(const char *name,
+                                            float default_value,
+                                            float min,
+                                            float max);
+
+/**
+ * Registers an integer parameter. See `foxdbg_add_param_float`.
+ *
+ * # Safety
+ *
+ * `name` must be a valid NUL-terminated C string.
+ */
+enum foxdbg_result_t foxdbg_add_param_int(const char *name,
+                                          int32_t default_value,
+                                          int32_t min,
+                                          int32_t max);
+
+/**
+ * Registers a boolean parameter, shown as a toggle in Foxglove. See
+ * `foxdbg_add_param_float`.
+ *
+ * # Safety
+ *
+ * `name` must be a valid NUL-terminated C string.
+ */
+enum foxdbg_result_t foxdbg_add_param_bool(const char *name, bool default_value);
+
+/**
+ * Copies the current value of a float parameter into `value`. Reading is cheap enough to
+ * do on every iteration of a control loop, and always returns a value as a whole.
+ *
+ * # Safety
+ *
+ * `name` must be a valid NUL-terminated C string, and `value` must point to writable
+ * memory for a `float`.
+ */
+enum foxdbg_result_t foxdbg_get_param_float(const char *name, float *value);
+
+/**
+ * Copies the current value of an integer parameter into `value`. See
+ * `foxdbg_get_param_float`.
+ *
+ * # Safety
+ *
+ * `name` must be a valid NUL-terminated C string, and `value` must point to writable
+ * memory for an `int32_t`.
+ */
+enum foxdbg_result_t foxdbg_get_param_int(const char *name, int32_t *value);
+
+/**
+ * Copies the current value of a boolean parameter into `value`. See
+ * `foxdbg_get_param_float`.
+ *
+ * # Safety
+ *
+ * `name` must be a valid NUL-terminated C string, and `value` must point to writable
+ * memory for a `bool`.
+ */
+enum foxdbg_result_t foxdbg_get_param_bool(const char *name, bool *value);
+
+/**
+ * Writes the current value of every parameter to `path` as a JSON object keyed by name,
+ * which can be edited by hand and read back with `foxdbg_load_params`.
+ *
+ * # Safety
+ *
+ * `path` must be a valid NUL-terminated C string.
+ */
+enum foxdbg_result_t foxdbg_save_params(const char *path);
+
+/**
+ * Sets parameters to the values in a JSON file written by `foxdbg_save_params`. Values are
+ * clamped to the range of their parameter, and values for parameters that are not
+ * registered yet replace their default when they are. Nothing is applied if the file holds
+ * a value of the wrong type for its parameter.
+ *
+ * # Safety
+ *
+ * `path` must be a valid NUL-terminated C string.
  */
-void foxdbg_write_channel(int channel_id, const void *data, size_t size);
+enum foxdbg_result_t foxdbg_load_params(const char *path);
 
 /**
- * Writes metadata for a channel, used for types like images to specify dimensions.
+ * Registers a service named `name` that Foxglove clients can call, for example from the
+ * Service Call panel, to trigger an action in the application. Each request is passed to
+ * `callback` as JSON, and the JSON it writes back is sent to the client as the response.
+ * `request_schema` is a JSON Schema describing the request, shown to clients so they can
+ * build one, or NULL to leave the request undescribed.
+ *
+ * Each service runs its callback on a thread of its own, one call at a time, never on the
+ * WebSocket server's threads. Callbacks of different services can therefore run at the
+ * same time, and must lock any state they share. A call that has not completed within `service_timeout_ms` of
+ * `foxdbg_config_t` is answered with an error, and its response is discarded once the
+ * callback returns. Services may be added before or after `foxdbg_init`, and stay
+ * registered for the lifetime of the process.
+ *
+ * # Safety
+ *
+ * `name` must be a valid NUL-terminated C string, and `request_schema` must be NULL or a
+ * valid NUL-terminated C string. `callback` must be safe to call with `user_data` from
+ * another thread for the lifetime of the process.
  */
-void foxdbg_write_channel_info(int channel_id, const void *data, size_t size);
+enum foxdbg_result_t foxdbg_add_service(const char *name,
+                                        const char *request_schema,
+                                        foxdbg_service_callback_t callback,
+                                        void *user_data);
 
 #ifdef __cplusplus
 }  // extern "C"
@@ -0,0 +1,435 @@
+/**
+ * C++ wrapper around foxdbg.h.
+ *
+ * Requires C++20 for std::span. Failed calls throw foxdbg::Error, carrying the
+ * foxdbg_result_t code and the message from foxdbg_last_error().
+ *
+ *     foxdbg::Session session;
+ *     foxdbg::Channel<foxdbg_cube_t> cubes("/cubes/test", 30);
+ *     cubes.write(std::span(cube_array));
+ */
+
+#ifndef FOXDBG_HPP
+#define FOXDBG_HPP
+
+#include "foxdbg.h"
+
+#include <algorithm>
+#include <cstdint>
+#include <cstring>
+#include <exception>
+#include <functional>
+#include <limits>
+#include <memory>
+#include <span>
+#include <stdexcept>
+#include <string>
+
+namespace foxdbg {
+
+/** Thrown when a foxdbg call fails. */
+class Error : public std::runtime_error {
+public:
+    Error(foxdbg_result_t code, const char *message)
+        : std::runtime_error(message ? message : "foxdbg call failed"), code_(code) {}
+
+    foxdbg_result_t code() const noexcept { return code_; }
+
+private:
+    foxdbg_result_t code_;
+};
+
+/** Throws foxdbg::Error if `result` is not FOXDBG_RESULT_OK. */
+inline void check(foxdbg_result_t result) {
+    if (result != FOXDBG_RESULT_OK) {
+        throw Error(result, foxdbg_last_error());
+    }
+}
+
+/**
+ * Initialises foxdbg on construction and shuts it down on destruction.
+ *
+ * Only one session should exist at a time, typically owned by main().
+ */
+class Session {
+public:
+    Session() { check(foxdbg_init()); }
+
+    explicit Session(const foxdbg_config_t &config) { check(foxdbg_init_with_config(&config)); }
+
+    ~Session() { foxdbg_shutdown(); }
+
+    Session(const Session &) = delete;
+    Session &operator=(const Session &) = delete;
+
+    /** Starts recording to `path`, or to a timestamped file if `path` is NULL. */
+    void start_recording(const char *path = nullptr,
+                         const foxdbg_mcap_options_t *options = nullptr) {
+        check(foxdbg_start_recording(path, options));
+    }
+
+    void stop_recording() { check(foxdbg_stop_recording()); }
+
+    void attach_file(const std::string &path, const std::string &media_type) {
+        check(foxdbg_attach_file(path.c_str(), media_type.c_str()));
+    }
+
+    void dump_blackbox(const std::string &path) { check(foxdbg_dump_blackbox(path.c_str())); }
+};
+
+/**
+ * Opens a frame on construction and commits it on destruction, so the writes the
+ * calling thread makes in between are published together with one timestamp.
+ *
+ *     {
+ *         foxdbg::Frame frame(sec, nsec);
+ *         cubes.write(std::span(cube_array));
+ *         pose.write(vehicle_pose);
+ *     }
+ */
+class Frame {
+public:
+    Frame(std::uint32_t sec, std::uint32_t nsec) : exceptions_(std::uncaught_exceptions()) {
+        check(foxdbg_frame_begin(sec, nsec));
+    }
+
+    /**
+     * Commits the frame, throwing foxdbg::Error if that fails. If the scope is being left
+     * because of an exception, the frame is discarded instead with foxdbg_frame_abort(),
+     * so a partly written frame is never published.
+     */
+    ~Frame() noexcept(false) {
+        if (!open_) {
+            return;
+        }
+        if (std::uncaught_exceptions() > exceptions_) {
+            foxdbg_frame_abort();
+        } else {
+            check(foxdbg_frame_commit());
+        }
+    }
+
+    Frame(const Frame &) = delete;
+    Frame &operator=(const Frame &) = delete;
+
+    /** Commits the frame early, reporting any error instead of ignoring it. */
+    void commit() {
+        open_ = false;
+        check(foxdbg_frame_commit());
+    }
+
+private:
+    int exceptions_;
+    bool open_ = true;
+};
+
+/** Maps the element type written to a Channel onto its channel type. */
+template <typename T>
+struct channel_traits;
+
+template <>
+struct channel_traits<float> {
+    static constexpr foxdbg_channel_type_t type = FOXDBG_CHANNEL_TYPE_FLOAT;
+};
+
+template <>
+struct channel_traits<std::int32_t> {
+    static constexpr foxdbg_channel_type_t type = FOXDBG_CHANNEL_TYPE_INTEGER;
+};
+
+template <>
+struct channel_traits<bool> {
+    static constexpr foxdbg_channel_type_t type = FOXDBG_CHANNEL_TYPE_BOOLEAN;
+};
+
+template <>
+struct channel_traits<foxdbg_vector4_t> {
+    static constexpr foxdbg_channel_type_t type = FOXDBG_CHANNEL_TYPE_POINTCLOUD;
+};
+
+template <>
+struct channel_traits<foxdbg_cube_t> {
+    static constexpr foxdbg_channel_type_t type = FOXDBG_CHANNEL_TYPE_CUBES;
+};
+
+template <>
+struct channel_traits<foxdbg_line_t> {
+    static constexpr foxdbg_channel_type_t type = FOXDBG_CHANNEL_TYPE_LINES;
+};
+
+template <>
+struct channel_traits<foxdbg_pose_t> {
+    static constexpr foxdbg_channel_type_t type = FOXDBG_CHANNEL_TYPE_POSE;
+};
+
+template <>
+struct channel_traits<foxdbg_transform_t> {
+    static constexpr foxdbg_channel_type_t type = FOXDBG_CHANNEL_TYPE_TRANSFORM;
+};
+
+template <>
+struct channel_traits<foxdbg_location_t> {
+    static constexpr foxdbg_channel_type_t type = FOXDBG_CHANNEL_TYPE_LOCATION;
+};
+
+/**
+ * A channel written with values of type `T`, whose size is computed from the type.
+ *
+ * Point clouds, cubes and lines take any number of elements. The other types take a
+ * single value. Channels may be created before the Session, and registering a topic that
+ * already exists with the same type shares the existing channel.
+ */
+template <typename T>
+class Channel {
+public:
+    explicit Channel(const std::string &topic, int target_hz = 0) {
+        check(foxdbg_add_channel(topic.c_str(), channel_traits<T>::type, target_hz, &id_));
+    }
+
+    int id() const noexcept { return id_; }
+
+    void write(std::span<const T> data) {
+        check(foxdbg_write_channel(id_, data.data(), data.size_bytes()));
+    }
+
+    void write(const T &value) { write(std::span<const T>(&value, 1)); }
+
+    /** Writes `data` stamped with the time it was captured. */
+    void write_stamped(std::uint32_t sec, std::uint32_t nsec, std::span<const T> data) {
+        check(foxdbg_write_channel_stamped(id_, sec, nsec, data.data(), data.size_bytes()));
+    }
+
+    void write_stamped(std::uint32_t sec, std::uint32_t nsec, const T &value) {
+        write_stamped(sec, nsec, std::span<const T>(&value, 1));
+    }
+
+    /** Sets the channel's display options. See foxdbg_channel_options_t. */
+    void set_options(const foxdbg_channel_options_t &options) {
+        check(foxdbg_write_channel_info(id_, &options, sizeof(options)));
+    }
+
+    foxdbg_channel_stats_t stats() const {
+        foxdbg_channel_stats_t stats;
+        check(foxdbg_get_channel_stats(id_, &stats));
+        return stats;
+    }
+
+    /** Removes the channel for every user of its topic. */
+    void remove() { check(foxdbg_remove_channel(id_)); }
+
+private:
+    int id_ = -1;
+};
+
+/**
+ * A channel that Foxglove clients publish to, received as values of type `T`: bool,
+ * float, std::int32_t or foxdbg_pose_t.
+ */
+template <typename T>
+class RxChannel {
+public:
+    explicit RxChannel(const std::string &topic) {
+        check(foxdbg_add_rx_channel(topic.c_str(), channel_traits<T>::type, &id_));
+    }
+
+    int id() const noexcept { return id_; }
+
+    /**
+     * Copies the latest value received into `value` without waiting, and returns whether
+     * it was received since the last call. `value` is left as it is until the first value
+     * is received.
+     */
+    bool get(T &value) {
+        bool is_new = false;
+        check(foxdbg_get_rx_channel(id_, &value, sizeof(T), &is_new));
+        return is_new;
+    }
+
+private:
+    int id_ = -1;
+};
+
+/**
+ * Calls a function with every value Foxglove clients publish on a topic, in order, for as
+ * long as it exists. The function runs on foxdbg's rx dispatch thread, and any exception it
+ * throws is discarded.
+ */
+template <typename T>
+class Subscription {
+public:
+    Subscription(const std::string &topic, std::function<void(const T &)> callback)
+        : callback_(std::make_unique<std::function<void(const T &)>>(std::move(callback))) {
+        check(foxdbg_subscribe_rx(topic.c_str(), channel_traits<T>::type, &Subscription::dispatch,
+                                  callback_.get(), &id_));
+    }
+
+    /** Unsubscribes, waiting for the function to return if it is running. */
+    ~Subscription() { foxdbg_unsubscribe_rx(id_); }
+
+    Subscription(const Subscription &) = delete;
+    Subscription &operator=(const Subscription &) = delete;
+
+    int id() const noexcept { return id_; }
+
+private:
+    static void dispatch(const void *data, size_t, void *user_data) {
+        try {
+            (*static_cast<std::function<void(const T &)> *>(user_data))(
+                *static_cast<const T *>(data));
+        } catch (...) {
+        }
+    }
+
+    std::unique_ptr<std::function<void(const T &)>> callback_;
+    int id_ = -1;
+};
+
+/** Maps the type of a Param onto the foxdbg functions that register and read it. */
+template <typename T>
+struct param_traits;
+
+template <>
+struct param_traits<float> {
+    static foxdbg_result_t add(const char *name, float value, float min, float max) {
+        return foxdbg_add_param_float(name, value, min, max);
+    }
+    static foxdbg_result_t get(const char *name, float *value) {
+        return foxdbg_get_param_float(name, value);
+    }
+};
+
+template <>
+struct param_traits<std::int32_t> {
+    static foxdbg_result_t add(const char *name, std::int32_t value, std::int32_t min,
+                               std::int32_t max) {
+        return foxdbg_add_param_int(name, value, min, max);
+    }
+    static foxdbg_result_t get(const char *name, std::int32_t *value) {
+        return foxdbg_get_param_int(name, value);
+    }
+};
+
+template <>
+struct param_traits<bool> {
+    static foxdbg_result_t add(const char *name, bool value, bool, bool) {
+        return foxdbg_add_param_bool(name, value);
+    }
+    static foxdbg_result_t get(const char *name, bool *value) {
+        return foxdbg_get_param_bool(name, value);
+    }
+};
+
+/**
+ * A parameter tunable from the Parameters panel in Foxglove: float, std::int32_t or bool.
+ * Values set from Foxglove are clamped to `min..max`, which bool parameters ignore.
+ */
+template <typename T>
+class Param {
+public:
+    Param(const std::string &name, T default_value,
+          T min = std::numeric_limits<T>::lowest(), T max = std::numeric_limits<T>::max())
+        : name_(name) {
+        check(param_traits<T>::add(name_.c_str(), default_value, min, max));
+    }
+
+    const std::string &name() const noexcept { return name_; }
+
+    /** Returns the current value. */
+    T get() const {
+        T value{};
+        check(param_traits<T>::get(name_.c_str(), &value));
+        return value;
+    }
+
+    operator T() const { return get(); }
+
+private:
+    std::string name_;
+};
+
+/** Writes the current value of every parameter to a JSON file at `path`. */
+inline void save_params(const std::string &path) { check(foxdbg_save_params(path.c_str())); }
+
+/** Sets parameters to the values in a JSON file at `path`. See foxdbg_load_params(). */
+inline void load_params(const std::string &path) { check(foxdbg_load_params(path.c_str())); }
+
+/**
+ * Answers a service call with the JSON response to a JSON request. Throwing fails the call,
+ * sending the exception's message to the client.
+ */
+using ServiceHandler = std::function<std::string(const std::string &request)>;
+
+/** Passes a service call from foxdbg to its ServiceHandler. */
+inline bool call_service(const char *request, size_t request_size, char *response,
+                         size_t response_capacity, size_t *response_size, void *user_data) {
+    std::string result;
+    bool ok = true;
+    try {
+        result = (*static_cast<ServiceHandler *>(user_data))(std::string(request, request_size));
+    } catch (const std::exception &e) {
+        result = e.what();
+        ok = false;
+    } catch (...) {
+        result = "unknown exception";
+        ok = false;
+    }
+    // A response that does not fit is reported to the client by foxdbg.
+    std::memcpy(response, result.data(), std::min(result.size(), response_capacity));
+    *response_size = result.size();
+    return ok;
+}
+
+/**
+ * Registers a service Foxglove clients can call, answered by `handler`. An empty
+ * `request_schema` leaves the request undescribed. Services stay registered for the lifetime
+ * of the process. See foxdbg_add_service().
+ */
+inline void add_service(const std::string &name, const std::string &request_schema,
+                        ServiceHandler handler) {
+    auto user_data = std::make_unique<ServiceHandler>(std::move(handler));
+    const char *schema = request_schema.empty() ? nullptr : request_schema.c_str();
+    check(foxdbg_add_service(name.c_str(), schema, &call_service, user_data.get()));
+    // Owned by the service from now on, which is never removed.
+    user_data.release();
+}
+
+/** A channel written with raw pixel data, which is JPEG compressed before publishing. */
+class ImageChannel {
+public:
+    explicit ImageChannel(const std::string &topic, int target_hz = 0) {
+        check(foxdbg_add_channel(topic.c_str(), FOXDBG_CHANNEL_TYPE_IMAGE, target_hz, &id_));
+    }
+
+    int id() const noexcept { return id_; }
+
+    /** Writes an image whose dimensions are given by `info`. */
+    void write(std::span<const std::uint8_t> pixels, const foxdbg_image_info_t &info) {
+        check(foxdbg_write_image(id_, &info, pixels.data(), pixels.size_bytes()));
+    }
+
+    void write_stamped(std::uint32_t sec, std::uint32_t nsec, std::span<const std::uint8_t> pixels,
+                       const foxdbg_image_info_t &info) {
+        check(foxdbg_write_image_stamped(id_, sec, nsec, &info, pixels.data(),
+                                         pixels.size_bytes()));
+    }
+
+    /** Sets the channel's display options. See foxdbg_channel_options_t. */
+    void set_options(const foxdbg_channel_options_t &options) {
+        check(foxdbg_write_channel_info(id_, &options, sizeof(options)));
+    }
+
+    foxdbg_channel_stats_t stats() const {
+        foxdbg_channel_stats_t stats;
+        check(foxdbg_get_channel_stats(id_, &stats));
+        return stats;
+    }
+
+    void remove() { check(foxdbg_remove_channel(id_)); }
+
+private:
+    int id_ = -1;
+};
+
+}  // namespace foxdbg
+
+#endif /* FOXDBG_HPP */
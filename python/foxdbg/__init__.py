@@ -0,0 +1,530 @@
+"""Python bindings for foxdbg-rs.
+
+Publishes into the same Foxglove session and recordings as the C API, through the
+foxdbg shared library:
+
+    import foxdbg
+    import numpy as np
+
+    foxdbg.init()
+    cloud = foxdbg.add_channel("/lidar/points", foxdbg.ChannelType.POINTCLOUD, 10)
+    cloud.write(np.random.rand(1000, 4).astype(np.float32))
+    camera = foxdbg.add_channel("/camera/front", foxdbg.ChannelType.IMAGE, 30)
+    camera.write(frame)  # (height, width, channels) uint8 array
+    foxdbg.shutdown()
+"""
+
+import ctypes
+import json
+from contextlib import contextmanager
+from os import fspath
+
+try:
+    import numpy as np
+except ImportError:  # numpy is only needed to write arrays
+    np = None
+
+from ._lib import lib
+from .types import (
+    ChannelType,
+    Compression,
+    OverflowPolicy,
+    Result,
+    foxdbg_channel_options_t,
+    foxdbg_channel_stats_t,
+    foxdbg_color_t,
+    foxdbg_config_t,
+    foxdbg_cube_t,
+    foxdbg_image_info_t,
+    foxdbg_line_t,
+    foxdbg_location_t,
+    foxdbg_mcap_options_t,
+    foxdbg_pose_t,
+    foxdbg_rx_callback_t,
+    foxdbg_service_callback_t,
+    foxdbg_transform_t,
+    foxdbg_vector3_t,
+    foxdbg_vector4_t,
+)
+
+__all__ = [
+    "Channel",
+    "ChannelType",
+    "Compression",
+    "FoxdbgError",
+    "OverflowPolicy",
+    "Param",
+    "Result",
+    "RxChannel",
+    "Subscription",
+    "add_channel",
+    "add_param",
+    "add_rx_channel",
+    "add_service",
+    "attach_data",
+    "attach_file",
+    "config_default",
+    "dump_blackbox",
+    "frame",
+    "init",
+    "load_params",
+    "save_params",
+    "set_time",
+    "shutdown",
+    "start_recording",
+    "stop_recording",
+    "subscribe_rx",
+    "write",
+    "write_metadata",
+    "foxdbg_channel_options_t",
+    "foxdbg_channel_stats_t",
+    "foxdbg_color_t",
+    "foxdbg_config_t",
+    "foxdbg_cube_t",
+    "foxdbg_image_info_t",
+    "foxdbg_line_t",
+    "foxdbg_location_t",
+    "foxdbg_mcap_options_t",
+    "foxdbg_pose_t",
+    "foxdbg_transform_t",
+    "foxdbg_vector3_t",
+    "foxdbg_vector4_t",
+]
+
+# Element type written to each channel type, used to convert Python values and numpy
+# arrays into the layout the library expects.
+_ELEMENT_TYPES = {
+    ChannelType.POINTCLOUD: foxdbg_vector4_t,
+    ChannelType.CUBES: foxdbg_cube_t,
+    ChannelType.LINES: foxdbg_line_t,
+    ChannelType.POSE: foxdbg_pose_t,
+    ChannelType.TRANSFORM: foxdbg_transform_t,
+    ChannelType.LOCATION: foxdbg_location_t,
+    ChannelType.FLOAT: ctypes.c_float,
+    ChannelType.INTEGER: ctypes.c_int32,
+    ChannelType.BOOLEAN: ctypes.c_bool,
+}
+
+_SCALAR_DTYPES = {
+    ChannelType.FLOAT: "float32",
+    ChannelType.INTEGER: "int32",
+    ChannelType.BOOLEAN: "bool",
+}
+
+# Callbacks of the services added with add_service().
+_services = []
+
+
+class FoxdbgError(Exception):
+    """Raised when a foxdbg call fails. `code` holds the foxdbg_result_t."""
+
+    def __init__(self, code, message):
+        super().__init__(message)
+        self.code = code
+
+
+def _check(result):
+    if result != Result.OK:
+        message = lib.foxdbg_last_error()
+        raise FoxdbgError(
+            Result(result), message.decode(errors="replace") if message else Result(result).name
+        )
+
+
+def _c_str(value):
+    return None if value is None else fspath(value).encode()
+
+
+def _stamp(timestamp):
+    """Splits a timestamp given as float seconds or a (sec, nsec) tuple."""
+    if isinstance(timestamp, tuple):
+        return timestamp
+    sec = int(timestamp)
+    return sec, int(round((timestamp - sec) * 1e9))
+
+
+def config_default():
+    """Returns a configuration populated with the defaults used by init()."""
+    return lib.foxdbg_config_default()
+
+
+def init(config=None):
+    """Starts the WebSocket server and recording, using `config` if given."""
+    if config is None:
+        _check(lib.foxdbg_init())
+    else:
+        _check(lib.foxdbg_init_with_config(ctypes.byref(config)))
+
+
+def shutdown():
+    _check(lib.foxdbg_shutdown())
+
+
+def start_recording(path=None, options=None):
+    options = None if options is None else ctypes.byref(options)
+    _check(lib.foxdbg_start_recording(_c_str(path), options))
+
+
+def stop_recording():
+    _check(lib.foxdbg_stop_recording())
+
+
+def write_metadata(name, entries):
+    """Adds a metadata record made of the key/value pairs in the `entries` dict."""
+    keys = (ctypes.c_char_p * len(entries))(*(str(k).encode() for k in entries))
+    values = (ctypes.c_char_p * len(entries))(*(str(v).encode() for v in entries.values()))
+    _check(lib.foxdbg_write_metadata(name.encode(), keys, values, len(entries)))
+
+
+def attach_file(path, media_type):
+    _check(lib.foxdbg_attach_file(_c_str(path), media_type.encode()))
+
+
+def attach_data(name, media_type, data):
+    data = bytes(data)
+    _check(lib.foxdbg_attach_data(name.encode(), media_type.encode(), data, len(data)))
+
+
+def dump_blackbox(path):
+    _check(lib.foxdbg_dump_blackbox(_c_str(path)))
+
+
+def set_time(timestamp):
+    """Sets the time messages are stamped with, as float seconds or (sec, nsec)."""
+    _check(lib.foxdbg_set_time(*_stamp(timestamp)))
+
+
+@contextmanager
+def frame(timestamp):
+    """Publishes the writes made by this thread inside the `with` block together, all
+    stamped with `timestamp` given as float seconds or (sec, nsec).
+
+    If the block raises, the frame is discarded instead, so a partly written frame is
+    never published.
+    """
+    _check(lib.foxdbg_frame_begin(*_stamp(timestamp)))
+    try:
+        yield
+    except BaseException:
+        _check(lib.foxdbg_frame_abort())
+        raise
+    _check(lib.foxdbg_frame_commit())
+
+
+class Channel:
+    """A channel created with add_channel()."""
+
+    def __init__(self, channel_id, topic, channel_type):
+        self.id = channel_id
+        self.topic = topic
+        self.type = ChannelType(channel_type)
+
+    def __repr__(self):
+        return f"Channel(id={self.id}, topic={self.topic!r}, type={self.type.name})"
+
+    def write(self, data, timestamp=None):
+        """Writes `data` to the channel, stamped with `timestamp` if given.
+
+        `data` may be a numpy array, a ctypes structure or array, a list of structures
+        or, for float, integer and boolean channels, a Python scalar. Point clouds take
+        an (N, 4) or (N, 3) float array and images a (height, width) or
+        (height, width, channels) uint8 array, whose image info is set from its shape.
+        """
+        info = self._image_info(data) if self.type == ChannelType.IMAGE else None
+        buffer = self._as_buffer(data)
+        pointer, size = ctypes.c_void_p(ctypes.addressof(buffer)), ctypes.sizeof(buffer)
+        if info is not None:
+            # Written together, so another thread cannot change the dimensions in between
+            if timestamp is None:
+                _check(lib.foxdbg_write_image(self.id, ctypes.byref(info), pointer, size))
+            else:
+                sec, nsec = _stamp(timestamp)
+                _check(
+                    lib.foxdbg_write_image_stamped(
+                        self.id, sec, nsec, ctypes.byref(info), pointer, size
+                    )
+                )
+        elif timestamp is None:
+            _check(lib.foxdbg_write_channel(self.id, pointer, size))
+        else:
+            sec, nsec = _stamp(timestamp)
+            _check(lib.foxdbg_write_channel_stamped(self.id, sec, nsec, pointer, size))
+
+    def write_info(self, info):
+        """Sets the channel info: a foxdbg_channel_options_t, or a foxdbg_image_info_t
+        for image channels."""
+        _check(lib.foxdbg_write_channel_info(self.id, ctypes.byref(info), ctypes.sizeof(info)))
+
+    def set_options(self, frame_id=None, entity_id_prefix=None, lifetime=0, frame_locked=False):
+        """Sets the channel's display options. `lifetime` is in seconds, 0 showing scene
+        entities until they are replaced."""
+        self.write_info(
+            foxdbg_channel_options_t(
+                _c_str(frame_id),
+                _c_str(entity_id_prefix),
+                int(round(lifetime * 1000)),
+                frame_locked,
+            )
+        )
+
+    def stats(self):
+        """Returns the number of writes published to and dropped from the channel."""
+        stats = foxdbg_channel_stats_t()
+        _check(lib.foxdbg_get_channel_stats(self.id, ctypes.byref(stats)))
+        return stats
+
+    def remove(self):
+        _check(lib.foxdbg_remove_channel(self.id))
+
+    def _image_info(self, data):
+        """Returns the image info of a numpy image, or None for raw pixel data written with
+        the channel's current info."""
+        if np is None or not isinstance(data, np.ndarray):
+            return None
+        if data.dtype != np.uint8 or data.ndim not in (2, 3):
+            raise ValueError("images must be (height, width[, channels]) uint8 arrays")
+        channels = 1 if data.ndim == 2 else data.shape[2]
+        return foxdbg_image_info_t(data.shape[1], data.shape[0], channels)
+
+    def _as_buffer(self, data):
+        """Returns a ctypes object holding `data` laid out as the channel's type."""
+        if isinstance(data, (ctypes.Structure, ctypes.Array, ctypes._SimpleCData)):
+            return data
+
+        element = _ELEMENT_TYPES.get(self.type)
+        if isinstance(data, (list, tuple)) and data and isinstance(data[0], ctypes.Structure):
+            return (element * len(data))(*data)
+        if self.type in _SCALAR_DTYPES and isinstance(data, (bool, int, float)):
+            return element(data)
+        if isinstance(data, (bytes, bytearray, memoryview)):
+            return (ctypes.c_uint8 * len(data)).from_buffer_copy(data)
+        if np is not None:
+            return self._array_buffer(data)
+        raise TypeError(f"cannot write {type(data).__name__} to a {self.type.name} channel")
+
+    def _array_buffer(self, data):
+        """Converts `data` to a numpy array of the channel's type, shared with ctypes."""
+        if self.type in _SCALAR_DTYPES:
+            data = np.asarray(data, dtype=_SCALAR_DTYPES[self.type])
+        elif self.type == ChannelType.POINTCLOUD:
+            data = np.asarray(data, dtype=np.float32)
+            if data.ndim == 2 and data.shape[1] == 3:
+                data = np.hstack([data, np.zeros((len(data), 1), dtype=np.float32)])
+        elif not isinstance(data, np.ndarray):
+            raise TypeError(f"cannot write {type(data).__name__} to a {self.type.name} channel")
+
+        data = np.ascontiguousarray(data)
+        if not data.flags.writeable:
+            data = data.copy()
+        # The ctypes array shares the numpy array's memory and keeps it alive.
+        return (ctypes.c_uint8 * data.nbytes).from_buffer(data)
+
+
+def add_channel(topic, channel_type, target_hz=0):
+    """Creates a channel, or returns the existing channel if `topic` already exists with
+    the same type. Writes arriving faster than `target_hz` are dropped."""
+    channel_id = ctypes.c_int()
+    _check(
+        lib.foxdbg_add_channel(
+            topic.encode(), int(channel_type), target_hz, ctypes.byref(channel_id)
+        )
+    )
+    return Channel(channel_id.value, topic, channel_type)
+
+
+class RxChannel:
+    """A channel that Foxglove clients publish to, created with add_rx_channel()."""
+
+    def __init__(self, channel_id, topic, channel_type):
+        self.id = channel_id
+        self.topic = topic
+        self.type = ChannelType(channel_type)
+        self._value = _ELEMENT_TYPES[self.type]()
+        self._received = False
+
+    def __repr__(self):
+        return f"RxChannel(id={self.id}, topic={self.topic!r}, type={self.type.name})"
+
+    def get(self):
+        """Returns the latest value received, or None before the first, along with whether
+        it was received since the last call. Never waits for a value."""
+        is_new = ctypes.c_bool()
+        _check(
+            lib.foxdbg_get_rx_channel(
+                self.id,
+                ctypes.byref(self._value),
+                ctypes.sizeof(self._value),
+                ctypes.byref(is_new),
+            )
+        )
+        self._received |= is_new.value
+        if not self._received:
+            return None, False
+        if self.type in _SCALAR_DTYPES:
+            return self._value.value, is_new.value
+        # Copied, since the buffer is overwritten by the next call.
+        return foxdbg_pose_t.from_buffer_copy(self._value), is_new.value
+
+
+def add_rx_channel(topic, channel_type):
+    """Creates a channel that Foxglove clients can publish boolean, float, integer or pose
+    values to, or returns the existing channel if `topic` already exists with the same
+    type."""
+    channel_id = ctypes.c_int()
+    _check(lib.foxdbg_add_rx_channel(topic.encode(), int(channel_type), ctypes.byref(channel_id)))
+    return RxChannel(channel_id.value, topic, channel_type)
+
+
+class Subscription:
+    """A callback subscribed with subscribe_rx(). Usable as a context manager that
+    unsubscribes on exit."""
+
+    def __init__(self, topic, channel_type, callback):
+        self.topic = topic
+        self.type = ChannelType(channel_type)
+        element_type = _ELEMENT_TYPES[self.type]
+        scalar = self.type in _SCALAR_DTYPES
+
+        def dispatch(data, _size, _user_data):
+            value = ctypes.cast(data, ctypes.POINTER(element_type)).contents
+            # Copied, since the data is only valid during the call.
+            callback(value.value if scalar else element_type.from_buffer_copy(value))
+
+        # Kept referenced for as long as the library may call it.
+        self._dispatch = foxdbg_rx_callback_t(dispatch)
+        subscription_id = ctypes.c_int()
+        _check(
+            lib.foxdbg_subscribe_rx(
+                topic.encode(),
+                int(channel_type),
+                self._dispatch,
+                None,
+                ctypes.byref(subscription_id),
+            )
+        )
+        self.id = subscription_id.value
+
+    def __repr__(self):
+        return f"Subscription(id={self.id}, topic={self.topic!r}, type={self.type.name})"
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.unsubscribe()
+
+    def unsubscribe(self):
+        """Stops the callback, waiting for it to return if it is running. Calling this
+        again does nothing."""
+        if self._dispatch is not None:
+            _check(lib.foxdbg_unsubscribe_rx(self.id))
+            self._dispatch = None
+
+
+def subscribe_rx(topic, channel_type, callback):
+    """Calls `callback` with every value Foxglove clients publish on `topic`, in order,
+    until the returned subscription is unsubscribed. The rx channel is created like
+    add_rx_channel() if needed.
+
+    The callback runs on the library's dispatch thread, so it should hand work to the
+    main thread rather than block for long. Exceptions it raises are printed and ignored."""
+    return Subscription(topic, channel_type, callback)
+
+
+class Param:
+    """A parameter tunable from the Parameters panel in Foxglove, created with
+    add_param()."""
+
+    def __init__(self, name, value_type):
+        self.name = name
+        self.type = value_type
+        self._get, self._value = {
+            float: (lib.foxdbg_get_param_float, ctypes.c_float),
+            int: (lib.foxdbg_get_param_int, ctypes.c_int32),
+            bool: (lib.foxdbg_get_param_bool, ctypes.c_bool),
+        }[value_type]
+
+    def __repr__(self):
+        return f"Param(name={self.name!r}, type={self.type.__name__})"
+
+    def get(self):
+        """Returns the current value."""
+        value = self._value()
+        _check(self._get(self.name.encode(), ctypes.byref(value)))
+        return value.value
+
+
+def add_param(name, default, min=None, max=None):
+    """Registers a parameter tunable from Foxglove, whose type follows `default`: a bool,
+    int or float. Values set from Foxglove are clamped to `min` and `max`, which default
+    to the whole range of the type and are ignored for bools. Registering a name again
+    keeps its current value."""
+    if isinstance(default, bool):
+        _check(lib.foxdbg_add_param_bool(name.encode(), default))
+        return Param(name, bool)
+    if isinstance(default, int):
+        _check(
+            lib.foxdbg_add_param_int(
+                name.encode(),
+                default,
+                -(2**31) if min is None else min,
+                2**31 - 1 if max is None else max,
+            )
+        )
+        return Param(name, int)
+    _check(
+        lib.foxdbg_add_param_float(
+            name.encode(),
+            default,
+            float("-inf") if min is None else min,
+            float("inf") if max is None else max,
+        )
+    )
+    return Param(name, float)
+
+
+def save_params(path):
+    """Writes the current value of every parameter to a JSON file at `path`."""
+    _check(lib.foxdbg_save_params(_c_str(path)))
+
+
+def load_params(path):
+    """Sets parameters to the values in a JSON file at `path`, as written by save_params().
+    Values for parameters not added yet replace their default once they are."""
+    _check(lib.foxdbg_load_params(_c_str(path)))
+
+
+def add_service(name, callback, request_schema=None):
+    """Registers a service Foxglove clients can call, for example from the Service Call
+    panel. `callback` is called with the decoded JSON request and returns the response,
+    which is encoded as JSON; returning None responds with an empty object. Raising an
+    exception fails the call, sending its message to the client. `request_schema` is a JSON
+    Schema describing the request, as a dict or a string.
+
+    The callback runs on a thread of the library's, and a call that takes longer than the
+    configured `service_timeout_ms` is answered with an error. Services stay registered for
+    the lifetime of the process."""
+    if isinstance(request_schema, dict):
+        request_schema = json.dumps(request_schema)
+
+    def call(request, request_size, response, response_capacity, response_size, _user_data):
+        try:
+            payload = ctypes.string_at(request, request_size)
+            result = callback(json.loads(payload) if payload else None)
+            data, ok = json.dumps({} if result is None else result).encode(), True
+        except Exception as e:
+            data, ok = str(e).encode(), False
+        # A response that does not fit is reported to the client by the library.
+        ctypes.memmove(response, data, min(len(data), response_capacity))
+        response_size[0] = len(data)
+        return ok
+
+    handler = foxdbg_service_callback_t(call)
+    _check(lib.foxdbg_add_service(name.encode(), _c_str(request_schema), handler, None))
+    # Kept referenced for as long as the library may call it, which is for good.
+    _services.append(handler)
+
+
+def write(channel, data, timestamp=None):
+    """Writes `data` to `channel`. See Channel.write."""
+    channel.write(data, timestamp)
@@ -0,0 +1,137 @@
+"""Loads the foxdbg shared library and declares the signatures of its functions."""
+
+import ctypes
+import ctypes.util
+import os
+import sys
+from pathlib import Path
+
+from .types import (
+    foxdbg_channel_stats_t,
+    foxdbg_clock_callback_t,
+    foxdbg_config_t,
+    foxdbg_image_info_t,
+    foxdbg_mcap_options_t,
+    foxdbg_rx_callback_t,
+    foxdbg_service_callback_t,
+)
+
+# Released packages name the library after foxdbg, cargo builds after the crate.
+_LIBRARY_NAMES = {
+    "win32": ["foxdbg.dll", "foxdbg_rs.dll"],
+    "darwin": ["libfoxdbg.dylib", "libfoxdbg_rs.dylib"],
+}
+
+
+def _candidates():
+    # An explicit path always wins.
+    if "FOXDBG_LIBRARY" in os.environ:
+        yield os.environ["FOXDBG_LIBRARY"]
+        return
+
+    names = _LIBRARY_NAMES.get(sys.platform, ["libfoxdbg.so", "libfoxdbg_rs.so"])
+    # Next to the package, as installed from a release, then in a cargo build of this
+    # repository, then wherever the system linker looks.
+    package_dir = Path(__file__).resolve().parent
+    repo_dir = package_dir.parent.parent
+    for directory in [package_dir, repo_dir / "target" / "release", repo_dir / "target" / "debug"]:
+        for name in names:
+            path = directory / name
+            if path.exists():
+                yield str(path)
+
+    for library in ["foxdbg", "foxdbg_rs"]:
+        found = ctypes.util.find_library(library)
+        if found:
+            yield found
+
+
+def _load():
+    tried = []
+    for candidate in _candidates():
+        try:
+            return ctypes.CDLL(candidate)
+        except OSError as e:
+            tried.append(f"{candidate}: {e}")
+    raise ImportError(
+        "Could not load the foxdbg shared library. Build it with `cargo build --release` "
+        "or set FOXDBG_LIBRARY to its path." + "".join(f"\n  {t}" for t in tried)
+    )
+
+
+lib = _load()
+
+_c_int = ctypes.c_int
+_result = ctypes.c_int
+_str = ctypes.c_char_p
+_ptr = ctypes.c_void_p
+_size = ctypes.c_size_t
+
+_SIGNATURES = {
+    "foxdbg_config_default": (foxdbg_config_t, []),
+    "foxdbg_last_error": (_str, []),
+    "foxdbg_init": (_result, []),
+    "foxdbg_init_with_config": (_result, [ctypes.POINTER(foxdbg_config_t)]),
+    "foxdbg_shutdown": (_result, []),
+    "foxdbg_start_recording": (_result, [_str, ctypes.POINTER(foxdbg_mcap_options_t)]),
+    "foxdbg_stop_recording": (_result, []),
+    "foxdbg_write_metadata": (
+        _result,
+        [_str, ctypes.POINTER(_str), ctypes.POINTER(_str), _size],
+    ),
+    "foxdbg_attach_file": (_result, [_str, _str]),
+    "foxdbg_attach_data": (_result, [_str, _str, _ptr, _size]),
+    "foxdbg_dump_blackbox": (_result, [_str]),
+    "foxdbg_set_clock_callback": (_result, [foxdbg_clock_callback_t, _ptr]),
+    "foxdbg_set_time": (_result, [ctypes.c_uint32, ctypes.c_uint32]),
+    "foxdbg_add_channel": (_result, [_str, _c_int, _c_int, ctypes.POINTER(_c_int)]),
+    "foxdbg_remove_channel": (_result, [_c_int]),
+    "foxdbg_write_channel": (_result, [_c_int, _ptr, _size]),
+    "foxdbg_write_channel_stamped": (
+        _result,
+        [_c_int, ctypes.c_uint32, ctypes.c_uint32, _ptr, _size],
+    ),
+    "foxdbg_write_channel_info": (_result, [_c_int, _ptr, _size]),
+    "foxdbg_write_image": (
+        _result,
+        [_c_int, ctypes.POINTER(foxdbg_image_info_t), _ptr, _size],
+    ),
+    "foxdbg_write_image_stamped": (
+        _result,
+        [
+            _c_int,
+            ctypes.c_uint32,
+            ctypes.c_uint32,
+            ctypes.POINTER(foxdbg_image_info_t),
+            _ptr,
+            _size,
+        ],
+    ),
+    "foxdbg_write_topic": (_result, [_str, _ptr, _size]),
+    "foxdbg_write_topic_info": (_result, [_str, _ptr, _size]),
+    "foxdbg_add_rx_channel": (_result, [_str, _c_int, ctypes.POINTER(_c_int)]),
+    "foxdbg_get_rx_channel": (_result, [_c_int, _ptr, _size, ctypes.POINTER(ctypes.c_bool)]),
+    "foxdbg_subscribe_rx": (
+        _result,
+        [_str, _c_int, foxdbg_rx_callback_t, _ptr, ctypes.POINTER(_c_int)],
+    ),
+    "foxdbg_unsubscribe_rx": (_result, [_c_int]),
+    "foxdbg_add_param_float": (_result, [_str, ctypes.c_float, ctypes.c_float, ctypes.c_float]),
+    "foxdbg_add_param_int": (_result, [_str, ctypes.c_int32, ctypes.c_int32, ctypes.c_int32]),
+    "foxdbg_add_param_bool": (_result, [_str, ctypes.c_bool]),
+    "foxdbg_get_param_float": (_result, [_str, ctypes.POINTER(ctypes.c_float)]),
+    "foxdbg_get_param_int": (_result, [_str, ctypes.POINTER(ctypes.c_int32)]),
+    "foxdbg_get_param_bool": (_result, [_str, ctypes.POINTER(ctypes.c_bool)]),
+    "foxdbg_save_params": (_result, [_str]),
+    "foxdbg_load_params": (_result, [_str]),
+    "foxdbg_add_service": (_result, [_str, _str, foxdbg_service_callback_t, _ptr]),
+    "foxdbg_frame_begin": (_result, [ctypes.c_uint32, ctypes.c_uint32]),
+    "foxdbg_frame_commit": (_result, []),
+    "foxdbg_frame_abort": (_result, []),
+    "foxdbg_get_channel_stats": (_result, [_c_int, ctypes.POINTER(foxdbg_channel_stats_t)]),
+}
+
+for _name, (_restype, _argtypes) in _SIGNATURES.items():
+    _function = getattr(lib, _name)
+    _function.restype = _restype
+    _function.argtypes = _argtypes
@@ -0,0 +1,212 @@
+"""ctypes mirrors of the C types in foxdbg.h (src/types.rs).
+
+Field order and types must match the Rust definitions exactly, since these structures
+are passed to the library by pointer.
+"""
+
+import ctypes
+from enum import IntEnum
+
+
+class foxdbg_color_t(ctypes.Structure):
+    _fields_ = [
+        ("r", ctypes.c_float),
+        ("g", ctypes.c_float),
+        ("b", ctypes.c_float),
+        ("a", ctypes.c_float),
+    ]
+
+
+class foxdbg_vector3_t(ctypes.Structure):
+    _fields_ = [
+        ("x", ctypes.c_float),
+        ("y", ctypes.c_float),
+        ("z", ctypes.c_float),
+    ]
+
+
+class foxdbg_vector4_t(ctypes.Structure):
+    _fields_ = [
+        ("x", ctypes.c_float),
+        ("y", ctypes.c_float),
+        ("z", ctypes.c_float),
+        ("w", ctypes.c_float),
+    ]
+
+
+class foxdbg_pose_t(ctypes.Structure):
+    _fields_ = [
+        ("position", foxdbg_vector3_t),
+        ("orientation", foxdbg_vector3_t),
+        ("color", foxdbg_color_t),
+    ]
+
+
+class foxdbg_cube_t(ctypes.Structure):
+    _fields_ = [
+        ("position", foxdbg_vector3_t),
+        ("size", foxdbg_vector3_t),
+        ("orientation", foxdbg_vector3_t),
+        ("color", foxdbg_color_t),
+    ]
+
+
+class foxdbg_transform_t(ctypes.Structure):
+    """A transform between two coordinate frames.
+
+    The frame ids are C strings, so the bytes objects assigned to them must be kept
+    alive until the transform has been written.
+    """
+
+    _fields_ = [
+        ("id", ctypes.c_char_p),
+        ("parent_id", ctypes.c_char_p),
+        ("position", foxdbg_vector3_t),
+        ("orientation", foxdbg_vector3_t),
+    ]
+
+
+class foxdbg_line_t(ctypes.Structure):
+    _fields_ = [
+        ("start", foxdbg_vector3_t),
+        ("end", foxdbg_vector3_t),
+        ("color", foxdbg_color_t),
+        ("thickness", ctypes.c_float),
+    ]
+
+
+class foxdbg_location_t(ctypes.Structure):
+    _fields_ = [
+        ("timestamp_sec", ctypes.c_uint32),
+        ("timestamp_nsec", ctypes.c_uint32),
+        ("latitude", ctypes.c_double),
+        ("longitude", ctypes.c_double),
+        ("altitude", ctypes.c_double),
+    ]
+
+
+class foxdbg_image_info_t(ctypes.Structure):
+    _fields_ = [
+        ("width", ctypes.c_int32),
+        ("height", ctypes.c_int32),
+        ("channels", ctypes.c_int32),
+    ]
+
+
+class foxdbg_channel_stats_t(ctypes.Structure):
+    _fields_ = [
+        ("written", ctypes.c_uint64),
+        ("dropped", ctypes.c_uint64),
+    ]
+
+
+class foxdbg_channel_options_t(ctypes.Structure):
+    """Display options of a channel, set with Channel.set_options().
+
+    The strings are copied by the library, so need only stay alive for the call.
+    """
+
+    _fields_ = [
+        ("frame_id", ctypes.c_char_p),
+        ("entity_id_prefix", ctypes.c_char_p),
+        ("lifetime_ms", ctypes.c_uint32),
+        ("frame_locked", ctypes.c_bool),
+    ]
+
+
+class ChannelType(IntEnum):
+    """Mirror of foxdbg_channel_type_t."""
+
+    IMAGE = 0
+    POINTCLOUD = 1
+    CUBES = 2
+    LINES = 3
+    POSE = 4
+    TRANSFORM = 5
+    LOCATION = 6
+    FLOAT = 7
+    INTEGER = 8
+    BOOLEAN = 9
+
+
+class Result(IntEnum):
+    """Mirror of foxdbg_result_t."""
+
+    OK = 0
+    INVALID_ARGUMENT = 1
+    NOT_INITIALISED = 2
+    NOT_ENABLED = 3
+    UNKNOWN_CHANNEL = 4
+    SIZE_MISMATCH = 5
+    MISSING_CHANNEL_INFO = 6
+    ENCODE_FAILED = 7
+    IO_ERROR = 8
+    RECORDING_FAILED = 9
+    SERVER_FAILED = 10
+    INTERNAL_ERROR = 11
+    CHANNEL_CONFLICT = 12
+    UNKNOWN_PARAMETER = 13
+
+
+class Compression(IntEnum):
+    """Mirror of foxdbg_compression_t."""
+
+    NONE = 0
+    LZ4 = 1
+    ZSTD = 2
+
+
+class OverflowPolicy(IntEnum):
+    """Mirror of foxdbg_overflow_policy_t."""
+
+    DROP_OLDEST = 0
+    DROP_NEWEST = 1
+
+
+class foxdbg_mcap_options_t(ctypes.Structure):
+    _fields_ = [
+        ("compression", ctypes.c_int),
+        ("compression_level", ctypes.c_uint32),
+        ("chunk_size", ctypes.c_uint64),
+        ("emit_message_indexes", ctypes.c_bool),
+    ]
+
+
+class foxdbg_config_t(ctypes.Structure):
+    """Configuration passed to init. Obtain one with config_default()."""
+
+    _fields_ = [
+        ("host", ctypes.c_char_p),
+        ("port", ctypes.c_uint16),
+        ("server_name", ctypes.c_char_p),
+        ("recording_dir", ctypes.c_char_p),
+        ("enable_server", ctypes.c_bool),
+        ("enable_recording", ctypes.c_bool),
+        ("max_file_size", ctypes.c_uint64),
+        ("max_file_duration_sec", ctypes.c_uint32),
+        ("mcap", foxdbg_mcap_options_t),
+        ("enable_blackbox", ctypes.c_bool),
+        ("blackbox_duration_sec", ctypes.c_uint32),
+        ("blackbox_max_bytes", ctypes.c_uint64),
+        ("enable_crash_safety", ctypes.c_bool),
+        ("install_signal_handlers", ctypes.c_bool),
+        ("flush_interval_ms", ctypes.c_uint32),
+        ("rx_queue_depth", ctypes.c_uint32),
+        ("rx_overflow_policy", ctypes.c_int),
+        ("service_timeout_ms", ctypes.c_uint32),
+    ]
+
+
+foxdbg_clock_callback_t = ctypes.CFUNCTYPE(ctypes.c_uint64, ctypes.c_void_p)
+
+foxdbg_rx_callback_t = ctypes.CFUNCTYPE(None, ctypes.c_void_p, ctypes.c_size_t, ctypes.c_void_p)
+
+foxdbg_service_callback_t = ctypes.CFUNCTYPE(
+    ctypes.c_bool,
+    ctypes.c_void_p,
+    ctypes.c_size_t,
+    ctypes.c_void_p,
+    ctypes.c_size_t,
+    ctypes.POINTER(ctypes.c_size_t),
+    ctypes.c_void_p,
+)
@@ -22,15 +22,34 @@ for T in "${TARGETS[@]}"; do
   PKG_DIR="dist/${OUT}"
   mkdir -p "${PKG_DIR}/include" "${PKG_DIR}/lib" "${PKG_DIR}/cmake"
 
-  cp include/foxdbg.h "${PKG_DIR}/include/"
+  cp include/foxdbg.h include/foxdbg.hpp "${PKG_DIR}/include/"
   if [[ "$T" == *windows-msvc ]]; then
-    cp "target/${T}/release/foxdbg.lib" "${PKG_DIR}/lib/"
+    cp "target/${T}/release/foxdbg_rs.lib" "${PKG_DIR}/lib/foxdbg.lib"
   else
-    cp "target/${T}/release/libfoxdbg.a" "${PKG_DIR}/lib/"
+    cp "target/${T}/release/libfoxdbg_rs.a" "${PKG_DIR}/lib/libfoxdbg.a"
   fi
   [[ -f cmake/foxdbgConfig.cmake ]] && cp cmake/foxdbgConfig.cmake "${PKG_DIR}/cmake/"
 
-  (cd dist && zip -r "${OUT}.zip" "${OUT}")
+  # Shared library, also bundled into the Python package so it loads without installing.
+  # Cargo names it after the crate, so it is renamed to match the static library.
+  case "$T" in
+    *windows-msvc) BUILT="foxdbg_rs.dll" SHARED="foxdbg.dll" ;;
+    *apple-darwin) BUILT="libfoxdbg_rs.dylib" SHARED="libfoxdbg.dylib" ;;
+    *) BUILT="libfoxdbg_rs.so" SHARED="libfoxdbg.so" ;;
+  esac
+  if [[ "$SHARED" == *.so ]]; then
+    # Installed under its soname, with the unversioned name linkers look for pointing at it
+    SONAME="$(objdump -p "target/${T}/release/${BUILT}" | awk '/SONAME/ {print $2}')"
+    cp "target/${T}/release/${BUILT}" "${PKG_DIR}/lib/${SONAME}"
+    ln -s "${SONAME}" "${PKG_DIR}/lib/${SHARED}"
+  else
+    cp "target/${T}/release/${BUILT}" "${PKG_DIR}/lib/${SHARED}"
+  fi
+  mkdir -p "${PKG_DIR}/python"
+  cp -r python/foxdbg python/pyproject.toml "${PKG_DIR}/python/"
+  cp "target/${T}/release/${BUILT}" "${PKG_DIR}/python/foxdbg/${SHARED}"
+
+  (cd dist && zip -ry "${OUT}.zip" "${OUT}")
   echo "Packaged dist/${OUT}.zip"
 done
 
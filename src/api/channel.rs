@@ -0,0 +1,229 @@
+use super::ImageInfo;
+use super::kind::{ChannelKind, RxKind};
+use crate::channels::{self, ChannelKey, ChannelOptions, dispatch, manager, rx};
+use crate::error::Result;
+use crate::{foxdbg_channel_stats_t, foxdbg_channel_type_t};
+use foxglove::ChannelId;
+use foxglove::schemas::Timestamp;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::mem;
+use std::mem::MaybeUninit;
+
+/// A channel carrying a single kind of data, selected by one of the markers in `kind`.
+///
+/// The channel is registered in the same registry as channels added from C, so C code can
+/// write to it by topic and vice versa.
+#[derive(Debug)]
+pub struct TypedChannel<K: ChannelKind> {
+    id: ChannelId,
+    kind: PhantomData<fn() -> K>,
+}
+
+impl<K: ChannelKind> TypedChannel<K> {
+    /// Registers a channel on `topic`, or returns the existing channel if the topic is
+    /// already registered with the same type. See `foxdbg_add_channel`.
+    pub fn new(topic: &str, target_hz: i32) -> Result<Self> {
+        let id = manager::add_channel(topic, K::CHANNEL_TYPE, target_hz)?;
+        Ok(TypedChannel {
+            id: ChannelId::new(id),
+            kind: PhantomData,
+        })
+    }
+
+    pub fn id(&self) -> ChannelId {
+        self.id
+    }
+
+    /// Writes `data` to the channel, stamped with the current time.
+    pub fn write(&self, data: &K::Data<'_>) -> Result<()> {
+        K::write(self.id, None, data)
+    }
+
+    /// Writes `data` to the channel, stamped with the time it was captured. See
+    /// `foxdbg_write_channel_stamped`.
+    pub fn write_stamped(&self, timestamp: Timestamp, data: &K::Data<'_>) -> Result<()> {
+        K::write(self.id, Some(timestamp), data)
+    }
+
+    /// Replaces the channel's display options, such as the frame its data is expressed
+    /// in. See `foxdbg_channel_options_t`.
+    pub fn set_options(&self, options: ChannelOptions) -> Result<()> {
+        channels::set_options(ChannelKey::Id(self.id), options)
+    }
+
+    /// Returns the number of writes published to and dropped from the channel.
+    pub fn stats(&self) -> Result<foxdbg_channel_stats_t> {
+        channels::stats(ChannelKey::Id(self.id))
+    }
+
+    /// Removes the channel. See `foxdbg_remove_channel`.
+    pub fn remove(self) -> Result<()> {
+        manager::remove_channel(self.id)
+    }
+}
+
+impl<K: ChannelKind> Clone for TypedChannel<K> {
+    fn clone(&self) -> Self {
+        TypedChannel {
+            id: self.id,
+            kind: PhantomData,
+        }
+    }
+}
+
+/// A channel carrying images, which are JPEG compressed before being published.
+///
+/// Unlike the C API, the image info is passed with every write, so the size of the pixel
+/// data is always checked against the dimensions it was captured with.
+#[derive(Debug, Clone)]
+pub struct ImageChannel {
+    id: ChannelId,
+}
+
+impl ImageChannel {
+    /// Registers an image channel on `topic`, or returns the existing channel if the topic
+    /// is already registered as an image channel. See `foxdbg_add_channel`.
+    pub fn new(topic: &str, target_hz: i32) -> Result<Self> {
+        let channel_type = foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_IMAGE;
+        let id = manager::add_channel(topic, channel_type, target_hz)?;
+        Ok(ImageChannel {
+            id: ChannelId::new(id),
+        })
+    }
+
+    pub fn id(&self) -> ChannelId {
+        self.id
+    }
+
+    /// Writes an image with the dimensions in `info`, stamped with the current time.
+    pub fn write(&self, pixels: &[u8], info: ImageInfo) -> Result<()> {
+        self.write_image(None, pixels, info)
+    }
+
+    /// Writes an image with the dimensions in `info`, stamped with the time it was
+    /// captured.
+    pub fn write_stamped(
+        &self,
+        timestamp: Timestamp,
+        pixels: &[u8],
+        info: ImageInfo,
+    ) -> Result<()> {
+        self.write_image(Some(timestamp), pixels, info)
+    }
+
+    /// Replaces the channel's display options, such as the frame its data is expressed
+    /// in. See `foxdbg_channel_options_t`.
+    pub fn set_options(&self, options: ChannelOptions) -> Result<()> {
+        channels::set_options(ChannelKey::Id(self.id), options)
+    }
+
+    /// Returns the number of writes published to and dropped from the channel.
+    pub fn stats(&self) -> Result<foxdbg_channel_stats_t> {
+        channels::stats(ChannelKey::Id(self.id))
+    }
+
+    /// Removes the channel. See `foxdbg_remove_channel`.
+    pub fn remove(self) -> Result<()> {
+        manager::remove_channel(self.id)
+    }
+
+    fn write_image(
+        &self,
+        timestamp: Option<Timestamp>,
+        pixels: &[u8],
+        info: ImageInfo,
+    ) -> Result<()> {
+        let pixels_ptr = pixels.as_ptr() as *const c_void;
+        unsafe {
+            channels::write_image(
+                ChannelKey::Id(self.id),
+                timestamp,
+                &info,
+                pixels_ptr,
+                pixels.len(),
+            )
+        }
+    }
+}
+
+/// A channel that Foxglove clients publish to, such as a setpoint or a mode switch set from
+/// the Publish panel. Reading it never waits for a new value.
+#[derive(Debug)]
+pub struct RxChannel<K: RxKind> {
+    id: u64,
+    kind: PhantomData<fn() -> K>,
+}
+
+impl<K: RxKind> RxChannel<K> {
+    /// Registers an rx channel on `topic`, or returns the existing channel if the topic is
+    /// already registered with the same type. See `foxdbg_add_rx_channel`.
+    pub fn new(topic: &str) -> Result<Self> {
+        let id = rx::add_rx_channel(topic, K::CHANNEL_TYPE)?;
+        Ok(RxChannel {
+            id,
+            kind: PhantomData,
+        })
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the latest value received, along with whether it was received since the
+    /// channel was last read, or `None` if no value has been received yet.
+    pub fn get(&self) -> Result<Option<(K::Value, bool)>> {
+        let mut value = MaybeUninit::<K::Value>::uninit();
+        let size = mem::size_of::<K::Value>();
+        let is_new = unsafe { rx::get_rx_channel(self.id, value.as_mut_ptr().cast(), size) }?;
+        // The value is only written, with the channel's type, when one has been received.
+        Ok(is_new.map(|is_new| (unsafe { value.assume_init() }, is_new)))
+    }
+
+    /// Calls `callback` with every value subsequently received, in order, from the rx
+    /// dispatch thread, until the returned subscription is dropped. See
+    /// `foxdbg_subscribe_rx`.
+    pub fn subscribe<F>(&self, mut callback: F) -> Result<RxSubscription>
+    where
+        F: FnMut(K::Value) + Send + 'static,
+    {
+        let id = dispatch::subscribe(
+            self.id,
+            Box::new(move |data, _size| {
+                // The dispatcher passes values of the channel's type, which `K` matches.
+                callback(unsafe { data.cast::<K::Value>().read_unaligned() })
+            }),
+        )?;
+        Ok(RxSubscription { id })
+    }
+}
+
+impl<K: RxKind> Clone for RxChannel<K> {
+    fn clone(&self) -> Self {
+        RxChannel {
+            id: self.id,
+            kind: PhantomData,
+        }
+    }
+}
+
+/// A callback subscribed to an rx channel with `RxChannel::subscribe`. Dropping it
+/// unsubscribes, waiting for the callback to return if it is running.
+#[must_use = "dropping a subscription unsubscribes it"]
+pub struct RxSubscription {
+    id: u64,
+}
+
+impl RxSubscription {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl Drop for RxSubscription {
+    fn drop(&mut self) {
+        if let Err(e) = dispatch::unsubscribe(self.id) {
+            log::warn!("Failed to unsubscribe rx subscription {}: {}", self.id, e);
+        }
+    }
+}
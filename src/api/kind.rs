@@ -0,0 +1,149 @@
+//! Marker types selecting the kind of data a `TypedChannel` carries.
+//!
+//! Each marker fixes the channel type the channel is registered with and the Rust type
+//! written to it, so `TypedChannel<Cubes>::write` takes a `&[Cube]` and works out its size
+//! itself.
+
+use super::{Cube, Line, Vector4};
+use crate::channels::{self, ChannelKey};
+use crate::error::Result;
+use crate::foxdbg_channel_type_t;
+use crate::foxdbg_transform_t;
+use foxglove::ChannelId;
+use foxglove::schemas::Timestamp;
+use std::ffi::c_void;
+use std::mem;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// The kind of data carried by a `TypedChannel`. Implemented by the markers in this module.
+pub trait ChannelKind: sealed::Sealed {
+    /// The channel type the channel is registered with.
+    const CHANNEL_TYPE: foxdbg_channel_type_t;
+
+    /// The data written to the channel in a single call.
+    type Data<'a>: ?Sized;
+
+    #[doc(hidden)]
+    fn write(
+        channel_id: ChannelId,
+        timestamp: Option<Timestamp>,
+        data: &Self::Data<'_>,
+    ) -> Result<()>;
+}
+
+/// Writes `data` as the raw bytes the C writers expect.
+fn write_raw<T: ?Sized>(
+    channel_id: ChannelId,
+    timestamp: Option<Timestamp>,
+    data: &T,
+) -> Result<()> {
+    let key = ChannelKey::Id(channel_id);
+    let size = mem::size_of_val(data);
+    // The pointer and size come from a reference, so they describe valid, readable memory
+    // laid out as the channel's type.
+    unsafe { channels::write(key, timestamp, data as *const T as *const c_void, size) }
+}
+
+macro_rules! channel_kind {
+    ($(#[$doc:meta])* $kind:ident, $channel_type:ident, $data:ty) => {
+        $(#[$doc])*
+        #[derive(Debug)]
+        pub enum $kind {}
+
+        impl sealed::Sealed for $kind {}
+
+        impl ChannelKind for $kind {
+            const CHANNEL_TYPE: foxdbg_channel_type_t = foxdbg_channel_type_t::$channel_type;
+            type Data<'a> = $data;
+
+            fn write(channel_id: ChannelId, timestamp: Option<Timestamp>, data: &$data) -> Result<()> {
+                write_raw(channel_id, timestamp, data)
+            }
+        }
+    };
+}
+
+channel_kind!(
+    /// A single `f32` value.
+    Float, FOXDBG_CHANNEL_TYPE_FLOAT, f32
+);
+channel_kind!(
+    /// A single `i32` value.
+    Integer, FOXDBG_CHANNEL_TYPE_INTEGER, i32
+);
+channel_kind!(
+    /// A single `bool` value.
+    Boolean, FOXDBG_CHANNEL_TYPE_BOOLEAN, bool
+);
+channel_kind!(
+    /// A point cloud of `Vector4` points, whose `w` is the point's intensity.
+    PointCloud, FOXDBG_CHANNEL_TYPE_POINTCLOUD, [Vector4]
+);
+channel_kind!(
+    /// A set of cubes drawn in the 3D panel.
+    Cubes, FOXDBG_CHANNEL_TYPE_CUBES, [Cube]
+);
+channel_kind!(
+    /// A set of lines drawn in the 3D panel.
+    Lines, FOXDBG_CHANNEL_TYPE_LINES, [Line]
+);
+channel_kind!(
+    /// A single pose drawn as an arrow in the 3D panel.
+    Pose, FOXDBG_CHANNEL_TYPE_POSE, super::Pose
+);
+channel_kind!(
+    /// A GPS location fix.
+    Location, FOXDBG_CHANNEL_TYPE_LOCATION, super::Location
+);
+
+/// A transform between two coordinate frames.
+#[derive(Debug)]
+pub enum Transform {}
+
+impl sealed::Sealed for Transform {}
+
+impl ChannelKind for Transform {
+    const CHANNEL_TYPE: foxdbg_channel_type_t =
+        foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_TRANSFORM;
+    type Data<'a> = super::Transform<'a>;
+
+    fn write(
+        channel_id: ChannelId,
+        timestamp: Option<Timestamp>,
+        data: &super::Transform,
+    ) -> Result<()> {
+        let transform = foxdbg_transform_t {
+            id: data.id.as_ptr(),
+            parent_id: data.parent_id.as_ptr(),
+            position: data.position,
+            orientation: data.orientation,
+        };
+        write_raw(channel_id, timestamp, &transform)
+    }
+}
+
+/// A kind of data that Foxglove clients can publish to the application, received with an
+/// `RxChannel`.
+pub trait RxKind: ChannelKind {
+    /// The value received on the channel.
+    type Value: Copy;
+}
+
+impl RxKind for Float {
+    type Value = f32;
+}
+
+impl RxKind for Integer {
+    type Value = i32;
+}
+
+impl RxKind for Boolean {
+    type Value = bool;
+}
+
+impl RxKind for Pose {
+    type Value = super::Pose;
+}
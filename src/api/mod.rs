@@ -0,0 +1,160 @@
+//! Safe Rust API.
+//!
+//! Rust crates can depend on foxdbg-rs directly and log through these types instead of the
+//! C functions. Both APIs share the same channels, server and recording, so Rust and C code
+//! running in one process publish into the same Foxglove session.
+
+mod channel;
+pub mod kind;
+mod param;
+
+pub use channel::{ImageChannel, RxChannel, RxSubscription, TypedChannel};
+pub use kind::{ChannelKind, RxKind};
+pub use param::Param;
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::recording::McapOptions;
+use crate::{channels, clock, core, params, services};
+use crate::{
+    foxdbg_color_t, foxdbg_cube_t, foxdbg_image_info_t, foxdbg_line_t, foxdbg_location_t,
+    foxdbg_pose_t, foxdbg_vector3_t, foxdbg_vector4_t,
+};
+use foxglove::schemas::Timestamp;
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::path::Path;
+
+pub type Color = foxdbg_color_t;
+pub type Vector3 = foxdbg_vector3_t;
+pub type Vector4 = foxdbg_vector4_t;
+pub type Pose = foxdbg_pose_t;
+pub type Cube = foxdbg_cube_t;
+pub type Line = foxdbg_line_t;
+pub type Location = foxdbg_location_t;
+pub type ImageInfo = foxdbg_image_info_t;
+
+/// A transform between two coordinate frames, written to a `TypedChannel<kind::Transform>`.
+///
+/// This is the borrowed counterpart of `foxdbg_transform_t`, whose frame ids are raw
+/// pointers.
+#[derive(Debug, Clone, Copy)]
+pub struct Transform<'a> {
+    pub id: &'a CStr,
+    pub parent_id: &'a CStr,
+    pub position: Vector3,
+    pub orientation: Vector3,
+}
+
+/// A running foxdbg-rs session, started by `Foxdbg::init` and shut down when dropped.
+///
+/// Channels do not borrow the session: they can be created before it starts, and writes
+/// made while no session is running are published to nothing.
+#[derive(Debug)]
+pub struct Foxdbg {
+    /// Whether this handle started the session, and so shuts it down when dropped.
+    owned: bool,
+}
+
+impl Foxdbg {
+    /// Starts the WebSocket server and recording as configured by `config`.
+    ///
+    /// If foxdbg-rs was already initialised, for example by C code in the same process, the
+    /// running session is left as it is and the returned handle does not shut it down.
+    pub fn init(config: Config) -> Result<Foxdbg> {
+        crate::init_logger();
+        let owned = core::init(&config)?;
+        Ok(Foxdbg { owned })
+    }
+
+    /// Starts recording to a new MCAP file, replacing any recording in progress. See
+    /// `foxdbg_start_recording`.
+    pub fn start_recording(
+        &self,
+        path: Option<&Path>,
+        options: Option<&McapOptions>,
+    ) -> Result<()> {
+        core::start_recording(path, options)
+    }
+
+    /// Stops the recording in progress, closing its MCAP file.
+    pub fn stop_recording(&self) {
+        core::stop_recording();
+    }
+
+    /// Adds a metadata record to the session. See `foxdbg_write_metadata`.
+    pub fn write_metadata(&self, name: &str, entries: BTreeMap<String, String>) -> Result<()> {
+        core::add_metadata(name, entries)
+    }
+
+    /// Attaches the file at `path` to the session. See `foxdbg_attach_file`.
+    pub fn attach_file(&self, path: &Path, media_type: &str) -> Result<()> {
+        core::attach_file(path, media_type)
+    }
+
+    /// Attaches `data` to the session under `name`. See `foxdbg_attach_data`.
+    pub fn attach_data(&self, name: &str, media_type: &str, data: Vec<u8>) -> Result<()> {
+        core::attach_data(name, media_type, data)
+    }
+
+    /// Writes the contents of the blackbox to a new MCAP file at `path`.
+    pub fn dump_blackbox(&self, path: &Path) -> Result<()> {
+        core::dump_blackbox(path)
+    }
+
+    /// Sets the current time, replacing the system clock. See `foxdbg_set_time`.
+    pub fn set_time(&self, timestamp: Timestamp) {
+        clock::set_time(timestamp);
+    }
+
+    /// Opens a frame on the calling thread, stamping its writes with `timestamp` until
+    /// `commit_frame`. See `foxdbg_frame_begin`.
+    pub fn begin_frame(&self, timestamp: Timestamp) -> Result<()> {
+        channels::frame::begin(timestamp)
+    }
+
+    /// Publishes the writes queued in the calling thread's frame together. See
+    /// `foxdbg_frame_commit`.
+    pub fn commit_frame(&self) -> Result<()> {
+        channels::frame::commit()
+    }
+
+    /// Closes the calling thread's frame without publishing its writes. See
+    /// `foxdbg_frame_abort`.
+    pub fn abort_frame(&self) -> Result<()> {
+        channels::frame::abort()
+    }
+
+    /// Writes the current value of every parameter to a JSON file at `path`. See
+    /// `foxdbg_save_params`.
+    pub fn save_params(&self, path: &Path) -> Result<()> {
+        params::save_params(path)
+    }
+
+    /// Sets parameters to the values in a JSON file at `path`. See `foxdbg_load_params`.
+    pub fn load_params(&self, path: &Path) -> Result<()> {
+        params::load_params(path)
+    }
+
+    /// Registers a service Foxglove clients can call, answered by `handler` with the JSON
+    /// response to each JSON request, or an error message. See `foxdbg_add_service`.
+    pub fn add_service<F>(&self, name: &str, request_schema: Option<&str>, handler: F) -> Result<()>
+    where
+        F: FnMut(&[u8]) -> std::result::Result<Vec<u8>, String> + Send + 'static,
+    {
+        services::add_service(name, request_schema, Box::new(handler))
+    }
+
+    /// Shuts the session down, closing the recording and stopping the server.
+    pub fn shutdown(self) {
+        // Dropping the handle does the work.
+    }
+}
+
+impl Drop for Foxdbg {
+    fn drop(&mut self) {
+        if self.owned {
+            core::shutdown();
+        }
+    }
+}
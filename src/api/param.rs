@@ -0,0 +1,50 @@
+use crate::error::Result;
+use crate::params::{self, ParamType};
+use std::marker::PhantomData;
+
+/// A parameter tunable from the Parameters panel in Foxglove, holding an `f32`, `i32` or
+/// `bool`.
+pub struct Param<T: ParamType> {
+    name: String,
+    kind: PhantomData<fn() -> T>,
+}
+
+impl<T: ParamType> Param<T> {
+    /// Registers a parameter that can be set to any value of its type. See
+    /// `foxdbg_add_param_float`.
+    pub fn new(name: &str, default: T) -> Result<Self> {
+        Self::register(name, default, f64::NEG_INFINITY, f64::INFINITY)
+    }
+
+    /// Registers a parameter whose values set from Foxglove are clamped to `min..=max`.
+    pub fn with_range(name: &str, default: T, min: T, max: T) -> Result<Self> {
+        let (min, max) = (min.into_value().as_f64(), max.into_value().as_f64());
+        Self::register(name, default, min, max)
+    }
+
+    fn register(name: &str, default: T, min: f64, max: f64) -> Result<Self> {
+        params::add_param(name, default.into_value(), min, max)?;
+        Ok(Param {
+            name: name.to_owned(),
+            kind: PhantomData,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the current value.
+    pub fn get(&self) -> Result<T> {
+        params::get_param(&self.name)
+    }
+}
+
+impl<T: ParamType> Clone for Param<T> {
+    fn clone(&self) -> Self {
+        Param {
+            name: self.name.clone(),
+            kind: PhantomData,
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use crate::error::{Error, Result};
+use crate::foxdbg_overflow_policy_t;
+use crate::state::RxValue;
+use crate::util::{LazyThread, UserData};
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, VecDeque};
+use std::ffi::c_void;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+
+/// Name of the thread subscription callbacks run on.
+const DISPATCH_THREAD_NAME: &str = "foxdbg-rx-dispatch";
+
+/// Default number of messages queued for dispatch, used until foxdbg is initialised.
+const DEFAULT_QUEUE_DEPTH: usize = 256;
+
+/// A subscriber's callback, called with a pointer to the received value and its size.
+pub type Callback = Box<dyn FnMut(*const c_void, usize) + Send>;
+
+struct Subscription {
+    channel_id: u64,
+    /// Taken out while the callback runs, so the subscriptions lock is not held meanwhile.
+    callback: Option<Callback>,
+}
+
+/// A received message waiting to be handed to a subscriber.
+struct Delivery {
+    subscription_id: u64,
+    value: RxValue,
+}
+
+struct Queue {
+    deliveries: VecDeque<Delivery>,
+    depth: usize,
+    policy: foxdbg_overflow_policy_t,
+    /// Whether messages have been dropped since the queue last drained, so the overflow is
+    /// only reported once.
+    overflowing: bool,
+}
+
+impl Queue {
+    /// Queues `delivery`, making room by the overflow policy if the queue is full.
+    fn push(&mut self, delivery: Delivery) {
+        if self.deliveries.len() >= self.depth {
+            if !self.overflowing {
+                log::warn!(
+                    "Rx dispatch queue is full, dropping the {} messages until it drains",
+                    match self.policy {
+                        foxdbg_overflow_policy_t::FOXDBG_OVERFLOW_DROP_OLDEST => "oldest",
+                        foxdbg_overflow_policy_t::FOXDBG_OVERFLOW_DROP_NEWEST => "newest",
+                    }
+                );
+                self.overflowing = true;
+            }
+            match self.policy {
+                foxdbg_overflow_policy_t::FOXDBG_OVERFLOW_DROP_OLDEST => {
+                    self.deliveries.pop_front();
+                }
+                foxdbg_overflow_policy_t::FOXDBG_OVERFLOW_DROP_NEWEST => return,
+            }
+        }
+        self.deliveries.push_back(delivery);
+    }
+}
+
+static SUBSCRIPTIONS: Lazy<Mutex<HashMap<u64, Subscription>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(0);
+
+static QUEUE: Mutex<Queue> = Mutex::new(Queue {
+    deliveries: VecDeque::new(),
+    depth: DEFAULT_QUEUE_DEPTH,
+    policy: foxdbg_overflow_policy_t::FOXDBG_OVERFLOW_DROP_OLDEST,
+    overflowing: false,
+});
+
+/// Signalled whenever a delivery is queued.
+static QUEUED: Condvar = Condvar::new();
+
+/// Held by the dispatch thread for as long as a callback runs, so unsubscribing can wait
+/// for a running callback to return.
+static DISPATCHING: Mutex<()> = Mutex::new(());
+
+static DISPATCH_THREAD: LazyThread = LazyThread::new(DISPATCH_THREAD_NAME);
+
+/// Wraps a C callback and the `user_data` it was registered with.
+pub fn c_callback(
+    callback: unsafe extern "C" fn(*const c_void, usize, *mut c_void),
+    user_data: *mut c_void,
+) -> Callback {
+    let user_data = UserData(user_data);
+    Box::new(move |data, size| unsafe { callback(data, size, user_data.get()) })
+}
+
+/// Sets how many messages are queued for dispatch, and what happens to messages received
+/// while the queue is full. Messages already queued are kept.
+pub fn configure(depth: usize, policy: foxdbg_overflow_policy_t) {
+    let mut queue = QUEUE
+        .lock()
+        .expect("Failed to lock rx dispatch queue mutex");
+    queue.depth = depth.max(1);
+    queue.policy = policy;
+}
+
+/// Calls `callback` with every message subsequently received on the rx channel
+/// `channel_id`, in order, from the dispatch thread. Returns the id to unsubscribe with.
+///
+/// # Errors
+///
+/// Returns an error if the dispatch thread could not be started.
+pub fn subscribe(channel_id: u64, callback: Callback) -> Result<u64> {
+    DISPATCH_THREAD.ensure_started(dispatch_loop)?;
+
+    let id = NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed);
+    SUBSCRIPTIONS
+        .lock()
+        .expect("Failed to lock rx subscriptions mutex")
+        .insert(
+            id,
+            Subscription {
+                channel_id,
+                callback: Some(callback),
+            },
+        );
+    Ok(id)
+}
+
+/// Removes a subscription. Once this returns, its callback is not running and will not be
+/// called again, unless this is called from the callback itself.
+///
+/// # Errors
+///
+/// Returns an error if no subscription has the given id.
+pub fn unsubscribe(subscription_id: u64) -> Result<()> {
+    let subscription = SUBSCRIPTIONS
+        .lock()
+        .expect("Failed to lock rx subscriptions mutex")
+        .remove(&subscription_id)
+        .ok_or_else(|| {
+            Error::InvalidArgument(format!("no rx subscription has id {}", subscription_id))
+        })?;
+
+    // A callback that unsubscribes itself would otherwise wait for itself to return.
+    if !DISPATCH_THREAD.is_current() {
+        drop(
+            DISPATCHING
+                .lock()
+                .expect("Failed to lock rx dispatching mutex"),
+        );
+    }
+    drop(subscription);
+    Ok(())
+}
+
+/// Queues `value`, received on the rx channel `channel_id`, for every subscription to it.
+pub fn publish(channel_id: u64, value: RxValue) {
+    let subscription_ids: Vec<u64> = SUBSCRIPTIONS
+        .lock()
+        .expect("Failed to lock rx subscriptions mutex")
+        .iter()
+        .filter(|(_, subscription)| subscription.channel_id == channel_id)
+        .map(|(id, _)| *id)
+        .collect();
+    if subscription_ids.is_empty() {
+        return;
+    }
+
+    let mut queue = QUEUE
+        .lock()
+        .expect("Failed to lock rx dispatch queue mutex");
+    for subscription_id in subscription_ids {
+        queue.push(Delivery {
+            subscription_id,
+            value,
+        });
+    }
+    QUEUED.notify_one();
+}
+
+fn dispatch_loop() {
+    loop {
+        let delivery = {
+            let mut queue = QUEUE
+                .lock()
+                .expect("Failed to lock rx dispatch queue mutex");
+            loop {
+                if let Some(delivery) = queue.deliveries.pop_front() {
+                    break delivery;
+                }
+                queue.overflowing = false;
+                queue = QUEUED
+                    .wait(queue)
+                    .expect("Failed to lock rx dispatch queue mutex");
+            }
+        };
+        dispatch(delivery);
+    }
+}
+
+/// Calls the callback of the delivery's subscription, if it is still subscribed.
+fn dispatch(delivery: Delivery) {
+    let _dispatching = DISPATCHING
+        .lock()
+        .expect("Failed to lock rx dispatching mutex");
+
+    let Some(mut callback) = SUBSCRIPTIONS
+        .lock()
+        .expect("Failed to lock rx subscriptions mutex")
+        .get_mut(&delivery.subscription_id)
+        .and_then(|subscription| subscription.callback.take())
+    else {
+        return;
+    };
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| match delivery.value {
+        RxValue::Boolean(value) => call(&mut callback, &value),
+        RxValue::Float(value) => call(&mut callback, &value),
+        RxValue::Integer(value) => call(&mut callback, &value),
+        RxValue::Pose(value) => call(&mut callback, &value),
+    }));
+    if result.is_err() {
+        log::error!(
+            "Rx subscription {} panicked in its callback",
+            delivery.subscription_id
+        );
+    }
+
+    // The callback is only put back if it did not unsubscribe itself while running.
+    if let Some(subscription) = SUBSCRIPTIONS
+        .lock()
+        .expect("Failed to lock rx subscriptions mutex")
+        .get_mut(&delivery.subscription_id)
+    {
+        subscription.callback = Some(callback);
+    }
+}
+
+fn call<T>(callback: &mut Callback, value: &T) {
+    callback(value as *const T as *const c_void, mem::size_of::<T>());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    fn queue(depth: usize, policy: foxdbg_overflow_policy_t) -> Queue {
+        Queue {
+            deliveries: VecDeque::new(),
+            depth,
+            policy,
+            overflowing: false,
+        }
+    }
+
+    /// Pushes a float delivery for each value, and returns the values left queued.
+    fn push_all(queue: &mut Queue, values: &[f32]) -> Vec<f32> {
+        for &value in values {
+            queue.push(Delivery {
+                subscription_id: 0,
+                value: RxValue::Float(value),
+            });
+        }
+        queue
+            .deliveries
+            .iter()
+            .map(|delivery| match delivery.value {
+                RxValue::Float(value) => value,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drops_the_oldest_messages_when_full() {
+        let policy = foxdbg_overflow_policy_t::FOXDBG_OVERFLOW_DROP_OLDEST;
+        let mut queue = queue(3, policy);
+        assert_eq!(
+            push_all(&mut queue, &[1.0, 2.0, 3.0, 4.0, 5.0]),
+            [3.0, 4.0, 5.0]
+        );
+        assert!(queue.overflowing);
+    }
+
+    #[test]
+    fn drops_the_newest_messages_when_full() {
+        let policy = foxdbg_overflow_policy_t::FOXDBG_OVERFLOW_DROP_NEWEST;
+        let mut queue = queue(3, policy);
+        assert_eq!(
+            push_all(&mut queue, &[1.0, 2.0, 3.0, 4.0, 5.0]),
+            [1.0, 2.0, 3.0]
+        );
+        assert!(queue.overflowing);
+    }
+
+    #[test]
+    fn calls_subscribers_in_order_on_the_dispatch_thread() {
+        // Far above the ids of registered rx channels, so no other test publishes to it.
+        let channel_id = u64::MAX - 22;
+        let (sender, receiver) = mpsc::channel();
+        let callback: Callback = Box::new(move |data, size| {
+            assert_eq!(size, mem::size_of::<f32>());
+            let value = unsafe { *(data as *const f32) };
+            let thread = thread::current().name().map(str::to_owned);
+            sender.send((value, thread)).unwrap();
+        });
+        let subscription_id = subscribe(channel_id, callback).unwrap();
+
+        for value in [1.0, 2.0, 3.0] {
+            publish(channel_id, RxValue::Float(value));
+        }
+        for expected in [1.0, 2.0, 3.0] {
+            let (value, thread) = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(value, expected);
+            assert_eq!(thread.as_deref(), Some(DISPATCH_THREAD_NAME));
+        }
+
+        unsubscribe(subscription_id).unwrap();
+        publish(channel_id, RxValue::Float(4.0));
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+}
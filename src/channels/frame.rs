@@ -0,0 +1,265 @@
+use crate::error::{Error, Result};
+use crate::state::{self, ChannelState};
+use foxglove::ChannelId;
+use foxglove::schemas::Timestamp;
+use std::cell::RefCell;
+use std::ffi::c_void;
+use std::sync::PoisonError;
+
+use super::writer;
+
+thread_local! {
+    /// The frame open on this thread, if any.
+    ///
+    /// Frames are per thread, so a thread batching a perception cycle does not capture
+    /// writes made concurrently by other threads, which are published immediately.
+    static FRAME: RefCell<Option<Frame>> = const { RefCell::new(None) };
+}
+
+/// Writes queued between `begin` and `commit`, all stamped with the frame's timestamp.
+#[derive(Debug)]
+struct Frame {
+    timestamp: Timestamp,
+    messages: Vec<QueuedMessage>,
+}
+
+/// A message encoded when it was written, waiting to be logged when its frame commits.
+#[derive(Debug)]
+struct QueuedMessage {
+    channel_id: ChannelId,
+    timestamp: Timestamp,
+    data: Vec<u8>,
+}
+
+/// Opens a frame on the calling thread. Until `commit`, writes made on this thread are
+/// stamped with `timestamp` and queued instead of being published.
+///
+/// # Errors
+///
+/// Returns an error if a frame is already open on this thread. That frame stays open.
+pub fn begin(timestamp: Timestamp) -> Result<()> {
+    FRAME.with_borrow_mut(|frame| {
+        if frame.is_some() {
+            return Err(Error::InvalidArgument(
+                "a frame is already open on this thread".to_owned(),
+            ));
+        }
+        *frame = Some(Frame {
+            timestamp,
+            messages: Vec::new(),
+        });
+        Ok(())
+    })
+}
+
+/// Returns the timestamp of the frame open on the calling thread, if any.
+pub fn timestamp() -> Option<Timestamp> {
+    FRAME.with_borrow(|frame| frame.as_ref().map(|frame| frame.timestamp))
+}
+
+/// Encodes a write and queues it in the frame open on the calling thread. The data is
+/// copied, so the caller's buffer may be reused before the frame commits.
+///
+/// # Safety
+///
+/// `data` must point to at least `size` readable bytes laid out as the channel's type.
+///
+/// # Errors
+///
+/// Returns an error if no frame is open on this thread, or the write could not be
+/// encoded. The rest of the frame is unaffected.
+pub unsafe fn queue(
+    channel_id: ChannelId,
+    channel_state: &mut ChannelState,
+    timestamp: Timestamp,
+    data: *const c_void,
+    size: usize,
+) -> Result<()> {
+    FRAME.with_borrow_mut(|frame| {
+        let frame = frame
+            .as_mut()
+            .ok_or_else(|| Error::InvalidArgument("no frame is open on this thread".to_owned()))?;
+        let encoded = unsafe { writer::encode_channel(channel_state, timestamp, data, size) }?;
+        if let Some(data) = encoded {
+            frame.messages.push(QueuedMessage {
+                channel_id,
+                timestamp,
+                data,
+            });
+        }
+        Ok(())
+    })
+}
+
+/// Closes the frame open on the calling thread, discarding its queued writes. They never
+/// took a slot in their channel's target rate, so the next write is not held back.
+///
+/// # Errors
+///
+/// Returns an error if no frame is open on this thread.
+pub fn abort() -> Result<()> {
+    FRAME
+        .with_borrow_mut(Option::take)
+        .map(drop)
+        .ok_or_else(|| Error::InvalidArgument("no frame is open on this thread".to_owned()))
+}
+
+/// Closes the frame open on the calling thread, publishing its queued writes in the order
+/// they were made while holding the `CHANNELS` lock once for the whole batch.
+///
+/// # Errors
+///
+/// Returns an error if no frame is open on this thread, or if a channel written in the
+/// frame was removed before the commit. The writes to every other channel are still
+/// published, and the frame is closed either way.
+pub fn commit() -> Result<()> {
+    let frame = FRAME
+        .with_borrow_mut(Option::take)
+        .ok_or_else(|| Error::InvalidArgument("no frame is open on this thread".to_owned()))?;
+
+    let mut channels = state::CHANNELS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let mut result = Ok(());
+    for message in frame.messages {
+        let published = match channels.get_mut(&message.channel_id) {
+            Some(channel_state) => {
+                // Admitted again, since an earlier write in the frame may have taken the slot.
+                if channel_state.rate_limiter.admit(message.timestamp) {
+                    writer::log_message(channel_state, message.timestamp, &message.data)
+                } else {
+                    Ok(())
+                }
+            }
+            None => Err(Error::UnknownChannel(u64::from(message.channel_id) as i64)),
+        };
+        if result.is_ok() {
+            result = published;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::tests::{float_channel, stats, write};
+    use foxglove::{Context, Metadata, RawChannel, Sink, SinkId};
+    use std::sync::{Arc, Mutex};
+
+    /// Captures the log time of every message logged to a topic under `prefix`.
+    struct CaptureSink {
+        id: SinkId,
+        prefix: &'static str,
+        logged: Mutex<Vec<(String, u64)>>,
+    }
+
+    impl CaptureSink {
+        fn install(prefix: &'static str) -> Arc<Self> {
+            let sink = Arc::new(CaptureSink {
+                id: SinkId::next(),
+                prefix,
+                logged: Mutex::new(Vec::new()),
+            });
+            Context::get_default().add_sink(sink.clone());
+            sink
+        }
+
+        fn logged(&self) -> Vec<(String, u64)> {
+            self.logged.lock().unwrap().clone()
+        }
+    }
+
+    impl Sink for CaptureSink {
+        fn id(&self) -> SinkId {
+            self.id
+        }
+
+        fn log(
+            &self,
+            channel: &RawChannel,
+            _msg: &[u8],
+            metadata: &Metadata,
+        ) -> std::result::Result<(), foxglove::FoxgloveError> {
+            if channel.topic().starts_with(self.prefix) {
+                let topic = channel.topic().to_owned();
+                self.logged.lock().unwrap().push((topic, metadata.log_time));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn publishes_queued_writes_on_commit_stamped_with_the_frame_time() {
+        let sink = CaptureSink::install("/test/frame/commit/");
+        let a = float_channel("/test/frame/commit/a", 0);
+        let b = float_channel("/test/frame/commit/b", 0);
+
+        let frame_time = Timestamp::new(7, 500);
+        begin(frame_time).unwrap();
+        write(a, 1.0, None).unwrap();
+        write(b, 2.0, None).unwrap();
+        assert_eq!(stats(a).written, 0);
+        assert!(sink.logged().is_empty());
+
+        commit().unwrap();
+        let nanos = frame_time.total_nanos();
+        assert_eq!(
+            sink.logged(),
+            [
+                ("/test/frame/commit/a".to_owned(), nanos),
+                ("/test/frame/commit/b".to_owned(), nanos)
+            ]
+        );
+        assert_eq!((stats(a).written, stats(b).written), (1, 1));
+    }
+
+    #[test]
+    fn aborted_frames_publish_nothing() {
+        let sink = CaptureSink::install("/test/frame/abort/");
+        let channel_id = float_channel("/test/frame/abort/a", 0);
+        begin(Timestamp::new(1, 0)).unwrap();
+        write(channel_id, 1.0, None).unwrap();
+        abort().unwrap();
+
+        assert!(timestamp().is_none());
+        assert!(sink.logged().is_empty());
+        assert_eq!(stats(channel_id).written, 0);
+    }
+
+    #[test]
+    fn rejects_nested_and_unopened_frames() {
+        assert!(commit().is_err());
+        assert!(abort().is_err());
+
+        begin(Timestamp::new(1, 0)).unwrap();
+        assert!(begin(Timestamp::new(2, 0)).is_err());
+        assert_eq!(timestamp(), Some(Timestamp::new(1, 0)));
+        commit().unwrap();
+        assert!(commit().is_err());
+    }
+
+    #[test]
+    fn decimates_writes_to_one_channel_within_a_frame() {
+        let channel_id = float_channel("/test/frame/decimated", 10);
+        begin(Timestamp::new(1, 0)).unwrap();
+        write(channel_id, 1.0, None).unwrap();
+        write(channel_id, 2.0, None).unwrap();
+        commit().unwrap();
+
+        let stats = stats(channel_id);
+        assert_eq!((stats.written, stats.dropped), (1, 1));
+    }
+
+    #[test]
+    fn aborted_writes_do_not_use_up_rate_limit_slots() {
+        let channel_id = float_channel("/test/frame/abort_rate", 10);
+        begin(Timestamp::new(1, 0)).unwrap();
+        write(channel_id, 1.0, None).unwrap();
+        abort().unwrap();
+
+        write(channel_id, 2.0, Some(Timestamp::new(1, 10_000_000))).unwrap();
+        let stats = stats(channel_id);
+        assert_eq!((stats.written, stats.dropped), (1, 0));
+    }
+}
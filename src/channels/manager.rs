@@ -1,7 +1,10 @@
 use foxglove::schemas::{CompressedImage, FrameTransform, LocationFix, PointCloud, SceneUpdate};
-use foxglove::{Channel, Context};
+use foxglove::{Channel, ChannelId, Context};
+use std::sync::PoisonError;
 
+use super::rate_limit::RateLimiter;
 use super::schemas::{Bool, Float, Integer};
+use crate::error::{Error, Result};
 use crate::foxdbg_channel_type_t;
 
 use crate::state::{self, ChannelInfo, ChannelState};
@@ -12,59 +15,100 @@ use crate::state::{self, ChannelInfo, ChannelState};
 /// with the appropriate schema, and stores the channel's state in the global `CHANNELS`
 /// map. It returns a channel ID that can be used to publish data to the channel.
 ///
+/// Registering a topic that already exists with the same type returns the existing
+/// channel, so independent modules can each register the channels they write to. The
+/// existing channel keeps its target rate and channel info.
+///
 /// # Arguments
 ///
 /// * `topic_name` - The name of the topic to create.
 /// * `channel_type` - The type of data that will be published on the channel.
+/// * `target_hz` - The maximum rate at which writes are published, or 0 for no limit.
 ///
 /// # Returns
 ///
-/// The ID of the newly created channel.
+/// The ID of the new or existing channel.
+///
+/// # Errors
+///
+/// Returns an error if the topic is already registered with a different type.
 pub fn add_channel(
     topic_name: &str,
     channel_type: foxdbg_channel_type_t,
-) -> u64 {
-    match channel_type {
-        foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_FLOAT => {
-            Channel::<Float>::new(topic_name);
+    target_hz: i32,
+) -> Result<u64> {
+    // The lock is held until the channel is registered, so concurrent registrations of
+    // the same topic cannot both create a channel.
+    let mut channels = state::CHANNELS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(channel_id) = channels.topic_id(topic_name) {
+        let existing = channels
+            .get(&channel_id)
+            .expect("Topic index is out of date");
+        if existing.channel_type != channel_type {
+            return Err(Error::ChannelConflict(format!(
+                "topic '{}' is already registered as {:?}, not {:?}",
+                topic_name, existing.channel_type, channel_type
+            )));
         }
+        return Ok(channel_id.into());
+    }
+
+    let channel_id = match channel_type {
+        foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_FLOAT => Channel::<Float>::new(topic_name).id(),
         foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_IMAGE => {
-            Channel::<CompressedImage>::new(topic_name);
+            Channel::<CompressedImage>::new(topic_name).id()
         }
         foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_POINTCLOUD => {
-            Channel::<PointCloud>::new(topic_name);
+            Channel::<PointCloud>::new(topic_name).id()
         }
         foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_CUBES
         | foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_LINES
         | foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_POSE => {
-            Channel::<SceneUpdate>::new(topic_name);
+            Channel::<SceneUpdate>::new(topic_name).id()
         }
         foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_TRANSFORM => {
-            Channel::<FrameTransform>::new(topic_name);
+            Channel::<FrameTransform>::new(topic_name).id()
         }
         foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_LOCATION => {
-            Channel::<LocationFix>::new(topic_name);
+            Channel::<LocationFix>::new(topic_name).id()
         }
         foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_INTEGER => {
-            Channel::<Integer>::new(topic_name);
-        }
-        foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_BOOLEAN => {
-            Channel::<Bool>::new(topic_name);
+            Channel::<Integer>::new(topic_name).id()
         }
+        foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_BOOLEAN => Channel::<Bool>::new(topic_name).id(),
     };
 
-    let channel_id = Context::get_default()
-        .get_channel_by_topic(topic_name)
-        .unwrap()
-        .id();
-
-    let mut channels = state::CHANNELS.lock().unwrap();
     let state = ChannelState {
-        channel_type: channel_type,
-        channel_info: ChannelInfo::NoInfo(),
-        channel_topic: topic_name.to_owned() 
+        channel_type,
+        channel_info: ChannelInfo::default(),
+        channel_topic: topic_name.to_owned(),
+        rate_limiter: RateLimiter::new(target_hz),
     };
 
     channels.insert(channel_id, state);
-    channel_id.into()
+    Ok(channel_id.into())
+}
+
+/// Removes a channel, so it can no longer be written to.
+///
+/// The Foxglove channel is closed, which unadvertises it to connected clients and stops
+/// recording it. Its topic can be registered again afterwards, with any type.
+///
+/// # Errors
+///
+/// Returns an error if no channel has the given ID.
+pub fn remove_channel(channel_id: ChannelId) -> Result<()> {
+    let mut channels = state::CHANNELS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let state = channels
+        .remove(&channel_id)
+        .ok_or(Error::UnknownChannel(u64::from(channel_id) as i64))?;
+
+    if let Some(channel) = Context::get_default().get_channel_by_topic(&state.channel_topic) {
+        channel.close();
+    }
+    Ok(())
 }
@@ -1,3 +1,257 @@
+pub mod dispatch;
+pub mod frame;
 pub mod manager;
+pub mod options;
+pub mod rate_limit;
+pub mod rx;
 pub mod schemas;
 pub mod writer;
+
+use crate::clock;
+use crate::error::{Error, Result};
+use crate::state::{self, ChannelRegistry, ChannelState};
+use crate::{foxdbg_channel_stats_t, foxdbg_image_info_t};
+use foxglove::ChannelId;
+use foxglove::schemas::Timestamp;
+use std::ffi::c_void;
+use std::mem;
+use std::sync::PoisonError;
+
+pub use options::ChannelOptions;
+
+/// Identifies a registered channel, either by its id or by its topic.
+#[derive(Debug, Clone, Copy)]
+pub enum ChannelKey<'a> {
+    Id(ChannelId),
+    Topic(&'a str),
+}
+
+impl ChannelKey<'_> {
+    fn lookup<'r>(
+        &self,
+        channels: &'r mut ChannelRegistry,
+    ) -> Result<(ChannelId, &'r mut ChannelState)> {
+        let channel_id = match *self {
+            ChannelKey::Id(id) => id,
+            ChannelKey::Topic(topic) => channels
+                .topic_id(topic)
+                .ok_or_else(|| Error::UnknownTopic(topic.to_owned()))?,
+        };
+        let channel_state = channels
+            .get_mut(&channel_id)
+            .ok_or(Error::UnknownChannel(u64::from(channel_id) as i64))?;
+        Ok((channel_id, channel_state))
+    }
+}
+
+/// Runs `f` on the state of the channel identified by `key`, holding the `CHANNELS` lock
+/// for the duration of the call.
+pub fn with_channel<R>(
+    key: ChannelKey,
+    f: impl FnOnce(&mut ChannelState) -> Result<R>,
+) -> Result<R> {
+    let mut channels = state::CHANNELS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let (_, channel_state) = key.lookup(&mut channels)?;
+    f(channel_state)
+}
+
+/// Writes a data payload to the channel identified by `key`. See `write_locked`.
+///
+/// # Safety
+///
+/// `data` must point to at least `size` readable bytes laid out as the channel's type.
+pub unsafe fn write(
+    key: ChannelKey,
+    timestamp: Option<Timestamp>,
+    data: *const c_void,
+    size: usize,
+) -> Result<()> {
+    let stamp = stamp(timestamp);
+    let mut channels = state::CHANNELS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let (channel_id, channel_state) = key.lookup(&mut channels)?;
+    unsafe { write_locked(channel_id, channel_state, stamp, timestamp, data, size) }
+}
+
+/// Sets the dimensions of the image channel identified by `key` to `info` and writes an
+/// image with them. The `CHANNELS` lock is held across both, so a concurrent write cannot
+/// change the dimensions in between. See `write_locked`.
+///
+/// # Safety
+///
+/// `data` must point to at least `size` readable bytes of pixel data.
+pub unsafe fn write_image(
+    key: ChannelKey,
+    timestamp: Option<Timestamp>,
+    info: &foxdbg_image_info_t,
+    data: *const c_void,
+    size: usize,
+) -> Result<()> {
+    let stamp = stamp(timestamp);
+    let mut channels = state::CHANNELS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let (channel_id, channel_state) = key.lookup(&mut channels)?;
+    let info_ptr = info as *const foxdbg_image_info_t as *const c_void;
+    unsafe {
+        writer::write_channel_info(
+            channel_state,
+            info_ptr,
+            mem::size_of::<foxdbg_image_info_t>(),
+        )
+    }?;
+    unsafe { write_locked(channel_id, channel_state, stamp, timestamp, data, size) }
+}
+
+/// Returns the time a write given `timestamp` is stamped with: the timestamp itself, else
+/// that of the frame open on the calling thread, else the current time.
+///
+/// A custom clock calls into the application, which may call back into foxdbg, so this
+/// must be called before the `CHANNELS` lock is taken.
+pub fn stamp(timestamp: Option<Timestamp>) -> Timestamp {
+    timestamp
+        .or_else(frame::timestamp)
+        .unwrap_or_else(clock::now)
+}
+
+/// Writes a data payload to a channel whose state is already locked, stamped with `stamp`
+/// as returned by `stamp` for the write's own `timestamp`. If the calling thread has a
+/// frame open, the write is queued in it. Otherwise it is published immediately by
+/// `writer::write_channel`.
+///
+/// # Safety
+///
+/// `data` must point to at least `size` readable bytes laid out as the channel's type.
+pub unsafe fn write_locked(
+    channel_id: ChannelId,
+    channel_state: &mut ChannelState,
+    stamp: Timestamp,
+    timestamp: Option<Timestamp>,
+    data: *const c_void,
+    size: usize,
+) -> Result<()> {
+    match frame::timestamp() {
+        Some(_) => unsafe { frame::queue(channel_id, channel_state, stamp, data, size) },
+        None => unsafe { writer::write_channel(channel_state, stamp, timestamp, data, size) },
+    }
+}
+
+/// Writes metadata for the channel identified by `key`. See `writer::write_channel_info`.
+///
+/// # Safety
+///
+/// `data` must be NULL or point to at least `size` readable bytes laid out as the
+/// channel's info type.
+pub unsafe fn write_info(key: ChannelKey, data: *const c_void, size: usize) -> Result<()> {
+    with_channel(key, |channel_state| unsafe {
+        writer::write_channel_info(channel_state, data, size)
+    })
+}
+
+/// Returns the write counters of the channel identified by `key`.
+pub fn stats(key: ChannelKey) -> Result<foxdbg_channel_stats_t> {
+    with_channel(key, |channel_state| {
+        Ok(foxdbg_channel_stats_t {
+            written: channel_state.rate_limiter.written,
+            dropped: channel_state.rate_limiter.dropped,
+        })
+    })
+}
+
+/// Replaces the display options of the channel identified by `key`.
+pub fn set_options(key: ChannelKey, options: ChannelOptions) -> Result<()> {
+    with_channel(key, |channel_state| {
+        channel_state.channel_info.options = options;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::foxdbg_channel_type_t;
+
+    pub(crate) fn float_channel(topic: &str, target_hz: i32) -> ChannelId {
+        let channel_type = foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_FLOAT;
+        ChannelId::new(manager::add_channel(topic, channel_type, target_hz).unwrap())
+    }
+
+    pub(crate) fn write(
+        channel_id: ChannelId,
+        value: f32,
+        timestamp: Option<Timestamp>,
+    ) -> Result<()> {
+        let data = &value as *const f32 as *const c_void;
+        unsafe {
+            super::write(
+                ChannelKey::Id(channel_id),
+                timestamp,
+                data,
+                mem::size_of::<f32>(),
+            )
+        }
+    }
+
+    pub(crate) fn stats(channel_id: ChannelId) -> foxdbg_channel_stats_t {
+        super::stats(ChannelKey::Id(channel_id)).unwrap()
+    }
+
+    fn millis(millis: u32) -> Timestamp {
+        Timestamp::new(millis / 1000, millis % 1000 * 1_000_000)
+    }
+
+    #[test]
+    fn drops_writes_faster_than_the_target_rate() {
+        let channel_id = float_channel("/test/channels/decimated", 10);
+        for i in 0..100 {
+            write(channel_id, i as f32, Some(millis(i * 10))).unwrap();
+        }
+        let stats = stats(channel_id);
+        assert_eq!((stats.written, stats.dropped), (10, 90));
+    }
+
+    #[test]
+    fn writes_everything_without_a_target_rate() {
+        let channel_id = float_channel("/test/channels/unlimited", 0);
+        for i in 0..100 {
+            write(channel_id, i as f32, Some(millis(i))).unwrap();
+        }
+        let stats = stats(channel_id);
+        assert_eq!((stats.written, stats.dropped), (100, 0));
+    }
+
+    #[test]
+    fn rejected_writes_are_not_counted_and_do_not_take_a_slot() {
+        let channel_id = float_channel("/test/channels/rejected", 10);
+        let value = 1.0f64;
+        let data = &value as *const f64 as *const c_void;
+        let result = unsafe {
+            super::write(
+                ChannelKey::Id(channel_id),
+                Some(millis(0)),
+                data,
+                mem::size_of::<f64>(),
+            )
+        };
+        assert!(result.is_err());
+
+        write(channel_id, 1.0, Some(millis(10))).unwrap();
+        let stats = stats(channel_id);
+        assert_eq!((stats.written, stats.dropped), (1, 0));
+    }
+
+    #[test]
+    fn reports_stats_by_topic() {
+        let topic = "/test/channels/stats_by_topic";
+        let channel_id = float_channel(topic, 10);
+        write(channel_id, 1.0, Some(millis(0))).unwrap();
+        write(channel_id, 2.0, Some(millis(50))).unwrap();
+
+        let stats = super::stats(ChannelKey::Topic(topic)).unwrap();
+        assert_eq!((stats.written, stats.dropped), (1, 1));
+        assert!(super::stats(ChannelKey::Topic("/test/channels/unknown")).is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use crate::config::string_from_c;
+use crate::foxdbg_channel_options_t;
+use foxglove::schemas::Duration;
+use std::time;
+
+/// Frame that channel data is expressed in unless a channel's options name another.
+pub const DEFAULT_FRAME_ID: &str = "world";
+
+/// Display options of a channel, mirroring `foxdbg_channel_options_t`.
+///
+/// Options that do not apply to a channel's type are ignored. Float, integer, boolean and
+/// transform channels use none of them, since transforms carry their own frame ids.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelOptions {
+    /// Coordinate frame the channel's data is expressed in.
+    pub frame_id: String,
+    /// Prepended to the topic to form the id of the scene entity the channel publishes.
+    pub entity_id_prefix: String,
+    /// How long scene entities are shown after being written, or `None` to show them
+    /// until they are replaced.
+    pub lifetime: Option<time::Duration>,
+    /// Whether scene entities move with their frame when the frame moves later.
+    pub frame_locked: bool,
+}
+
+impl Default for ChannelOptions {
+    fn default() -> Self {
+        ChannelOptions {
+            frame_id: DEFAULT_FRAME_ID.to_owned(),
+            entity_id_prefix: String::new(),
+            lifetime: None,
+            frame_locked: false,
+        }
+    }
+}
+
+impl ChannelOptions {
+    /// Copies the options out of a C options struct, filling NULL strings with defaults.
+    ///
+    /// # Safety
+    ///
+    /// Every non-NULL string in `options` must be a valid NUL-terminated C string.
+    pub(crate) unsafe fn from_c(options: &foxdbg_channel_options_t) -> Self {
+        ChannelOptions {
+            frame_id: unsafe { string_from_c(options.frame_id) }
+                .unwrap_or_else(|| DEFAULT_FRAME_ID.to_owned()),
+            entity_id_prefix: unsafe { string_from_c(options.entity_id_prefix) }
+                .unwrap_or_default(),
+            lifetime: (options.lifetime_ms > 0)
+                .then(|| time::Duration::from_millis(options.lifetime_ms.into())),
+            frame_locked: options.frame_locked,
+        }
+    }
+
+    /// Id of the scene entity published on `topic`.
+    pub(crate) fn entity_id(&self, topic: &str) -> String {
+        format!("{}{}", self.entity_id_prefix, topic)
+    }
+
+    /// Lifetime of scene entities in the Foxglove schema, saturating at its maximum.
+    pub(crate) fn entity_lifetime(&self) -> Option<Duration> {
+        self.lifetime
+            .map(|lifetime| Duration::try_from(lifetime).unwrap_or(Duration::MAX))
+    }
+}
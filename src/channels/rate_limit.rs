@@ -0,0 +1,166 @@
+use foxglove::schemas::Timestamp;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// Decimates the writes to a channel down to its target rate.
+///
+/// Writes are admitted on a fixed grid of `1 / target_hz` seconds, measured in message
+/// time, so a 1 kHz loop writing a 30 Hz channel publishes every 33rd or 34th write and
+/// averages exactly 30 Hz. Because message time follows the active clock, decimation also
+/// behaves correctly under simulation time running faster or slower than real time.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Minimum spacing between admitted writes, or 0 for no limit.
+    period: u64,
+    /// Message time from which the next write is admitted, in nanoseconds.
+    next_due: Option<u64>,
+    /// Number of writes published to the channel.
+    pub written: u64,
+    /// Number of writes dropped for arriving faster than the target rate.
+    pub dropped: u64,
+}
+
+impl RateLimiter {
+    /// Creates a limiter for `target_hz` writes per second. A target of zero or less
+    /// admits every write.
+    pub fn new(target_hz: i32) -> Self {
+        let period = match u64::try_from(target_hz) {
+            Ok(hz) if hz > 0 => NANOS_PER_SEC / hz,
+            _ => 0,
+        };
+        RateLimiter {
+            period,
+            next_due: None,
+            written: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Decides whether a write stamped with `timestamp` is due, counting it as dropped if
+    /// not. A due write only takes its slot on the grid once it is passed to `record`, so a
+    /// write that fails to be published does not hold back the next one.
+    pub fn admit(&mut self, timestamp: Timestamp) -> bool {
+        if self.period == 0 {
+            return true;
+        }
+
+        let now = timestamp.total_nanos();
+        match self.next_due {
+            // Time going backwards by more than a period, such as a replay being restarted,
+            // is admitted so the channel does not fall silent until it catches up again.
+            Some(next_due) if now < next_due && next_due - now <= self.period => {
+                self.dropped += 1;
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Counts an admitted write stamped with `timestamp` as written once it has been
+    /// published, moving the grid past it.
+    pub fn record(&mut self, timestamp: Timestamp) {
+        self.written += 1;
+        if self.period == 0 {
+            return;
+        }
+
+        let now = timestamp.total_nanos();
+        self.next_due = Some(match self.next_due {
+            // Stay on the grid, unless the channel fell more than a period behind it or time
+            // went backwards.
+            Some(next_due) if now >= next_due && now - next_due < self.period => {
+                next_due + self.period
+            }
+            _ => now + self.period,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MILLIS: u64 = 1_000_000;
+
+    fn at(nanos: u64) -> Timestamp {
+        Timestamp::new(
+            (nanos / NANOS_PER_SEC) as u32,
+            (nanos % NANOS_PER_SEC) as u32,
+        )
+    }
+
+    /// Offers a write at each time, recording the admitted ones, and returns which were.
+    fn offer(limiter: &mut RateLimiter, times: &[u64]) -> Vec<bool> {
+        times
+            .iter()
+            .map(|&nanos| {
+                let admitted = limiter.admit(at(nanos));
+                if admitted {
+                    limiter.record(at(nanos));
+                }
+                admitted
+            })
+            .collect()
+    }
+
+    #[test]
+    fn admits_writes_at_exactly_the_target_rate() {
+        let mut limiter = RateLimiter::new(10);
+        let times: Vec<u64> = (0..10).map(|i| i * 100 * MILLIS).collect();
+        assert_eq!(offer(&mut limiter, &times), vec![true; 10]);
+        assert_eq!(limiter.dropped, 0);
+    }
+
+    #[test]
+    fn decimates_a_faster_loop_to_the_grid() {
+        let mut limiter = RateLimiter::new(30);
+        let times: Vec<u64> = (0..1000).map(|i| i * MILLIS).collect();
+        let admitted = offer(&mut limiter, &times).iter().filter(|&&a| a).count();
+        assert_eq!(admitted, 30);
+        assert_eq!(limiter.dropped, 970);
+    }
+
+    #[test]
+    fn drops_a_burst_after_the_first_write() {
+        let mut limiter = RateLimiter::new(10);
+        let admitted = offer(&mut limiter, &[0, 1, 2, 3, 100 * MILLIS]);
+        assert_eq!(admitted, vec![true, false, false, false, true]);
+        assert_eq!(limiter.dropped, 3);
+    }
+
+    #[test]
+    fn restarts_the_grid_when_time_goes_backwards() {
+        let mut limiter = RateLimiter::new(10);
+        let admitted = offer(
+            &mut limiter,
+            &[10_000 * MILLIS, 0, 50 * MILLIS, 100 * MILLIS],
+        );
+        assert_eq!(admitted, vec![true, true, false, true]);
+    }
+
+    #[test]
+    fn keeps_dropping_when_time_steps_back_within_a_period() {
+        let mut limiter = RateLimiter::new(10);
+        let admitted = offer(&mut limiter, &[100 * MILLIS, 150 * MILLIS, 120 * MILLIS]);
+        assert_eq!(admitted, vec![true, false, false]);
+    }
+
+    #[test]
+    fn does_not_use_up_a_slot_until_recorded() {
+        let mut limiter = RateLimiter::new(10);
+        assert!(limiter.admit(at(0)));
+        // The first write failed to publish, so the next one is still due.
+        assert!(limiter.admit(at(MILLIS)));
+        limiter.record(at(MILLIS));
+        assert!(!limiter.admit(at(2 * MILLIS)));
+    }
+
+    #[test]
+    fn admits_every_write_without_a_target() {
+        for target_hz in [0, -5] {
+            let mut limiter = RateLimiter::new(target_hz);
+            assert_eq!(offer(&mut limiter, &[0, 0, 1, 0]), vec![true; 4]);
+            assert_eq!(limiter.dropped, 0);
+        }
+    }
+}
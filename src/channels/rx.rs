@@ -0,0 +1,421 @@
+use crate::channels::dispatch;
+use crate::error::{Error, Result};
+use crate::state::{self, RxChannelState, RxValue};
+use crate::{foxdbg_channel_type_t, foxdbg_color_t, foxdbg_pose_t, foxdbg_vector3_t};
+use serde_json::Value;
+use std::f32::consts::FRAC_PI_2;
+use std::ffi::c_void;
+use std::mem;
+use std::sync::PoisonError;
+
+/// Message encoding rx channels accept, advertised to clients by the WebSocket server.
+pub const RX_ENCODING: &str = "json";
+
+/// Registers a channel that Foxglove clients can publish to on `topic`.
+///
+/// Registering a topic that already exists with the same type returns the existing
+/// channel, along with the latest value it received.
+///
+/// # Errors
+///
+/// Returns an error if `channel_type` cannot be received, or if the topic is already
+/// registered with a different type.
+pub fn add_rx_channel(topic_name: &str, channel_type: foxdbg_channel_type_t) -> Result<u64> {
+    if !matches!(
+        channel_type,
+        foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_BOOLEAN
+            | foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_FLOAT
+            | foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_INTEGER
+            | foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_POSE
+    ) {
+        return Err(Error::InvalidArgument(format!(
+            "channels of type {:?} cannot be received",
+            channel_type
+        )));
+    }
+
+    let mut channels = state::RX_CHANNELS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some(id) = channels.topic_id(topic_name) {
+        let existing_type = channels
+            .get(id)
+            .map(|state| state.channel_type)
+            .ok_or_else(|| Error::Internal(format!("rx channel {} has no state", id)))?;
+        if existing_type != channel_type {
+            return Err(Error::ChannelConflict(format!(
+                "rx topic '{}' is already registered as {:?}",
+                topic_name, existing_type
+            )));
+        }
+        return Ok(id);
+    }
+
+    Ok(channels.insert(RxChannelState {
+        channel_type,
+        channel_topic: topic_name.to_owned(),
+        latest: None,
+        is_new: false,
+    }))
+}
+
+/// Copies the latest value received on an rx channel into `data`, returning whether it
+/// was received since the channel was last read. Returns `None`, leaving `data` untouched,
+/// if no value has been received yet.
+///
+/// # Safety
+///
+/// `data` must point to at least `size` writable bytes.
+///
+/// # Errors
+///
+/// Returns an error if no rx channel has the given id, `data` is NULL, or `size` is not
+/// the size of the channel's type.
+pub unsafe fn get_rx_channel(
+    channel_id: u64,
+    data: *mut c_void,
+    size: usize,
+) -> Result<Option<bool>> {
+    if data.is_null() {
+        return Err(Error::InvalidArgument("data must not be NULL".to_owned()));
+    }
+
+    let mut channels = state::RX_CHANNELS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let channel_state = channels
+        .get_mut(channel_id)
+        .ok_or(Error::UnknownChannel(channel_id as i64))?;
+
+    let expected_size = match channel_state.channel_type {
+        foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_BOOLEAN => mem::size_of::<bool>(),
+        foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_FLOAT => mem::size_of::<f32>(),
+        foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_INTEGER => mem::size_of::<i32>(),
+        _ => mem::size_of::<foxdbg_pose_t>(),
+    };
+    if size != expected_size {
+        return Err(Error::SizeMismatch(format!(
+            "expected {} bytes for a {:?} rx channel but got {}",
+            expected_size, channel_state.channel_type, size
+        )));
+    }
+
+    // The data may come from a byte buffer, so it is written without assuming alignment.
+    match channel_state.latest {
+        Some(RxValue::Boolean(value)) => unsafe { (data as *mut bool).write_unaligned(value) },
+        Some(RxValue::Float(value)) => unsafe { (data as *mut f32).write_unaligned(value) },
+        Some(RxValue::Integer(value)) => unsafe { (data as *mut i32).write_unaligned(value) },
+        Some(RxValue::Pose(value)) => unsafe {
+            (data as *mut foxdbg_pose_t).write_unaligned(value)
+        },
+        None => return Ok(None),
+    }
+    Ok(Some(mem::take(&mut channel_state.is_new)))
+}
+
+/// Registers the rx channel `topic_name` like `add_rx_channel`, and calls `callback` with
+/// every message subsequently received on it. Returns the id to unsubscribe with.
+///
+/// # Errors
+///
+/// Returns an error if the rx channel cannot be registered.
+pub fn subscribe(
+    topic_name: &str,
+    channel_type: foxdbg_channel_type_t,
+    callback: dispatch::Callback,
+) -> Result<u64> {
+    let channel_id = add_rx_channel(topic_name, channel_type)?;
+    dispatch::subscribe(channel_id, callback)
+}
+
+/// Stores a message a client published on `topic` as the latest value of the rx channel
+/// registered on it, and queues it for the topic's subscribers. Messages on other topics,
+/// and messages that cannot be decoded, are dropped.
+pub fn receive(topic: &str, encoding: &str, payload: &[u8]) {
+    let (channel_id, value) = {
+        let mut channels = state::RX_CHANNELS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some(channel_id) = channels.topic_id(topic) else {
+            log::debug!("Ignoring client message on unregistered topic '{}'", topic);
+            return;
+        };
+        let channel_state = channels
+            .get_mut(channel_id)
+            .expect("Rx topic index is out of date");
+
+        match decode(channel_state.channel_type, encoding, payload) {
+            Ok(value) => {
+                channel_state.latest = Some(value);
+                channel_state.is_new = true;
+                (channel_id, value)
+            }
+            Err(e) => {
+                log::warn!("Dropping client message on '{}': {}", topic, e);
+                return;
+            }
+        }
+    };
+    dispatch::publish(channel_id, value);
+}
+
+/// Decodes a JSON message into a value of `channel_type`.
+///
+/// Booleans, floats and integers may be sent bare or in a `value` or `data` field, so the
+/// messages of foxdbg's own schemas and of ROS `std_msgs` are both accepted. Poses take a
+/// `position` and an `orientation`, optionally wrapped in a `pose` field as in
+/// `foxglove.PoseInFrame`, and an optional `color`.
+fn decode(channel_type: foxdbg_channel_type_t, encoding: &str, payload: &[u8]) -> Result<RxValue> {
+    if encoding != RX_ENCODING {
+        return Err(Error::InvalidArgument(format!(
+            "unsupported message encoding '{}'",
+            encoding
+        )));
+    }
+    let message: Value = serde_json::from_slice(payload)
+        .map_err(|e| Error::InvalidArgument(format!("invalid JSON: {}", e)))?;
+
+    match channel_type {
+        foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_BOOLEAN => scalar(&message)
+            .as_bool()
+            .map(RxValue::Boolean)
+            .ok_or_else(|| Error::InvalidArgument("expected a boolean".to_owned())),
+        foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_FLOAT => scalar(&message)
+            .as_f64()
+            .map(|value| RxValue::Float(value as f32))
+            .ok_or_else(|| Error::InvalidArgument("expected a number".to_owned())),
+        foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_INTEGER => scalar(&message)
+            .as_i64()
+            .and_then(|value| i32::try_from(value).ok())
+            .map(RxValue::Integer)
+            .ok_or_else(|| Error::InvalidArgument("expected a 32-bit integer".to_owned())),
+        _ => decode_pose(&message).map(RxValue::Pose),
+    }
+}
+
+/// Unwraps a scalar sent in a `value` or `data` field.
+fn scalar(message: &Value) -> &Value {
+    message
+        .get("value")
+        .or_else(|| message.get("data"))
+        .unwrap_or(message)
+}
+
+fn decode_pose(message: &Value) -> Result<foxdbg_pose_t> {
+    let pose = message.get("pose").unwrap_or(message);
+    let position = match pose.get("position") {
+        Some(position) => foxdbg_vector3_t {
+            x: number(position, "x", 0.0)?,
+            y: number(position, "y", 0.0)?,
+            z: number(position, "z", 0.0)?,
+        },
+        None => foxdbg_vector3_t {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+    };
+
+    // Orientations are taken as Euler angles like those written to pose channels, unless
+    // they are sent as a quaternion.
+    let orientation = match pose.get("orientation") {
+        Some(orientation) if orientation.get("w").is_some() => quaternion_to_euler(
+            [
+                number(orientation, "x", 0.0)?,
+                number(orientation, "y", 0.0)?,
+                number(orientation, "z", 0.0)?,
+                number(orientation, "w", 1.0)?,
+            ],
+            FRAC_PI_2,
+        ),
+        Some(orientation) => foxdbg_vector3_t {
+            x: number(orientation, "x", 0.0)?,
+            y: number(orientation, "y", 0.0)?,
+            z: number(orientation, "z", 0.0)?,
+        },
+        None => foxdbg_vector3_t {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+    };
+
+    let color = match message.get("color") {
+        Some(color) => foxdbg_color_t {
+            r: number(color, "r", 1.0)?,
+            g: number(color, "g", 1.0)?,
+            b: number(color, "b", 1.0)?,
+            a: number(color, "a", 1.0)?,
+        },
+        None => foxdbg_color_t {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+            a: 1.0,
+        },
+    };
+
+    Ok(foxdbg_pose_t {
+        position,
+        orientation,
+        color,
+    })
+}
+
+/// Reads the number in `field` of `object`, or `default` if the field is missing.
+fn number(object: &Value, field: &str, default: f32) -> Result<f32> {
+    match object.get(field) {
+        Some(value) => value
+            .as_f64()
+            .map(|value| value as f32)
+            .ok_or_else(|| Error::InvalidArgument(format!("expected '{}' to be a number", field))),
+        None => Ok(default),
+    }
+}
+
+/// Converts a quaternion given as `[x, y, z, w]` to Euler angles, removing a yaw offset.
+///
+/// This is the inverse of the conversion used when writing poses, so a received pose
+/// written back to a pose channel is drawn with the orientation it was sent with.
+fn quaternion_to_euler([x, y, z, w]: [f32; 4], yaw_offset: f32) -> foxdbg_vector3_t {
+    let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+    let pitch = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0).asin();
+    let yaw = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+
+    foxdbg_vector3_t {
+        x: pitch,
+        y: roll,
+        z: yaw - yaw_offset,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::writer::helpers::euler_to_quaternion;
+
+    const EPSILON: f32 = 1e-5;
+
+    fn decode_json(channel_type: foxdbg_channel_type_t, json: &str) -> Result<RxValue> {
+        decode(channel_type, RX_ENCODING, json.as_bytes())
+    }
+
+    fn decode_pose_json(json: &str) -> foxdbg_pose_t {
+        match decode_json(foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_POSE, json) {
+            Ok(RxValue::Pose(pose)) => pose,
+            other => panic!("expected a pose, got {:?}", other),
+        }
+    }
+
+    fn assert_close(actual: foxdbg_vector3_t, expected: [f32; 3]) {
+        let actual = [actual.x, actual.y, actual.z];
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a - e).abs() < EPSILON,
+                "expected {:?}, got {:?}",
+                expected,
+                actual
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_bare_and_wrapped_scalars() {
+        use foxdbg_channel_type_t::*;
+        assert!(matches!(
+            decode_json(FOXDBG_CHANNEL_TYPE_BOOLEAN, "true"),
+            Ok(RxValue::Boolean(true))
+        ));
+        assert!(matches!(
+            decode_json(FOXDBG_CHANNEL_TYPE_FLOAT, r#"{"value": 2.5}"#),
+            Ok(RxValue::Float(2.5))
+        ));
+        assert!(matches!(
+            decode_json(FOXDBG_CHANNEL_TYPE_INTEGER, r#"{"data": -7}"#),
+            Ok(RxValue::Integer(-7))
+        ));
+    }
+
+    #[test]
+    fn rejects_mistyped_scalars() {
+        use foxdbg_channel_type_t::*;
+        assert!(decode_json(FOXDBG_CHANNEL_TYPE_BOOLEAN, "1").is_err());
+        assert!(decode_json(FOXDBG_CHANNEL_TYPE_FLOAT, r#"{"value": "fast"}"#).is_err());
+        assert!(decode_json(FOXDBG_CHANNEL_TYPE_INTEGER, "1.5").is_err());
+        assert!(decode_json(FOXDBG_CHANNEL_TYPE_INTEGER, "4294967296").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_json_and_other_encodings() {
+        use foxdbg_channel_type_t::*;
+        assert!(decode_json(FOXDBG_CHANNEL_TYPE_FLOAT, "{").is_err());
+        assert!(decode(FOXDBG_CHANNEL_TYPE_FLOAT, "cdr", b"1.0").is_err());
+    }
+
+    #[test]
+    fn decodes_poses_with_defaults() {
+        let pose = decode_pose_json(r#"{"position": {"x": 1, "y": 2}}"#);
+        assert_close(pose.position, [1.0, 2.0, 0.0]);
+        assert_close(pose.orientation, [0.0, 0.0, 0.0]);
+        assert_eq!(
+            [pose.color.r, pose.color.g, pose.color.b, pose.color.a],
+            [1.0; 4]
+        );
+    }
+
+    #[test]
+    fn decodes_wrapped_poses_with_euler_orientations() {
+        let pose = decode_pose_json(
+            r#"{"pose": {"position": {"x": 3}, "orientation": {"x": 0.1, "y": 0.2, "z": 0.3}}}"#,
+        );
+        assert_close(pose.position, [3.0, 0.0, 0.0]);
+        assert_close(pose.orientation, [0.1, 0.2, 0.3]);
+    }
+
+    #[test]
+    fn rejects_poses_with_non_numeric_fields() {
+        let result = decode_json(
+            foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_POSE,
+            r#"{"position": {"x": "left"}}"#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn converts_the_identity_quaternion() {
+        assert_close(
+            quaternion_to_euler([0.0, 0.0, 0.0, 1.0], 0.0),
+            [0.0, 0.0, 0.0],
+        );
+        assert_close(
+            quaternion_to_euler([0.0, 0.0, 0.0, 1.0], FRAC_PI_2),
+            [0.0, 0.0, -FRAC_PI_2],
+        );
+    }
+
+    #[test]
+    fn inverts_the_conversion_used_when_writing_poses() {
+        for angles in [[0.1, 0.2, 0.3], [-0.5, 1.0, -2.0], [0.0, 0.0, 1.4]] {
+            let orientation = foxdbg_vector3_t {
+                x: angles[0],
+                y: angles[1],
+                z: angles[2],
+            };
+            let q = euler_to_quaternion(&orientation, FRAC_PI_2);
+            let quaternion = [q.x as f32, q.y as f32, q.z as f32, q.w as f32];
+            assert_close(quaternion_to_euler(quaternion, FRAC_PI_2), angles);
+        }
+    }
+
+    #[test]
+    fn decodes_quaternion_orientations_with_the_pose_yaw_offset() {
+        // A pose with no rotation is written as a quarter turn about z.
+        let half = FRAC_PI_2 / 2.0;
+        let json = format!(
+            r#"{{"orientation": {{"x": 0, "y": 0, "z": {}, "w": {}}}}}"#,
+            half.sin(),
+            half.cos()
+        );
+        assert_close(decode_pose_json(&json).orientation, [0.0, 0.0, 0.0]);
+    }
+}
@@ -1,19 +1,49 @@
 use foxglove::Encode;
+use foxglove::schemas::Timestamp;
+
+/// A point in time, laid out like the timestamps in the Foxglove schemas.
+#[derive(Encode)]
+pub struct Time {
+    pub sec: u32,
+    pub nsec: u32,
+}
+
+impl From<Timestamp> for Time {
+    fn from(timestamp: Timestamp) -> Self {
+        Time {
+            sec: timestamp.sec(),
+            nsec: timestamp.nsec(),
+        }
+    }
+}
 
 /// A custom Foxglove schema for a single 32-bit floating-point value.
 #[derive(Encode)]
 pub struct Float {
-    pub value: f32
+    pub timestamp: Time,
+    pub value: f32,
 }
 
 /// A custom Foxglove schema for a single 32-bit integer value.
 #[derive(Encode)]
 pub struct Integer {
-    pub value: i32
+    pub timestamp: Time,
+    pub value: i32,
 }
 
 /// A custom Foxglove schema for a single boolean value.
 #[derive(Encode)]
 pub struct Bool {
-    pub value: bool
+    pub timestamp: Time,
+    pub value: bool,
+}
+
+/// A custom Foxglove schema recording a change to a tunable parameter. Boolean values are
+/// recorded as 1 and 0, so every parameter can be plotted.
+#[derive(Encode)]
+pub struct ParameterChange {
+    pub timestamp: Time,
+    pub name: String,
+    pub value: f64,
+    pub previous_value: f64,
 }
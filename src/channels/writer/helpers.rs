@@ -1,16 +1,19 @@
 use std::ffi::c_void;
 use std::{mem, slice};
 
+use foxglove::Encode;
+use foxglove::bytes::BufMut;
 use foxglove::schemas::Quaternion;
 
+use crate::error::{Error, Result};
 use crate::foxdbg_vector3_t;
 
 /// Converts a raw C pointer to a Rust reference.
 ///
 /// This function takes a raw C pointer (`data`) and a `size`, and attempts to
 /// convert it into a Rust reference of type `T`. It checks that the `size` matches
-/// the size of `T` to prevent memory access errors. If the sizes do not match, it
-/// logs a warning and returns `None`.
+/// the size of `T` to prevent memory access errors, returning a `SizeMismatch` error
+/// if they do not match.
 ///
 /// # Safety
 ///
@@ -24,26 +27,25 @@ use crate::foxdbg_vector3_t;
 ///
 /// # Returns
 ///
-/// An `Option` containing a reference to the data if the size is valid, or `None`
-/// otherwise.
-pub(super) unsafe fn data_as_ref<'a, T>(data: *const c_void, size: usize) -> Option<&'a T> {
+/// A reference to the data if the size is valid.
+pub(super) unsafe fn data_as_ref<'a, T>(data: *const c_void, size: usize) -> Result<&'a T> {
     if size != mem::size_of::<T>() {
-        log::warn!(
-            "Invalid data size for type {}, expected {} but got {}",
-            std::any::type_name::<T>(),
+        return Err(Error::SizeMismatch(format!(
+            "expected {} bytes for {} but got {}",
             mem::size_of::<T>(),
+            type_name::<T>(),
             size
-        );
-        return None;
+        )));
     }
-    Some(unsafe { &*(data as *const T) })
+    Ok(unsafe { &*(data as *const T) })
 }
 
 /// Converts a raw C pointer to a Rust slice.
 ///
 /// This function takes a raw C pointer (`data`) and a total `size` in bytes, and
 /// converts it into a Rust slice of type `T`. It calculates the number of items in
-/// the slice based on the size of `T`.
+/// the slice based on the size of `T`, returning a `SizeMismatch` error if `size` is
+/// not a whole number of items.
 ///
 /// # Safety
 ///
@@ -60,16 +62,37 @@ pub(super) unsafe fn data_as_ref<'a, T>(data: *const c_void, size: usize) -> Opt
 /// # Returns
 ///
 /// A slice of type `T`.
-pub(super) unsafe fn data_as_slice<'a, T>(data: *const c_void, size: usize) -> &'a [T] {
+pub(super) unsafe fn data_as_slice<'a, T>(data: *const c_void, size: usize) -> Result<&'a [T]> {
     let item_size = mem::size_of::<T>();
     if item_size == 0 {
-        return &[];
+        return Ok(&[]);
+    }
+    if !size.is_multiple_of(item_size) {
+        return Err(Error::SizeMismatch(format!(
+            "expected a multiple of {} bytes for {} but got {}",
+            item_size,
+            type_name::<T>(),
+            size
+        )));
     }
     let num_items = size / item_size;
     if num_items == 0 {
-        return &[];
+        return Ok(&[]);
     }
-    unsafe { slice::from_raw_parts(data as *const T, num_items) }
+    Ok(unsafe { slice::from_raw_parts(data as *const T, num_items) })
+}
+
+/// Encodes `message` into `buf`, converting any failure into an `Encode` error.
+pub(super) fn encode<T: Encode>(message: &T, buf: &mut impl BufMut) -> Result<()> {
+    message
+        .encode(buf)
+        .map_err(|e| Error::Encode(e.to_string()))
+}
+
+/// The name of `T` without its module path, as it appears in the C header.
+fn type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
 }
 
 /// Converts Euler angles to a quaternion, with an optional yaw offset.
@@ -89,7 +112,10 @@ pub(super) unsafe fn data_as_slice<'a, T>(data: *const c_void, size: usize) -> &
 /// # Returns
 ///
 /// A `Quaternion` representing the converted orientation.
-pub(super) fn euler_to_quaternion(orientation: &foxdbg_vector3_t, yaw_offset: f32) -> Quaternion {
+pub(in crate::channels) fn euler_to_quaternion(
+    orientation: &foxdbg_vector3_t,
+    yaw_offset: f32,
+) -> Quaternion {
     let pitch = orientation.x;
     let roll = orientation.y;
     let yaw = orientation.z + yaw_offset;
@@ -2,15 +2,19 @@ pub mod scene;
 pub mod sensor;
 pub mod telemetry;
 
-mod helpers;
+pub(super) mod helpers;
 
-use foxglove::{Context, PartialMetadata, ToUnixNanos};
+use foxglove::schemas::Timestamp;
+use foxglove::{Context, PartialMetadata};
 use helpers::data_as_ref;
 use std::cell::RefCell;
 use std::ffi::c_void;
+use std::mem;
 
-use crate::{foxdbg_channel_type_t, foxdbg_image_info_t};
-use crate::state::{ChannelInfo, ChannelState};
+use crate::channels::ChannelOptions;
+use crate::error::{Error, Result};
+use crate::state::ChannelState;
+use crate::{foxdbg_channel_options_t, foxdbg_channel_type_t, foxdbg_image_info_t};
 
 thread_local! {
     /// A thread-local buffer used for serialising log data before sending it to Foxglove.
@@ -18,7 +22,7 @@ thread_local! {
     /// This buffer is reused for each `write_channel` call on the same thread to avoid
     /// the performance overhead of allocating a new buffer every time. The buffer is
     /// cleared before each use and has an initial capacity of 10MB to reduce the
-    /// likelihood of reallocations for large messages. Once the buffer has been reallocated, 
+    /// likelihood of reallocations for large messages. Once the buffer has been reallocated,
     /// it will not shrink
     static LOG_BUFFER: RefCell<Vec<u8>> = RefCell::new(Vec::with_capacity(10 * 1024 * 1024)); // 10MB initial capacity
 }
@@ -29,97 +33,224 @@ thread_local! {
 /// the data into a thread-local buffer based on the channel type, and then logs the
 /// data to the appropriate Foxglove channel.
 ///
+/// The message is stamped with `stamp`, which becomes both the timestamp inside the
+/// message and its log time. `timestamp` is the time the write was explicitly given, if
+/// any, which `stamp` was resolved from before the channel was locked.
+///
+/// Writes arriving faster than the channel's target rate are dropped before the data is
+/// read or encoded, and counted in the channel's `RateLimiter`.
+///
 /// # Safety
 ///
 /// This function is `unsafe` because it delegates to other `unsafe` functions that
 /// dereference the raw `data` pointer. The caller must ensure that the `data` pointer
-/// is valid, non-null, and that `size` correctly corresponds to the size of the data.
+/// is valid, and that `size` correctly corresponds to the size of the data.
 ///
 /// # Arguments
 ///
-/// * `channel_state` - A reference to the `ChannelState` for the channel to write to.
+/// * `channel_state` - A mutable reference to the `ChannelState` for the channel to write to.
+/// * `stamp` - The time the message is stamped with.
+/// * `timestamp` - The time the data was captured, if known.
 /// * `data` - A raw C pointer to the data payload.
 /// * `size` - The size of the data payload in bytes.
-pub unsafe fn write_channel(channel_state: &ChannelState, data: *const c_void, size: usize) {
-    let channel = Context::get_default()
-        .get_channel_by_topic(&channel_state.channel_topic)
-        .expect("Channel could not be found");
+///
+/// # Errors
+///
+/// Returns an error if `data` is NULL, its size does not match the channel type, or it
+/// could not be encoded. Nothing is logged to the channel in that case.
+pub unsafe fn write_channel(
+    channel_state: &mut ChannelState,
+    stamp: Timestamp,
+    timestamp: Option<Timestamp>,
+    data: *const c_void,
+    size: usize,
+) -> Result<()> {
+    if data.is_null() && size > 0 {
+        return Err(Error::InvalidArgument("data must not be NULL".to_owned()));
+    }
+
+    if !channel_state.rate_limiter.admit(stamp) {
+        return Ok(());
+    }
 
     LOG_BUFFER.with(|buf_cell| {
         let mut buf = buf_cell.borrow_mut();
         buf.clear();
 
-        unsafe {
-            match channel_state.channel_type {
-                foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_FLOAT => {
-                    telemetry::write_float(&mut *buf, data, size)
-                }
-                foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_INTEGER => {
-                    telemetry::write_int(&mut *buf, data, size)
-                }
-                foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_BOOLEAN => {
-                    telemetry::write_bool(&mut *buf, data, size)
-                }
-                foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_IMAGE => {
-                    sensor::write_image(&mut *buf, data, size, &channel_state.channel_info)
-                }
-                foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_POINTCLOUD => {
-                    sensor::write_pointcloud(&mut *buf, data, size)
-                }
-                foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_CUBES => {
-                    scene::write_cubes(&mut *buf, data, size, &channel_state.channel_topic)
-                }
-                foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_LINES => {
-                    scene::write_lines(&mut *buf, data, size, &channel_state.channel_topic)
-                }
-                foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_POSE => {
-                    scene::write_pose(&mut *buf, data, size, &channel_state.channel_topic)
-                }
-                foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_TRANSFORM => {
-                    scene::write_transform(&mut *buf, data, size)
-                }
-                foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_LOCATION => {
-                    sensor::write_location(&mut *buf, data, size)
-                }
+        unsafe { encode_message(&mut buf, channel_state, stamp, timestamp, data, size) }?;
+        log_message(channel_state, stamp, &buf)
+    })
+}
+
+/// Encodes a data payload for a channel into an owned buffer without logging it, so it can
+/// be logged later with `log_message`. This is how writes are queued in a frame.
+///
+/// The message is stamped with `timestamp`, and is subject to the channel's target rate
+/// like a write made with `write_channel`. It only takes its slot once it is logged, so it
+/// must be admitted again before then in case an earlier write took the slot first.
+///
+/// # Safety
+///
+/// The caller must ensure that the `data` pointer is valid, and that `size` correctly
+/// corresponds to the size of the data. Pointers within the data, such as the frame ids
+/// of a transform, are only read during this call.
+///
+/// # Errors
+///
+/// Returns an error if `data` is NULL, its size does not match the channel type, or it
+/// could not be encoded. Returns `None` if the write was dropped by the rate limit.
+pub unsafe fn encode_channel(
+    channel_state: &mut ChannelState,
+    timestamp: Timestamp,
+    data: *const c_void,
+    size: usize,
+) -> Result<Option<Vec<u8>>> {
+    if data.is_null() && size > 0 {
+        return Err(Error::InvalidArgument("data must not be NULL".to_owned()));
+    }
+
+    if !channel_state.rate_limiter.admit(timestamp) {
+        return Ok(None);
+    }
+
+    let mut buf = Vec::new();
+    unsafe {
+        encode_message(
+            &mut buf,
+            channel_state,
+            timestamp,
+            Some(timestamp),
+            data,
+            size,
+        )
+    }?;
+    Ok(Some(buf))
+}
+
+/// Logs an encoded message to the channel's Foxglove channel with `timestamp` as its log
+/// time, counting it as written and taking its slot in the channel's target rate.
+pub fn log_message(
+    channel_state: &mut ChannelState,
+    timestamp: Timestamp,
+    data: &[u8],
+) -> Result<()> {
+    let channel = Context::get_default()
+        .get_channel_by_topic(&channel_state.channel_topic)
+        .ok_or_else(|| {
+            Error::Internal(format!(
+                "channel '{}' is not registered",
+                channel_state.channel_topic
+            ))
+        })?;
+
+    channel.log_with_meta(
+        data,
+        PartialMetadata::with_log_time(timestamp.total_nanos()),
+    );
+    channel_state.rate_limiter.record(timestamp);
+    Ok(())
+}
+
+/// Serialises a data payload into `buf` based on the channel type.
+///
+/// `stamp` is the time the message is stamped with, and `timestamp` the time the write was
+/// explicitly given, if any. Location fixes given no time keep the time they carry.
+unsafe fn encode_message(
+    buf: &mut Vec<u8>,
+    channel_state: &ChannelState,
+    stamp: Timestamp,
+    timestamp: Option<Timestamp>,
+    data: *const c_void,
+    size: usize,
+) -> Result<()> {
+    let info = &channel_state.channel_info;
+    let topic = &channel_state.channel_topic;
+    unsafe {
+        match channel_state.channel_type {
+            foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_FLOAT => {
+                telemetry::write_float(buf, stamp, data, size)
+            }
+            foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_INTEGER => {
+                telemetry::write_int(buf, stamp, data, size)
+            }
+            foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_BOOLEAN => {
+                telemetry::write_bool(buf, stamp, data, size)
+            }
+            foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_IMAGE => {
+                sensor::write_image(buf, stamp, data, size, info, topic)
+            }
+            foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_POINTCLOUD => {
+                sensor::write_pointcloud(buf, stamp, data, size, &info.options)
+            }
+            foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_CUBES => {
+                scene::write_cubes(buf, stamp, data, size, &info.options, topic)
+            }
+            foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_LINES => {
+                scene::write_lines(buf, stamp, data, size, &info.options, topic)
+            }
+            foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_POSE => {
+                scene::write_pose(buf, stamp, data, size, &info.options, topic)
+            }
+            foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_TRANSFORM => {
+                scene::write_transform(buf, stamp, data, size)
+            }
+            foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_LOCATION => {
+                sensor::write_location(buf, timestamp, data, size, &info.options)
             }
         }
-        
-        channel.log(&buf);
-    });
+    }
 }
 
-/// Writes metadata for a channel, such as image dimensions.
+/// Writes metadata for a channel, such as image dimensions or display options.
 ///
-/// This function is used to provide additional information about a channel that is
-/// required for proper decoding or visualisation. For example, for an image channel,
-/// this function should be called with a `foxdbg_image_info_t` struct to provide the
-/// image dimensions.
+/// The kind of metadata is told apart by `size`. Every channel type accepts a
+/// `foxdbg_channel_options_t`, which replaces the channel's display options, while image
+/// channels also accept a `foxdbg_image_info_t` giving the dimensions of the images
+/// written to them, which is required before the first image is written.
 ///
 /// # Safety
 ///
-/// This function is `unsafe` because it calls `data_as_ref`, which dereferences a raw
-/// pointer. The caller must ensure that the `data` pointer is valid, non-null, and
-/// that `size` correctly corresponds to the size of the metadata struct.
+/// This function is `unsafe` because it dereferences a raw pointer, along with the
+/// strings of a `foxdbg_channel_options_t`. The caller must ensure that the `data`
+/// pointer is valid, non-null, and that `size` correctly corresponds to the size of the
+/// metadata struct.
 ///
 /// # Arguments
 ///
 /// * `channel_state` - A mutable reference to the `ChannelState` to update.
 /// * `data` - A raw C pointer to the metadata struct.
 /// * `size` - The size of the metadata struct in bytes.
-pub unsafe fn write_channel_info(channel_state: &mut ChannelState, data: *const c_void, size: usize) {
+///
+/// # Errors
+///
+/// Returns an error if `data` is NULL, or if `size` matches no metadata struct the
+/// channel type takes. The existing channel info is kept in that case.
+pub unsafe fn write_channel_info(
+    channel_state: &mut ChannelState,
+    data: *const c_void,
+    size: usize,
+) -> Result<()> {
+    if data.is_null() {
+        return Err(Error::InvalidArgument("data must not be NULL".to_owned()));
+    }
 
-    let channel_info = if let Some(image_info) =
-        unsafe { data_as_ref::<foxdbg_image_info_t>(data, size) }
-    {
-        match channel_state.channel_type {
-            crate::foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_IMAGE => {
-                ChannelInfo::CompressedImageInfo(*image_info)
-            }
-            _ => ChannelInfo::NoInfo(),
-        }
+    if size == mem::size_of::<foxdbg_channel_options_t>() {
+        let options = unsafe { data_as_ref::<foxdbg_channel_options_t>(data, size) }?;
+        channel_state.channel_info.options = unsafe { ChannelOptions::from_c(options) };
+    } else if channel_state.channel_type == foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_IMAGE {
+        let image_info = unsafe { data_as_ref::<foxdbg_image_info_t>(data, size) }?;
+        channel_state.channel_info.image_info = Some(*image_info);
     } else {
-        ChannelInfo::NoInfo()
-    };
-
-    channel_state.channel_info = channel_info;
+        return Err(Error::SizeMismatch(format!(
+            "channels of type {:?} take a foxdbg_channel_options_t of {} bytes, got {} bytes",
+            channel_state.channel_type,
+            mem::size_of::<foxdbg_channel_options_t>(),
+            size
+        )));
+    }
+    Ok(())
 }
+
+// Channel info is told apart by size, so the structs must never have the same size.
+const _: () =
+    assert!(mem::size_of::<foxdbg_channel_options_t>() != mem::size_of::<foxdbg_image_info_t>());
@@ -1,14 +1,16 @@
-use foxglove::Encode;
 use foxglove::bytes::BufMut;
 use foxglove::schemas::{
-    ArrowPrimitive, Color, CubePrimitive, FrameTransform, LinePrimitive, Point3, Pose, Quaternion, SceneEntity, SceneUpdate, Timestamp, Vector3
+    ArrowPrimitive, Color, CubePrimitive, FrameTransform, LinePrimitive, Point3, Pose, Quaternion,
+    SceneEntity, SceneUpdate, Timestamp, Vector3,
 };
 use std::f32::consts::FRAC_PI_2;
 use std::ffi::{CStr, c_void};
 
+use crate::channels::ChannelOptions;
+use crate::error::{Error, Result};
 use crate::types::{foxdbg_cube_t, foxdbg_line_t, foxdbg_pose_t, foxdbg_transform_t};
 
-use super::helpers::{data_as_ref, data_as_slice, euler_to_quaternion};
+use super::helpers::{data_as_ref, data_as_slice, encode, euler_to_quaternion};
 
 /// A generic helper function for creating and encoding `SceneUpdate` messages.
 ///
@@ -19,20 +21,25 @@ use super::helpers::{data_as_ref, data_as_slice, euler_to_quaternion};
 /// # Arguments
 ///
 /// * `buf` - The buffer to write the encoded `SceneUpdate` to.
+/// * `timestamp` - The time the `SceneEntity` is stamped with.
+/// * `options` - The channel's display options, giving the frame, id and lifetime of the
+///   `SceneEntity`.
 /// * `topic_name` - The name of the topic to associate with the `SceneEntity`.
 /// * `mutator` - A closure that takes a mutable reference to a `SceneEntity` and
 ///   modifies it as needed.
 fn write_scene_update(
     buf: &mut impl BufMut,
+    timestamp: Timestamp,
+    options: &ChannelOptions,
     topic_name: &str,
     mutator: impl FnOnce(&mut SceneEntity),
-) {
+) -> Result<()> {
     let mut entity = SceneEntity {
-        timestamp: Some(Timestamp::now()),
-        frame_id: "world".to_owned(),
-        id: topic_name.to_owned(),
-        lifetime: None,
-        frame_locked: false,
+        timestamp: Some(timestamp),
+        frame_id: options.frame_id.clone(),
+        id: options.entity_id(topic_name),
+        lifetime: options.entity_lifetime(),
+        frame_locked: options.frame_locked,
         metadata: Vec::new(),
         arrows: Vec::new(),
         cubes: Vec::new(),
@@ -46,58 +53,68 @@ fn write_scene_update(
 
     mutator(&mut entity);
 
-    SceneUpdate {
+    let update = SceneUpdate {
         entities: vec![entity],
         deletions: Vec::new(),
-    }
-    .encode(buf)
-    .unwrap();
+    };
+    encode(&update, buf)
 }
 
 // --- Writers ---
 
-pub(super) unsafe fn write_transform(buf: &mut impl BufMut, data: *const c_void, data_size: usize) {
-    if let Some(transform_data) = unsafe { data_as_ref::<foxdbg_transform_t>(data, data_size) } {
-        let parent_frame_id = unsafe {
-            CStr::from_ptr(transform_data.parent_id)
-                .to_string_lossy()
-                .into_owned()
-        };
-        let child_frame_id = unsafe {
-            CStr::from_ptr(transform_data.id)
-                .to_string_lossy()
-                .into_owned()
-        };
-
-        let translation = Vector3 {
-            x: transform_data.position.x as f64,
-            y: transform_data.position.y as f64,
-            z: transform_data.position.z as f64,
-        };
-
-        let rotation = euler_to_quaternion(&transform_data.orientation, 0.0);
-
-        FrameTransform {
-            timestamp: Some(Timestamp::now()),
-            parent_frame_id,
-            child_frame_id,
-            translation: Some(translation),
-            rotation: Some(rotation),
-        }
-        .encode(buf)
-        .unwrap();
+pub(super) unsafe fn write_transform(
+    buf: &mut impl BufMut,
+    timestamp: Timestamp,
+    data: *const c_void,
+    data_size: usize,
+) -> Result<()> {
+    let transform_data = unsafe { data_as_ref::<foxdbg_transform_t>(data, data_size) }?;
+    if transform_data.parent_id.is_null() || transform_data.id.is_null() {
+        return Err(Error::InvalidArgument(
+            "transform frame ids must not be NULL".to_owned(),
+        ));
     }
+
+    let parent_frame_id = unsafe {
+        CStr::from_ptr(transform_data.parent_id)
+            .to_string_lossy()
+            .into_owned()
+    };
+    let child_frame_id = unsafe {
+        CStr::from_ptr(transform_data.id)
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    let translation = Vector3 {
+        x: transform_data.position.x as f64,
+        y: transform_data.position.y as f64,
+        z: transform_data.position.z as f64,
+    };
+
+    let rotation = euler_to_quaternion(&transform_data.orientation, 0.0);
+
+    let transform = FrameTransform {
+        timestamp: Some(timestamp),
+        parent_frame_id,
+        child_frame_id,
+        translation: Some(translation),
+        rotation: Some(rotation),
+    };
+    encode(&transform, buf)
 }
 
 pub(super) unsafe fn write_lines(
     buf: &mut impl BufMut,
+    timestamp: Timestamp,
     data: *const c_void,
     data_size: usize,
+    options: &ChannelOptions,
     topic_name: &str,
-) {
-    let lines_slice = unsafe { data_as_slice::<foxdbg_line_t>(data, data_size) };
+) -> Result<()> {
+    let lines_slice = unsafe { data_as_slice::<foxdbg_line_t>(data, data_size) }?;
     if lines_slice.is_empty() {
-        return;
+        return Ok(());
     }
 
     let line_primitives: Vec<LinePrimitive> = lines_slice
@@ -148,62 +165,65 @@ pub(super) unsafe fn write_lines(
         })
         .collect();
 
-    write_scene_update(buf, topic_name, |entity| {
+    write_scene_update(buf, timestamp, options, topic_name, |entity| {
         entity.lines = line_primitives;
-    });
+    })
 }
 
 pub(super) unsafe fn write_pose(
     buf: &mut impl BufMut,
+    timestamp: Timestamp,
     data: *const c_void,
     data_size: usize,
+    options: &ChannelOptions,
     topic_name: &str,
-) {
-    if let Some(pose_data) = unsafe { data_as_ref::<foxdbg_pose_t>(data, data_size) } {
-        let orientation = euler_to_quaternion(&pose_data.orientation, FRAC_PI_2);
-
-        let position = Vector3 {
-            x: pose_data.position.x as f64,
-            y: pose_data.position.y as f64,
-            z: pose_data.position.z as f64,
-        };
-
-        let pose = Pose {
-            position: Some(position),
-            orientation: Some(orientation),
-        };
-
-        let color = Color {
-            r: pose_data.color.r as f64,
-            g: pose_data.color.g as f64,
-            b: pose_data.color.b as f64,
-            a: pose_data.color.a as f64,
-        };
-
-        let arrow = ArrowPrimitive {
-            pose: Some(pose),
-            shaft_length: 0.5,
-            shaft_diameter: 0.05,
-            head_length: 0.15,
-            head_diameter: 0.1,
-            color: Some(color),
-        };
-
-        write_scene_update(buf, topic_name, |entity| {
-            entity.arrows = vec![arrow];
-        });
-    }
+) -> Result<()> {
+    let pose_data = unsafe { data_as_ref::<foxdbg_pose_t>(data, data_size) }?;
+    let orientation = euler_to_quaternion(&pose_data.orientation, FRAC_PI_2);
+
+    let position = Vector3 {
+        x: pose_data.position.x as f64,
+        y: pose_data.position.y as f64,
+        z: pose_data.position.z as f64,
+    };
+
+    let pose = Pose {
+        position: Some(position),
+        orientation: Some(orientation),
+    };
+
+    let color = Color {
+        r: pose_data.color.r as f64,
+        g: pose_data.color.g as f64,
+        b: pose_data.color.b as f64,
+        a: pose_data.color.a as f64,
+    };
+
+    let arrow = ArrowPrimitive {
+        pose: Some(pose),
+        shaft_length: 0.5,
+        shaft_diameter: 0.05,
+        head_length: 0.15,
+        head_diameter: 0.1,
+        color: Some(color),
+    };
+
+    write_scene_update(buf, timestamp, options, topic_name, |entity| {
+        entity.arrows = vec![arrow];
+    })
 }
 
 pub(super) unsafe fn write_cubes(
     buf: &mut impl BufMut,
+    timestamp: Timestamp,
     data: *const c_void,
     data_size: usize,
+    options: &ChannelOptions,
     topic_name: &str,
-) {
-    let cubes_slice = unsafe { data_as_slice::<foxdbg_cube_t>(data, data_size) };
+) -> Result<()> {
+    let cubes_slice = unsafe { data_as_slice::<foxdbg_cube_t>(data, data_size) }?;
     if cubes_slice.is_empty() {
-        return;
+        return Ok(());
     }
 
     let cube_primitives: Vec<CubePrimitive> = cubes_slice
@@ -243,7 +263,7 @@ pub(super) unsafe fn write_cubes(
         })
         .collect();
 
-    write_scene_update(buf, topic_name, |entity| {
+    write_scene_update(buf, timestamp, options, topic_name, |entity| {
         entity.cubes = cube_primitives;
-    });
+    })
 }
@@ -1,46 +1,60 @@
+use crate::channels::ChannelOptions;
+use crate::error::{Error, Result};
 use crate::state::ChannelInfo;
 use crate::types::{foxdbg_location_t, foxdbg_vector4_t};
-use foxglove::Encode;
 use foxglove::bytes::{BufMut, Bytes};
 use foxglove::schemas::{CompressedImage, LocationFix, PackedElementField, PointCloud, Timestamp};
 use std::ffi::c_void;
 use std::mem;
-use std::slice;
 use turbojpeg::{Compressor, Image, PixelFormat};
 
-use super::helpers::data_as_ref;
+use super::helpers::{data_as_ref, data_as_slice, encode};
 
-pub(super) unsafe fn write_location(buf: &mut impl BufMut, data: *const c_void, data_size: usize) {
-    if let Some(location_data) = unsafe { data_as_ref::<foxdbg_location_t>(data, data_size) } {
-        let timestamp = Timestamp::new(location_data.timestamp_sec, location_data.timestamp_nsec);
+/// Writes a location fix. Unless the write was given a `timestamp`, the fix is stamped with
+/// the time carried in the `foxdbg_location_t` itself.
+pub(super) unsafe fn write_location(
+    buf: &mut impl BufMut,
+    timestamp: Option<Timestamp>,
+    data: *const c_void,
+    data_size: usize,
+    options: &ChannelOptions,
+) -> Result<()> {
+    let location_data = unsafe { data_as_ref::<foxdbg_location_t>(data, data_size) }?;
+    let timestamp = timestamp
+        .or_else(|| {
+            Timestamp::new_checked(location_data.timestamp_sec, location_data.timestamp_nsec)
+        })
+        .ok_or_else(|| Error::InvalidArgument("location timestamp is out of range".to_owned()))?;
 
-        LocationFix {
-            timestamp: Some(timestamp),
-            frame_id: "world".to_owned(),
-            latitude: location_data.latitude,
-            longitude: location_data.longitude,
-            altitude: location_data.altitude,
-            position_covariance: vec![0.0; 9],
-            position_covariance_type: 0,
-            color: None,
-        }
-        .encode(buf)
-        .unwrap();
-    }
+    let location = LocationFix {
+        timestamp: Some(timestamp),
+        frame_id: options.frame_id.clone(),
+        latitude: location_data.latitude,
+        longitude: location_data.longitude,
+        altitude: location_data.altitude,
+        position_covariance: vec![0.0; 9],
+        position_covariance_type: 0,
+        color: None,
+    };
+    encode(&location, buf)
 }
 
 pub(super) unsafe fn write_pointcloud(
     buf: &mut impl BufMut,
+    timestamp: Timestamp,
     data: *const c_void,
     data_size: usize,
-) {
-    let raw_bytes = unsafe { slice::from_raw_parts(data as *const u8, data_size) };
+    options: &ChannelOptions,
+) -> Result<()> {
+    // Checks the data holds a whole number of points.
+    unsafe { data_as_slice::<foxdbg_vector4_t>(data, data_size) }?;
+    let raw_bytes = unsafe { data_as_slice::<u8>(data, data_size) }?;
 
-    PointCloud {
-        timestamp:Some( Timestamp::now()),
-        frame_id: "world".to_owned(),
+    let pointcloud = PointCloud {
+        timestamp: Some(timestamp),
+        frame_id: options.frame_id.clone(),
         pose: None,
-                // The point stride is the size of the `foxdbg_vector4_t` struct in bytes.
+        // The point stride is the size of the `foxdbg_vector4_t` struct in bytes.
         point_stride: mem::size_of::<foxdbg_vector4_t>() as u32,
         // The fields describe the layout of the `foxdbg_vector4_t` struct.
         // Each field has a name, an offset within the struct, and a type.
@@ -68,37 +82,45 @@ pub(super) unsafe fn write_pointcloud(
             },
         ],
         data: Bytes::copy_from_slice(raw_bytes),
-    }
-    .encode(buf)
-    .unwrap();
+    };
+    encode(&pointcloud, buf)
 }
 
 pub(super) unsafe fn write_image(
     buf: &mut impl BufMut,
+    timestamp: Timestamp,
     data: *const c_void,
     data_size: usize,
     channel_info: &ChannelInfo,
-) {
-    let image_info = match channel_info {
-        ChannelInfo::CompressedImageInfo(image_info) => image_info,
-        ChannelInfo::NoInfo() => {
-            panic!("Attempted to write image to channel without setting channel info")
-        }
-    };
+    topic_name: &str,
+) -> Result<()> {
+    let image_info = channel_info
+        .image_info
+        .ok_or_else(|| Error::MissingChannelInfo(topic_name.to_owned()))?;
 
     let pixel_format = match image_info.channels {
         1 => PixelFormat::GRAY,
         3 => PixelFormat::RGB,
         4 => PixelFormat::RGBA,
         _ => {
-            log::warn!("Unsupported channel count: {}", image_info.channels);
-            return;
+            return Err(Error::InvalidArgument(format!(
+                "unsupported image channel count {}",
+                image_info.channels
+            )));
         }
     };
 
-    let raw_slice = unsafe { std::slice::from_raw_parts(data as *const u8, data_size) };
-
     let pitch = image_info.width as usize * image_info.channels as usize;
+    let expected_size = pitch * image_info.height as usize;
+    if data_size != expected_size {
+        return Err(Error::SizeMismatch(format!(
+            "expected {} bytes for a {}x{} image with {} channels but got {}",
+            expected_size, image_info.width, image_info.height, image_info.channels, data_size
+        )));
+    }
+
+    let raw_slice = unsafe { data_as_slice::<u8>(data, data_size) }?;
+
     let image = Image {
         pixels: raw_slice,
         width: image_info.width as usize,
@@ -107,17 +129,19 @@ pub(super) unsafe fn write_image(
         format: pixel_format,
     };
 
-    let mut compressor = Compressor::new().expect("Failed to create compressor");
-    compressor.set_quality(25).unwrap();
-    compressor.set_subsamp(turbojpeg::Subsamp::Sub2x2).unwrap();
-
-    let jpeg_data = compressor.compress_to_vec(image).unwrap();
-    CompressedImage {
-        timestamp: Some(Timestamp::now()),
-        frame_id: "world".to_string(),
+    let jpeg_data = compress_jpeg(image).map_err(|e| Error::Encode(e.to_string()))?;
+    let compressed_image = CompressedImage {
+        timestamp: Some(timestamp),
+        frame_id: channel_info.options.frame_id.clone(),
         data: Bytes::copy_from_slice(&jpeg_data),
         format: "JPEG".to_string(),
-    }
-    .encode(buf)
-    .unwrap()
+    };
+    encode(&compressed_image, buf)
+}
+
+fn compress_jpeg(image: Image<&[u8]>) -> turbojpeg::Result<Vec<u8>> {
+    let mut compressor = Compressor::new()?;
+    compressor.set_quality(25)?;
+    compressor.set_subsamp(turbojpeg::Subsamp::Sub2x2)?;
+    compressor.compress_to_vec(image)
 }
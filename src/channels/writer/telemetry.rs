@@ -1,8 +1,9 @@
 /// Writer functions for telemetary data
 use super::super::schemas::{Bool, Float, Integer};
-use super::helpers::data_as_ref;
-use foxglove::Encode;
+use super::helpers::{data_as_ref, encode};
+use crate::error::{Error, Result};
 use foxglove::bytes::BufMut;
+use foxglove::schemas::Timestamp;
 use std::ffi::c_void;
 use std::mem::size_of;
 
@@ -12,36 +13,66 @@ const I32_SIZE: usize = size_of::<i32>();
 
 unsafe fn encode_integer<T: Into<i32> + Copy>(
     buf: &mut impl BufMut,
+    timestamp: Timestamp,
     data: *const c_void,
     size: usize,
-) {
-    if let Some(value) = unsafe { data_as_ref::<T>(data, size) } {
-        Integer {
+) -> Result<()> {
+    let value = unsafe { data_as_ref::<T>(data, size) }?;
+    encode(
+        &Integer {
+            timestamp: timestamp.into(),
             value: (*value).into(),
-        }
-        .encode(buf)
-        .unwrap();
-    }
+        },
+        buf,
+    )
 }
 
-pub(super) unsafe fn write_int(buf: &mut impl BufMut, data: *const c_void, size: usize) {
+pub(super) unsafe fn write_int(
+    buf: &mut impl BufMut,
+    timestamp: Timestamp,
+    data: *const c_void,
+    size: usize,
+) -> Result<()> {
     // This can be called with multiple different int types so need to check which one
     match size {
-        I8_SIZE => unsafe { encode_integer::<i8>(buf, data, size) },
-        I16_SIZE => unsafe { encode_integer::<i16>(buf, data, size) },
-        I32_SIZE => unsafe { encode_integer::<i32>(buf, data, size) },
-        _ => {}
+        I8_SIZE => unsafe { encode_integer::<i8>(buf, timestamp, data, size) },
+        I16_SIZE => unsafe { encode_integer::<i16>(buf, timestamp, data, size) },
+        I32_SIZE => unsafe { encode_integer::<i32>(buf, timestamp, data, size) },
+        _ => Err(Error::SizeMismatch(format!(
+            "expected 1, 2 or 4 bytes for an integer but got {}",
+            size
+        ))),
     }
 }
 
-pub(super) unsafe fn write_bool(buf: &mut impl BufMut, data: *const c_void, size: usize) {
-    if let Some(value) = unsafe { data_as_ref::<bool>(data, size) } {
-        Bool { value: *value }.encode(buf).unwrap();
-    }
+pub(super) unsafe fn write_bool(
+    buf: &mut impl BufMut,
+    timestamp: Timestamp,
+    data: *const c_void,
+    size: usize,
+) -> Result<()> {
+    let value = unsafe { data_as_ref::<bool>(data, size) }?;
+    encode(
+        &Bool {
+            timestamp: timestamp.into(),
+            value: *value,
+        },
+        buf,
+    )
 }
 
-pub(super) unsafe fn write_float(buf: &mut impl BufMut, data: *const c_void, size: usize) {
-    if let Some(value) = unsafe { data_as_ref::<f32>(data, size) } {
-        Float { value: *value }.encode(buf).unwrap();
-    }
+pub(super) unsafe fn write_float(
+    buf: &mut impl BufMut,
+    timestamp: Timestamp,
+    data: *const c_void,
+    size: usize,
+) -> Result<()> {
+    let value = unsafe { data_as_ref::<f32>(data, size) }?;
+    encode(
+        &Float {
+            timestamp: timestamp.into(),
+            value: *value,
+        },
+        buf,
+    )
 }
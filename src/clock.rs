@@ -0,0 +1,94 @@
+use crate::foxdbg_clock_callback_t;
+use crate::state;
+use crate::util::UserData;
+use foxglove::schemas::Timestamp;
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// Minimum amount of clock time between two time broadcasts to Foxglove clients.
+const BROADCAST_INTERVAL_NANOS: u64 = 10_000_000;
+
+/// The clock every message, recording name and attachment is stamped with.
+static CLOCK: Mutex<Clock> = Mutex::new(Clock::System);
+
+/// Time most recently broadcast to Foxglove clients, in nanoseconds.
+static LAST_BROADCAST: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Copy)]
+enum Clock {
+    /// The system's wall-clock time.
+    System,
+    /// Time read from a callback supplied by the application, such as a simulator's clock.
+    Callback {
+        callback: unsafe extern "C" fn(*mut c_void) -> u64,
+        user_data: UserData,
+    },
+    /// Time set explicitly by the application, which stays put until it is set again.
+    Manual(Timestamp),
+}
+
+/// Returns the current time of the active clock.
+///
+/// With a custom clock, the time is also broadcast to connected Foxglove clients, so live
+/// playback follows it.
+pub fn now() -> Timestamp {
+    // Copy the clock out so the lock is not held while calling into the application,
+    // which may itself call back into foxdbg.
+    let clock = *CLOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let timestamp = match clock {
+        Clock::System => return Timestamp::now(),
+        Clock::Callback {
+            callback,
+            user_data,
+        } => from_nanos(unsafe { callback(user_data.get()) }),
+        Clock::Manual(timestamp) => timestamp,
+    };
+    broadcast(timestamp);
+    timestamp
+}
+
+/// Whether the application has replaced the system clock.
+pub fn is_custom() -> bool {
+    !matches!(
+        *CLOCK.lock().unwrap_or_else(PoisonError::into_inner),
+        Clock::System
+    )
+}
+
+/// Reads the time from `callback`, which returns nanoseconds since the UNIX epoch, or
+/// returns to the system clock if `callback` is `None`.
+pub fn set_callback(callback: foxdbg_clock_callback_t, user_data: *mut c_void) {
+    let clock = match callback {
+        Some(callback) => Clock::Callback {
+            callback,
+            user_data: UserData(user_data),
+        },
+        None => Clock::System,
+    };
+    *CLOCK.lock().unwrap_or_else(PoisonError::into_inner) = clock;
+}
+
+/// Sets the current time, which is used until it is set again.
+pub fn set_time(timestamp: Timestamp) {
+    *CLOCK.lock().unwrap_or_else(PoisonError::into_inner) = Clock::Manual(timestamp);
+    broadcast(timestamp);
+}
+
+/// Broadcasts `timestamp` to Foxglove clients, unless it is only slightly ahead of the
+/// last broadcast. Time going backwards, such as a replay being restarted, is always sent.
+fn broadcast(timestamp: Timestamp) {
+    let nanos = timestamp.total_nanos();
+    let last = LAST_BROADCAST.load(Ordering::Relaxed);
+    if nanos < last || nanos >= last.saturating_add(BROADCAST_INTERVAL_NANOS) {
+        LAST_BROADCAST.store(nanos, Ordering::Relaxed);
+        state::SERVER_STATE.broadcast_time(nanos);
+    }
+}
+
+fn from_nanos(nanos: u64) -> Timestamp {
+    let sec = u32::try_from(nanos / NANOS_PER_SEC).unwrap_or(u32::MAX);
+    Timestamp::new(sec, (nanos % NANOS_PER_SEC) as u32)
+}
@@ -0,0 +1,216 @@
+use crate::recording::{BlackboxLimits, DEFAULT_CHUNK_SIZE, McapOptions, RotationPolicy};
+use crate::{
+    foxdbg_compression_t, foxdbg_config_t, foxdbg_mcap_options_t, foxdbg_overflow_policy_t,
+};
+use std::ffi::{CStr, c_char};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Default host the WebSocket server binds to.
+const DEFAULT_HOST: &str = "127.0.0.1";
+
+/// Default port the WebSocket server listens on.
+const DEFAULT_PORT: u16 = 8765;
+
+/// Default length of history kept by the blackbox.
+const DEFAULT_BLACKBOX_DURATION_SEC: u32 = 30;
+
+/// Default memory budget of the blackbox.
+const DEFAULT_BLACKBOX_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Default number of received messages queued for subscription callbacks.
+const DEFAULT_RX_QUEUE_DEPTH: usize = 256;
+
+/// Default time a service call may take before its client is sent an error.
+const DEFAULT_SERVICE_TIMEOUT_MS: u32 = 5000;
+
+/// Rust-side configuration for the foxdbg-rs system.
+///
+/// This is the owned counterpart of the C-exported `foxdbg_config_t`. All C strings are
+/// copied out of the caller's memory when the configuration is converted, so the caller
+/// is free to release them once `foxdbg_init_with_config` returns.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub server_name: Option<String>,
+    pub recording_dir: PathBuf,
+    pub enable_server: bool,
+    pub enable_recording: bool,
+    pub rotation: RotationPolicy,
+    pub mcap: McapOptions,
+    /// Limits of the in-memory blackbox, or `None` if the blackbox is disabled.
+    pub blackbox: Option<BlackboxLimits>,
+    /// Whether to close the recording on panics and on exit without `shutdown`.
+    pub crash_safety: bool,
+    pub install_signal_handlers: bool,
+    /// How often buffered recording data is flushed to disk, or `None` to only write
+    /// completed chunks.
+    pub flush_interval: Option<Duration>,
+    /// Number of received messages queued for subscription callbacks.
+    pub rx_queue_depth: usize,
+    /// What happens to messages received while the subscription queue is full.
+    pub rx_overflow_policy: foxdbg_overflow_policy_t,
+    /// How long a service call may take before its client is sent an error.
+    pub service_timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            host: DEFAULT_HOST.to_owned(),
+            port: DEFAULT_PORT,
+            server_name: None,
+            recording_dir: PathBuf::new(),
+            enable_server: true,
+            enable_recording: true,
+            rotation: RotationPolicy::default(),
+            mcap: McapOptions::default(),
+            blackbox: None,
+            crash_safety: false,
+            install_signal_handlers: false,
+            flush_interval: None,
+            rx_queue_depth: DEFAULT_RX_QUEUE_DEPTH,
+            rx_overflow_policy: foxdbg_overflow_policy_t::FOXDBG_OVERFLOW_DROP_OLDEST,
+            service_timeout: Duration::from_millis(DEFAULT_SERVICE_TIMEOUT_MS.into()),
+        }
+    }
+}
+
+impl Config {
+    /// Converts a C configuration struct into an owned `Config`.
+    ///
+    /// Any string field that is NULL falls back to its default value.
+    ///
+    /// # Safety
+    ///
+    /// Every non-NULL string pointer in `config` must point to a valid, NUL-terminated
+    /// C string.
+    pub(crate) unsafe fn from_c(config: &foxdbg_config_t) -> Self {
+        let defaults = Config::default();
+
+        Config {
+            host: unsafe { string_from_c(config.host) }.unwrap_or(defaults.host),
+            port: config.port,
+            server_name: unsafe { string_from_c(config.server_name) },
+            recording_dir: unsafe { string_from_c(config.recording_dir) }
+                .map(PathBuf::from)
+                .unwrap_or(defaults.recording_dir),
+            enable_server: config.enable_server,
+            enable_recording: config.enable_recording,
+            rotation: RotationPolicy {
+                max_file_size: (config.max_file_size > 0).then_some(config.max_file_size),
+                max_file_duration: (config.max_file_duration_sec > 0)
+                    .then(|| Duration::from_secs(config.max_file_duration_sec.into())),
+            },
+            mcap: McapOptions::from(&config.mcap),
+            blackbox: config.enable_blackbox.then(|| BlackboxLimits {
+                max_duration: (config.blackbox_duration_sec > 0)
+                    .then(|| Duration::from_secs(config.blackbox_duration_sec.into())),
+                max_bytes: (config.blackbox_max_bytes > 0).then_some(config.blackbox_max_bytes),
+            }),
+            crash_safety: config.enable_crash_safety,
+            install_signal_handlers: config.install_signal_handlers,
+            flush_interval: (config.flush_interval_ms > 0)
+                .then(|| Duration::from_millis(config.flush_interval_ms.into())),
+            rx_queue_depth: match config.rx_queue_depth {
+                0 => defaults.rx_queue_depth,
+                depth => depth as usize,
+            },
+            rx_overflow_policy: config.rx_overflow_policy,
+            service_timeout: match config.service_timeout_ms {
+                0 => defaults.service_timeout,
+                timeout_ms => Duration::from_millis(timeout_ms.into()),
+            },
+        }
+    }
+}
+
+impl From<&Config> for foxdbg_config_t {
+    /// Builds a C configuration struct mirroring the numeric and boolean fields of `Config`.
+    ///
+    /// String fields are left NULL, which selects their defaults when converted back.
+    fn from(config: &Config) -> Self {
+        foxdbg_config_t {
+            host: std::ptr::null(),
+            port: config.port,
+            server_name: std::ptr::null(),
+            recording_dir: std::ptr::null(),
+            enable_server: config.enable_server,
+            enable_recording: config.enable_recording,
+            max_file_size: config.rotation.max_file_size.unwrap_or(0),
+            max_file_duration_sec: config
+                .rotation
+                .max_file_duration
+                .map_or(0, |duration| duration.as_secs() as u32),
+            mcap: foxdbg_mcap_options_t::from(&config.mcap),
+            enable_blackbox: config.blackbox.is_some(),
+            blackbox_duration_sec: config.blackbox.map_or(
+                DEFAULT_BLACKBOX_DURATION_SEC,
+                |limits| {
+                    limits
+                        .max_duration
+                        .map_or(0, |duration| duration.as_secs() as u32)
+                },
+            ),
+            blackbox_max_bytes: config
+                .blackbox
+                .map_or(DEFAULT_BLACKBOX_MAX_BYTES, |limits| {
+                    limits.max_bytes.unwrap_or(0)
+                }),
+            enable_crash_safety: config.crash_safety,
+            install_signal_handlers: config.install_signal_handlers,
+            flush_interval_ms: config
+                .flush_interval
+                .map_or(0, |interval| interval.as_millis() as u32),
+            rx_queue_depth: config.rx_queue_depth as u32,
+            rx_overflow_policy: config.rx_overflow_policy,
+            service_timeout_ms: config.service_timeout.as_millis() as u32,
+        }
+    }
+}
+
+impl From<&foxdbg_mcap_options_t> for McapOptions {
+    fn from(options: &foxdbg_mcap_options_t) -> Self {
+        McapOptions {
+            compression: match options.compression {
+                foxdbg_compression_t::FOXDBG_COMPRESSION_NONE => None,
+                foxdbg_compression_t::FOXDBG_COMPRESSION_LZ4 => Some(mcap::Compression::Lz4),
+                foxdbg_compression_t::FOXDBG_COMPRESSION_ZSTD => Some(mcap::Compression::Zstd),
+            },
+            compression_level: options.compression_level,
+            chunk_size: match options.chunk_size {
+                0 => DEFAULT_CHUNK_SIZE,
+                chunk_size => chunk_size,
+            },
+            emit_message_indexes: options.emit_message_indexes,
+        }
+    }
+}
+
+impl From<&McapOptions> for foxdbg_mcap_options_t {
+    fn from(options: &McapOptions) -> Self {
+        foxdbg_mcap_options_t {
+            compression: match options.compression {
+                None => foxdbg_compression_t::FOXDBG_COMPRESSION_NONE,
+                Some(mcap::Compression::Lz4) => foxdbg_compression_t::FOXDBG_COMPRESSION_LZ4,
+                Some(mcap::Compression::Zstd) => foxdbg_compression_t::FOXDBG_COMPRESSION_ZSTD,
+            },
+            compression_level: options.compression_level,
+            chunk_size: options.chunk_size,
+            emit_message_indexes: options.emit_message_indexes,
+        }
+    }
+}
+
+/// Copies a possibly-NULL C string into an owned `String`, replacing invalid UTF-8.
+pub(crate) unsafe fn string_from_c(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    Some(
+        unsafe { CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
@@ -1,52 +1,292 @@
+use crate::channels::{dispatch, rx};
+use crate::clock;
+use crate::config::Config;
+use crate::crash;
+use crate::error::{Error, Result};
+use crate::listener::Listener;
+use crate::recording::{BlackboxSink, McapOptions, RecordingSink, Session};
+use crate::services;
 use crate::state::{self};
-use log;
+use foxglove::ToUnixNanos;
+use foxglove::websocket::Capability;
+use mcap::Attachment;
+use mcap::records::Metadata;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, PoisonError};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Initialises the foxdbg-rs system.
 ///
-/// This function should be called once at the beginning of the application's lifecycle.
-/// It performs two main tasks:
+/// This function should be called at the beginning of the application's lifecycle. Calling
+/// it again while already initialised is a no-op; after `shutdown` it may be called again
+/// to start a fresh server and recording. Depending on the supplied `config`, it performs
+/// up to three tasks:
 ///
-/// 1.  Starts the Foxglove WebSocket server in a separate thread, which allows clients
-///     to connect and receive data.
-/// 2.  Creates a new MCAP file with a timestamped name and initialises the global
-///     `MCAP_STATE` with the writer handle. This allows data to be written to the
-///     MCAP file from anywhere in the application.
-pub fn init() {
+/// 1.  Starts the Foxglove WebSocket server in a separate thread, bound to the configured
+///     host and port, which allows clients to connect and receive data.
+/// 2.  Creates a new MCAP recording with a timestamped name in the configured recording
+///     directory and initialises the global `MCAP_STATE` with the recording sink. This
+///     allows data to be written to the MCAP file from anywhere in the application.
+/// 3.  Starts buffering the most recent messages of every channel in memory, so they can
+///     be written out later with `dump_blackbox`.
+///
+/// If crash safety is enabled, it also installs the handlers that close the recording when
+/// the process panics, exits or is interrupted without calling `shutdown`.
+///
+/// If the server or the recording cannot be started, everything started so far is stopped
+/// again and foxdbg-rs is left uninitialised.
+///
+/// Returns whether this call initialised foxdbg-rs, as opposed to finding it already
+/// initialised.
+pub fn init(config: &Config) -> Result<bool> {
+    let mut active_config = state::CONFIG.lock().unwrap_or_else(PoisonError::into_inner);
+    if active_config.is_some() {
+        log::warn!("foxdbg-rs is already initialised, ignoring repeated init");
+        return Ok(false);
+    }
+
+    if let Err(e) = start(config) {
+        stop();
+        return Err(e);
+    }
+
     log::info!("foxdbg-rs initialized");
+    *active_config = Some(config.clone());
+    Ok(true)
+}
+
+fn start(config: &Config) -> Result<()> {
+    dispatch::configure(config.rx_queue_depth, config.rx_overflow_policy);
+    services::configure(config.service_timeout);
+
+    if config.enable_server {
+        start_server(config)?;
+    } else {
+        log::info!("WebSocket server disabled by configuration");
+    }
+
+    if config.enable_recording {
+        let session = timestamped_session(&config.recording_dir);
+        open_recording(session, config, &config.mcap)?;
+    } else {
+        log::info!("MCAP recording disabled by configuration");
+    }
+
+    if config.crash_safety {
+        crash::install_panic_hook();
+        crash::install_exit_handler();
+    }
+    if config.install_signal_handlers {
+        crash::install_signal_handlers();
+    }
 
-    // Start the Foxglove WebSocket server in a background thread. The server will
-    // continue to run until the application exits.
-    if let Err(e) = foxglove::WebSocketServer::new().start_blocking() {
-        log::error!("Failed to start WebSocket server: {}", e);
+    if let Some(limits) = config.blackbox {
+        let sink = BlackboxSink::new(limits, config.mcap.write_options());
+        state::BLACKBOX_STATE.init(sink);
+        log::info!("Blackbox recording enabled");
     }
+    Ok(())
+}
+
+/// Starts the Foxglove WebSocket server in a background thread. The server will
+/// continue to run until `shutdown` is called.
+fn start_server(config: &Config) -> Result<()> {
+    let mut server = foxglove::WebSocketServer::new().bind(&config.host, config.port);
+    if let Some(name) = &config.server_name {
+        server = server.name(name);
+    }
+    // Clients publish to rx channels as JSON, and are told so when they connect. Services
+    // are advertised even when none exist yet, so they can be added while running.
+    let mut capabilities = vec![
+        Capability::ClientPublish,
+        Capability::Parameters,
+        Capability::Services,
+    ];
+    server = server
+        .supported_encodings([rx::RX_ENCODING])
+        .services(services::services())
+        .listener(Arc::new(Listener));
+
+    // Clients only follow the server's time if it is advertised when they connect, so the
+    // clock has to be replaced before the server starts.
+    let broadcasts_time = clock::is_custom();
+    if broadcasts_time {
+        capabilities.push(Capability::Time);
+    }
+    server = server.capabilities(capabilities);
+
+    let handle = server.start_blocking().map_err(Error::Server)?;
+    log::info!(
+        "WebSocket server listening on {}:{}",
+        config.host,
+        handle.port()
+    );
+    // Keep the handle in the global SERVER_STATE so the server can be stopped
+    // gracefully on shutdown instead of running until the process exits.
+    state::SERVER_STATE.init(handle, broadcasts_time);
+    Ok(())
+}
+
+/// Starts a new MCAP recording at runtime, replacing any recording already in progress.
+///
+/// If `path` is given, its file stem names the session and its parent directory holds the
+/// files, so `runs/auto.mcap` records to `runs/auto.mcap` or, with rotation enabled, to
+/// `runs/auto_0.mcap`, `runs/auto_1.mcap` and so on. Otherwise the session is named with
+/// the current UNIX timestamp in the configured recording directory. `options` overrides
+/// the MCAP options given at initialisation.
+///
+/// Every channel created so far is written into the new recording. Fails if foxdbg-rs is
+/// not initialised or the recording could not be started.
+pub fn start_recording(path: Option<&Path>, options: Option<&McapOptions>) -> Result<()> {
+    let active_config = state::CONFIG.lock().unwrap_or_else(PoisonError::into_inner);
+    let config = active_config.as_ref().ok_or(Error::NotInitialised)?;
+
+    let session = match path {
+        Some(path) => Session::from_path(path),
+        None => timestamped_session(&config.recording_dir),
+    };
+    open_recording(session, config, options.unwrap_or(&config.mcap))
+}
+
+/// Stops the MCAP recording in progress, closing its current file. Channels remain
+/// registered, so a later `start_recording` picks them up again.
+pub fn stop_recording() {
+    state::MCAP_STATE.close();
+}
 
-    // Create a new MCAP file for recording log data. The file is named with the
-    // current UNIX timestamp to ensure uniqueness.
+/// Names a session with the current UNIX timestamp.
+fn timestamped_session(dir: &Path) -> Session {
     let unix_time = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("Time is set before UNIX_EPOCH")
         .as_secs();
 
-    let mcap_file = format!("{:?}.mcap", unix_time);
-    match foxglove::McapWriter::new().create_new_buffered_file(&mcap_file) {
-        Ok(handle) => {
-            log::info!("MCAP writer started writing to '{}'", mcap_file);
-            // Initialise the global MCAP_STATE with the writer handle. This prevents the
-            // handle from being dropped, which would close the MCAP file.
-            state::MCAP_STATE.init(handle);
-        }
-        Err(e) => {
-            log::error!(
-                "Failed to start MCAP Writer for mcap file '{}': {}",
-                mcap_file,
-                e
-            );
-        }
-    };
+    Session::new(dir, unix_time.to_string())
+}
+
+/// Opens the first file of `session` and starts recording to it, using the rotation and
+/// flushing behaviour from `config`.
+fn open_recording(session: Session, config: &Config, options: &McapOptions) -> Result<()> {
+    let sink = RecordingSink::create(
+        session,
+        config.rotation,
+        options.write_options(),
+        config.flush_interval,
+    )?;
+    // Initialise the global MCAP_STATE with the recording sink. This registers the
+    // sink with the foxglove context and keeps it alive until it is stopped.
+    state::MCAP_STATE.init(sink);
+    Ok(())
 }
 
+/// Shuts down the foxdbg-rs system.
+///
+/// The WebSocket server is stopped gracefully, disconnecting any clients and releasing
+/// its port, and the MCAP file is closed so that its summary section is written. Calling
+/// it when not initialised is a no-op.
 pub fn shutdown() {
+    let mut active_config = state::CONFIG.lock().unwrap_or_else(PoisonError::into_inner);
+    if active_config.is_none() {
+        log::debug!("foxdbg-rs is not initialised, ignoring shutdown");
+        return;
+    }
+
     log::info!("Foxdbg-rs shutting down");
+    stop();
+    state::SESSION_RECORDS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+
+    *active_config = None;
+}
+
+/// Stops the server, the recording and the blackbox, whichever are running.
+fn stop() {
+    state::SERVER_STATE.stop();
     state::MCAP_STATE.close();
+    state::BLACKBOX_STATE.close();
+}
+
+/// Registers a metadata record for the session, such as the git hash or the driver.
+///
+/// The record is written into the current recording file straight away and into every
+/// recording file opened afterwards. Registering a record with the same `name` again
+/// replaces it for future files. Fails if it could not be written to the current file.
+pub fn add_metadata(name: &str, entries: BTreeMap<String, String>) -> Result<()> {
+    let metadata = Metadata {
+        name: name.to_owned(),
+        metadata: entries,
+    };
+
+    state::SESSION_RECORDS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .add_metadata(metadata.clone());
+
+    state::MCAP_STATE.write_metadata(&metadata)?;
+    Ok(())
+}
+
+/// Attaches the contents of the file at `path` to the session, named after the file.
+///
+/// Like metadata, the attachment is written into the current recording file and every
+/// recording file opened afterwards. Fails if the file could not be read or the attachment
+/// could not be written.
+pub fn attach_file(path: &Path, media_type: &str) -> Result<()> {
+    let data = std::fs::read(path).map_err(|e| {
+        Error::Io(std::io::Error::new(
+            e.kind(),
+            format!("Failed to read '{}': {}", path.display(), e),
+        ))
+    })?;
+    let name = path
+        .file_name()
+        .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy());
+    let create_time = std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or_else(|_| SystemTime::now());
+
+    add_attachment(&name, media_type, data, create_time)
+}
+
+/// Attaches an in-memory buffer to the session under `name`. See `attach_file`.
+pub fn attach_data(name: &str, media_type: &str, data: Vec<u8>) -> Result<()> {
+    add_attachment(name, media_type, data, SystemTime::now())
+}
+
+fn add_attachment(
+    name: &str,
+    media_type: &str,
+    data: Vec<u8>,
+    create_time: SystemTime,
+) -> Result<()> {
+    let attachment = Attachment {
+        log_time: clock::now().total_nanos(),
+        create_time: create_time.to_unix_nanos(),
+        name: name.to_owned(),
+        media_type: media_type.to_owned(),
+        data: Cow::Owned(data),
+    };
+
+    let result = state::MCAP_STATE.attach(&attachment);
+    state::SESSION_RECORDS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .add_attachment(attachment);
+
+    result?;
+    Ok(())
+}
+
+/// Writes the contents of the blackbox ring buffer to a new MCAP file at `path`.
+///
+/// Fails if the blackbox is not enabled or the file could not be written.
+pub fn dump_blackbox(path: &Path) -> Result<()> {
+    match state::BLACKBOX_STATE.dump(path)? {
+        true => Ok(()),
+        false => Err(Error::NotEnabled("blackbox")),
+    }
 }
@@ -0,0 +1,193 @@
+use crate::state;
+use std::cell::Cell;
+use std::ffi::c_void;
+use std::io;
+use std::mem;
+use std::panic::AssertUnwindSafe;
+use std::sync::Once;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread;
+
+/// Name of the thread that closes the recording when a signal arrives.
+const SIGNAL_THREAD_NAME: &str = "foxdbg-signals";
+
+static PANIC_HOOK: Once = Once::new();
+static SIGNAL_HANDLERS: Once = Once::new();
+static EXIT_HANDLER: Once = Once::new();
+
+/// Write end of the pipe the signal handlers pass signals to the signal thread through, or
+/// -1 before the handlers are installed.
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+thread_local! {
+    /// Whether a panic on this thread will be caught by `catch_unwind`.
+    static CATCHING_PANICS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Installs a panic hook that saves the current recording after the panic is reported.
+///
+/// Without the hook, a panic that takes down the process would leave the recording without
+/// its summary section. A panic that ends the process finalises the recording. Any other
+/// panic may still be caught, for example by joining the thread that panicked, so the
+/// recording is only flushed to disk and keeps going. Panics caught by [`catch_unwind`]
+/// leave the recording untouched. The previous hook is kept and runs first. Installing the
+/// hook more than once is a no-op.
+pub fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            previous(info);
+            if CATCHING_PANICS.get() {
+                return;
+            }
+            if panic_is_fatal() {
+                close_recording();
+            } else {
+                state::MCAP_STATE.flush_on_crash();
+            }
+        }));
+    });
+}
+
+/// Whether the panic being reported ends the process: every panic does when panics abort,
+/// and otherwise one on the main thread unwinds out of `main`.
+fn panic_is_fatal() -> bool {
+    cfg!(panic = "abort") || thread::current().name() == Some("main")
+}
+
+/// Runs `f`, catching any panic so that it does not unwind further, like
+/// [`std::panic::catch_unwind`]. The panic hook knows such panics are caught and does not
+/// close the recording for them.
+pub fn catch_unwind<R>(f: impl FnOnce() -> R) -> std::thread::Result<R> {
+    let was_catching = CATCHING_PANICS.replace(true);
+    // foxdbg-rs does not rely on state left behind by a panicking call, shared state is
+    // behind mutexes that are poisoned by the panic.
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING_PANICS.set(was_catching);
+    result
+}
+
+/// Installs SIGINT and SIGTERM handlers that close the current recording and then
+/// terminate the process with the default action for the signal.
+///
+/// Closing the recording is not async-signal-safe, so the handlers only pass the signal
+/// through a pipe to a dedicated thread, which does the work once the interrupted thread
+/// has let go of any locks. The handlers replace any handlers the application has
+/// installed for these signals.
+pub fn install_signal_handlers() {
+    SIGNAL_HANDLERS.call_once(|| {
+        let mut fds = [-1; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            log::warn!(
+                "Failed to create signal pipe, signal handlers not installed: {}",
+                io::Error::last_os_error()
+            );
+            return;
+        }
+        let [read_fd, write_fd] = fds;
+        for fd in fds {
+            unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+        }
+
+        let spawned = thread::Builder::new()
+            .name(SIGNAL_THREAD_NAME.to_owned())
+            .spawn(move || signal_loop(read_fd));
+        if let Err(e) = spawned {
+            log::warn!(
+                "Failed to start signal thread, signal handlers not installed: {}",
+                e
+            );
+            unsafe {
+                libc::close(read_fd);
+                libc::close(write_fd);
+            }
+            return;
+        }
+
+        SIGNAL_PIPE.store(write_fd, Ordering::Relaxed);
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            unsafe { libc::signal(signal, handler) };
+        }
+    });
+}
+
+/// Registers an `atexit` handler that closes the current recording when the process
+/// exits without calling `foxdbg_shutdown`.
+pub fn install_exit_handler() {
+    EXIT_HANDLER.call_once(|| {
+        if unsafe { libc::atexit(on_exit) } != 0 {
+            log::warn!("Failed to register exit handler");
+        }
+    });
+}
+
+/// Only makes async-signal-safe calls, handing the signal to the signal thread.
+///
+/// `errno` is restored before returning, so the interrupted code does not see it changed
+/// by the write.
+extern "C" fn on_signal(signal: libc::c_int) {
+    let errno = errno_location();
+    let saved_errno = unsafe { *errno };
+    let fd = SIGNAL_PIPE.load(Ordering::Relaxed);
+    unsafe {
+        libc::write(
+            fd,
+            &signal as *const libc::c_int as *const c_void,
+            mem::size_of::<libc::c_int>(),
+        );
+        *errno = saved_errno;
+    }
+}
+
+/// The calling thread's `errno`.
+fn errno_location() -> *mut libc::c_int {
+    #[cfg(any(target_os = "linux", target_os = "emscripten"))]
+    return unsafe { libc::__errno_location() };
+    #[cfg(target_os = "android")]
+    return unsafe { libc::__errno() };
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    return unsafe { libc::__error() };
+}
+
+/// Waits for a signal from the handlers, then closes the recording and terminates the
+/// process with the signal's default action.
+fn signal_loop(read_fd: libc::c_int) {
+    let mut signal: libc::c_int = 0;
+    loop {
+        let read = unsafe {
+            libc::read(
+                read_fd,
+                &mut signal as *mut libc::c_int as *mut c_void,
+                mem::size_of::<libc::c_int>(),
+            )
+        };
+        if read == mem::size_of::<libc::c_int>() as isize {
+            break;
+        }
+        if read < 0 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+            continue;
+        }
+        log::error!("Signal pipe closed, signals no longer close the recording");
+        return;
+    }
+
+    log::info!("Signal {} received, closing the recording", signal);
+    state::MCAP_STATE.close();
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+extern "C" fn on_exit() {
+    close_recording();
+}
+
+/// Finalises the current recording on a best-effort basis.
+///
+/// This runs from the panic hook and at exit, where the thread that crashed may hold the
+/// recording locks, so the recording is skipped rather than waited on if it is in use.
+fn close_recording() {
+    state::MCAP_STATE.close_on_crash();
+}
@@ -0,0 +1,150 @@
+use crate::crash;
+use crate::foxdbg_result_t;
+use foxglove::FoxgloveError;
+use std::cell::RefCell;
+use std::ffi::{CString, c_char};
+use std::fmt;
+use std::io;
+
+/// Errors reported by foxdbg-rs.
+///
+/// Every variant maps onto a `foxdbg_result_t` code returned across the FFI boundary, while
+/// its message is made available to C through `foxdbg_last_error`.
+#[derive(Debug)]
+pub enum Error {
+    /// A NULL pointer or otherwise unusable argument was passed in.
+    InvalidArgument(String),
+    /// The operation needs foxdbg-rs to be initialised first.
+    NotInitialised,
+    /// The named feature was not enabled in the configuration.
+    NotEnabled(&'static str),
+    /// No channel has the given id.
+    UnknownChannel(i64),
+    /// No channel has the given topic.
+    UnknownTopic(String),
+    /// No parameter has the given name.
+    UnknownParameter(String),
+    /// A channel was registered on a topic that already has a channel of another type.
+    ChannelConflict(String),
+    /// The size of the data does not match the type the channel expects.
+    SizeMismatch(String),
+    /// An image was written to a channel whose image info has not been set.
+    MissingChannelInfo(String),
+    /// The data could not be encoded into a message.
+    Encode(String),
+    Io(io::Error),
+    /// The MCAP recording could not be written.
+    Recording(FoxgloveError),
+    /// The WebSocket server could not be started.
+    Server(FoxgloveError),
+    /// foxdbg-rs panicked while handling the call.
+    Internal(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// The C result code corresponding to the error.
+    pub fn code(&self) -> foxdbg_result_t {
+        match self {
+            Error::InvalidArgument(_) => foxdbg_result_t::FOXDBG_RESULT_INVALID_ARGUMENT,
+            Error::NotInitialised => foxdbg_result_t::FOXDBG_RESULT_NOT_INITIALISED,
+            Error::NotEnabled(_) => foxdbg_result_t::FOXDBG_RESULT_NOT_ENABLED,
+            Error::UnknownChannel(_) | Error::UnknownTopic(_) => {
+                foxdbg_result_t::FOXDBG_RESULT_UNKNOWN_CHANNEL
+            }
+            Error::UnknownParameter(_) => foxdbg_result_t::FOXDBG_RESULT_UNKNOWN_PARAMETER,
+            Error::ChannelConflict(_) => foxdbg_result_t::FOXDBG_RESULT_CHANNEL_CONFLICT,
+            Error::SizeMismatch(_) => foxdbg_result_t::FOXDBG_RESULT_SIZE_MISMATCH,
+            Error::MissingChannelInfo(_) => foxdbg_result_t::FOXDBG_RESULT_MISSING_CHANNEL_INFO,
+            Error::Encode(_) => foxdbg_result_t::FOXDBG_RESULT_ENCODE_FAILED,
+            Error::Io(_) => foxdbg_result_t::FOXDBG_RESULT_IO_ERROR,
+            Error::Recording(_) => foxdbg_result_t::FOXDBG_RESULT_RECORDING_FAILED,
+            Error::Server(_) => foxdbg_result_t::FOXDBG_RESULT_SERVER_FAILED,
+            Error::Internal(_) => foxdbg_result_t::FOXDBG_RESULT_INTERNAL_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidArgument(msg) => write!(f, "Invalid argument: {}", msg),
+            Error::NotInitialised => write!(f, "foxdbg-rs is not initialised"),
+            Error::NotEnabled(feature) => write!(f, "The {} is not enabled", feature),
+            Error::UnknownChannel(id) => write!(f, "Unknown channel id {}", id),
+            Error::UnknownTopic(topic) => write!(f, "Unknown topic '{}'", topic),
+            Error::UnknownParameter(name) => write!(f, "Unknown parameter '{}'", name),
+            Error::ChannelConflict(msg) => write!(f, "Channel conflict: {}", msg),
+            Error::SizeMismatch(msg) => write!(f, "Invalid data size: {}", msg),
+            Error::MissingChannelInfo(topic) => {
+                write!(f, "Channel info has not been set for '{}'", topic)
+            }
+            Error::Encode(msg) => write!(f, "Failed to encode message: {}", msg),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Recording(e) => write!(f, "Recording failed: {}", e),
+            Error::Server(e) => write!(f, "WebSocket server failed: {}", e),
+            Error::Internal(msg) => write!(f, "Internal error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<FoxgloveError> for Error {
+    fn from(e: FoxgloveError) -> Self {
+        Error::Recording(e)
+    }
+}
+
+thread_local! {
+    /// Message of the most recent error returned to C on this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Runs the body of an exported function, converting its result into a `foxdbg_result_t`.
+///
+/// Errors are logged and stored as the thread's last error. Panics are caught so they never
+/// unwind into C, and are reported as `FOXDBG_RESULT_INTERNAL_ERROR`.
+pub fn ffi_result(f: impl FnOnce() -> Result<()>) -> foxdbg_result_t {
+    let error = match crash::catch_unwind(f) {
+        Ok(Ok(())) => return foxdbg_result_t::FOXDBG_RESULT_OK,
+        Ok(Err(e)) => e,
+        Err(payload) => {
+            let msg = payload
+                .downcast_ref::<&str>()
+                .map(|msg| msg.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_owned());
+            Error::Internal(msg)
+        }
+    };
+
+    log::warn!("{}", error);
+    let code = error.code();
+    set_last_error(&error);
+    code
+}
+
+fn set_last_error(error: &Error) {
+    // Interior NULs cannot be represented in a C string, so they are dropped.
+    let msg = error.to_string().replace('\0', "");
+    let msg = CString::new(msg).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(msg));
+}
+
+/// Returns a pointer to the message of the most recent error on this thread, or NULL if
+/// no call has failed on this thread.
+pub fn last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |msg| msg.as_ptr())
+    })
+}
@@ -1,71 +1,873 @@
 #![allow(non_camel_case_types)]
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::ffi::{CStr, c_int};
+use std::os::raw::c_char;
+use std::path::Path;
 
 // Declare internal modules
+mod api;
 mod channels;
+mod clock;
+mod config;
 mod core;
+mod crash;
+mod error;
+mod listener;
+mod params;
+mod recording;
+mod services;
 mod state;
+mod util;
 
 // C-exported types
 pub mod types;
+use channels::ChannelKey;
+use error::ffi_result;
 use foxglove::ChannelId;
-use state::CHANNELS;
 pub use types::*;
 
+// Safe Rust API
+pub use api::*;
+pub use channels::ChannelOptions;
+pub use config::Config;
+pub use error::{Error, Result};
+pub use foxglove::schemas::Timestamp;
+pub use mcap::Compression;
+pub use params::{ParamType, ParamValue};
+pub use recording::{BlackboxLimits, McapOptions, RotationPolicy};
+
 // FFI functions
 
+/// Returns a configuration populated with the default values used by `foxdbg_init`.
+#[unsafe(no_mangle)]
+pub extern "C" fn foxdbg_config_default() -> foxdbg_config_t {
+    foxdbg_config_t::from(&Config::default())
+}
+
+/// Returns a description of the most recent error on the calling thread, or NULL if no
+/// call has failed on this thread. The string remains valid until the next call on this
+/// thread fails.
+#[unsafe(no_mangle)]
+pub extern "C" fn foxdbg_last_error() -> *const c_char {
+    error::last_error()
+}
+
 /// Initialises the debugging system and starts the server thread.
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn foxdbg_init() {
-    let env = env_logger::Env::default().default_filter_or("info");
-    env_logger::init_from_env(env);
-    core::init();
+pub extern "C" fn foxdbg_init() -> foxdbg_result_t {
+    ffi_result(|| {
+        init_logger();
+        core::init(&Config::default()).map(|_| ())
+    })
+}
+
+/// Initialises the debugging system using the supplied configuration. Passing NULL is
+/// equivalent to calling `foxdbg_init`.
+///
+/// # Safety
+///
+/// `config` must be NULL or point to a valid `foxdbg_config_t` whose string fields are
+/// NULL or valid NUL-terminated C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_init_with_config(
+    config: *const foxdbg_config_t,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let config = match unsafe { config.as_ref() } {
+            Some(config) => unsafe { Config::from_c(config) },
+            None => Config::default(),
+        };
+        init_logger();
+        core::init(&config).map(|_| ())
+    })
 }
 
 /// Shuts down the server and cleans up resources.
 #[unsafe(no_mangle)]
-pub unsafe extern "C" fn foxdbg_shutdown() {
-    core::shutdown();
+pub extern "C" fn foxdbg_shutdown() -> foxdbg_result_t {
+    ffi_result(|| {
+        core::shutdown();
+        Ok(())
+    })
+}
+
+/// Starts recording to a new MCAP file at `path`, replacing any recording in progress.
+/// Passing NULL for `path` names the file with the current UNIX time in the configured
+/// recording directory. Passing NULL for `options` uses the options given at
+/// initialisation. Channels created before the recording started are included in it.
+///
+/// # Safety
+///
+/// `path` must be NULL or a valid NUL-terminated C string, and `options` must be NULL or
+/// point to a valid `foxdbg_mcap_options_t`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_start_recording(
+    path: *const c_char,
+    options: *const foxdbg_mcap_options_t,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let path = (!path.is_null()).then(|| unsafe { CStr::from_ptr(path) }.to_string_lossy());
+        let options = unsafe { options.as_ref() }.map(McapOptions::from);
+        core::start_recording(path.as_deref().map(Path::new), options.as_ref())
+    })
+}
+
+/// Stops the recording in progress, closing its MCAP file.
+#[unsafe(no_mangle)]
+pub extern "C" fn foxdbg_stop_recording() -> foxdbg_result_t {
+    ffi_result(|| {
+        core::stop_recording();
+        Ok(())
+    })
+}
+
+/// Adds a metadata record named `name` to the session, made of `count` key/value pairs
+/// taken from the `keys` and `values` arrays. The record is written into the current
+/// recording and into every recording file opened afterwards, so it can be called before
+/// recording starts.
+///
+/// # Safety
+///
+/// `name` must be a valid NUL-terminated C string, and `keys` and `values` must each point
+/// to `count` valid NUL-terminated C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_write_metadata(
+    name: *const c_char,
+    keys: *const *const c_char,
+    values: *const *const c_char,
+    count: usize,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let name = unsafe { str_arg(name, "name") }?;
+        if count > 0 && (keys.is_null() || values.is_null()) {
+            return Err(Error::InvalidArgument(
+                "keys and values must not be NULL".to_owned(),
+            ));
+        }
+        let mut entries = BTreeMap::new();
+        for i in 0..count {
+            let key = unsafe { str_arg(*keys.add(i), "key") }?;
+            let value = unsafe { str_arg(*values.add(i), "value") }?;
+            entries.insert(key.into_owned(), value.into_owned());
+        }
+        core::add_metadata(&name, entries)
+    })
+}
+
+/// Attaches the file at `path`, such as a YAML config or calibration file, to the session
+/// under its file name. Like metadata, it is written into the current recording and every
+/// recording file opened afterwards.
+///
+/// # Safety
+///
+/// `path` and `media_type` must be valid NUL-terminated C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_attach_file(
+    path: *const c_char,
+    media_type: *const c_char,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let path = unsafe { str_arg(path, "path") }?;
+        let media_type = unsafe { str_arg(media_type, "media_type") }?;
+        core::attach_file(Path::new(path.as_ref()), &media_type)
+    })
+}
+
+/// Attaches `size` bytes from `data` to the session under `name`. See
+/// `foxdbg_attach_file`.
+///
+/// # Safety
+///
+/// `name` and `media_type` must be valid NUL-terminated C strings, and `data` must point
+/// to at least `size` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_attach_data(
+    name: *const c_char,
+    media_type: *const c_char,
+    data: *const std::os::raw::c_void,
+    size: usize,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let name = unsafe { str_arg(name, "name") }?;
+        let media_type = unsafe { str_arg(media_type, "media_type") }?;
+        if data.is_null() && size > 0 {
+            return Err(Error::InvalidArgument("data must not be NULL".to_owned()));
+        }
+        let data = match size {
+            0 => Vec::new(),
+            _ => unsafe { std::slice::from_raw_parts(data as *const u8, size) }.to_vec(),
+        };
+        core::attach_data(&name, &media_type, data)
+    })
+}
+
+/// Writes the contents of the blackbox ring buffer to a new MCAP file at `path`. The
+/// file must not already exist. Returns `FOXDBG_RESULT_NOT_ENABLED` if the blackbox is
+/// not enabled.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_dump_blackbox(path: *const c_char) -> foxdbg_result_t {
+    ffi_result(|| {
+        let path = unsafe { str_arg(path, "path") }?;
+        core::dump_blackbox(Path::new(path.as_ref()))
+    })
+}
+
+/// Replaces the system clock with `callback`, which every message, attachment and server
+/// time broadcast is then stamped with, such as a simulator's or a log replay's clock.
+/// `user_data` is passed back to each call. Passing NULL for `callback` returns to the
+/// system clock. Set the clock before `foxdbg_init` so the server advertises its time to
+/// Foxglove clients.
+///
+/// # Safety
+///
+/// `callback` must remain safe to call with `user_data`, from any thread, until the clock
+/// is replaced.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_set_clock_callback(
+    callback: foxdbg_clock_callback_t,
+    user_data: *mut std::os::raw::c_void,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        clock::set_callback(callback, user_data);
+        Ok(())
+    })
 }
 
-/// Creates a new channel (topic) to publish data to Foxglove. It returns a channel ID for
-/// use with other functions.
+/// Sets the current time, which every message is stamped with until it is set again. Use
+/// it to step time manually, for example once per simulation tick. Like the clock
+/// callback, it replaces the system clock; see `foxdbg_set_clock_callback`. `nsec` may
+/// exceed one second, in which case the excess is carried into `sec`.
+#[unsafe(no_mangle)]
+pub extern "C" fn foxdbg_set_time(sec: u32, nsec: u32) -> foxdbg_result_t {
+    ffi_result(|| {
+        let timestamp = Timestamp::new_checked(sec, nsec)
+            .ok_or_else(|| Error::InvalidArgument("timestamp is out of range".to_owned()))?;
+        clock::set_time(timestamp);
+        Ok(())
+    })
+}
+
+/// Creates a new channel (topic) to publish data to Foxglove. The channel ID for use with
+/// other functions is stored in `channel_id`, which may be NULL if the channel is only
+/// written to by topic.
+///
+/// Adding a topic that already exists with the same type returns the existing channel.
+/// If the existing channel has a different type, `FOXDBG_RESULT_CHANNEL_CONFLICT` is
+/// returned instead.
+///
+/// Writes arriving faster than `target_hz` are dropped before any encoding or compression
+/// takes place, and counted in the channel's stats. Pass 0 to publish every write.
+///
+/// # Safety
+///
+/// `topic_name` must be a valid NUL-terminated C string, and `channel_id` must be NULL or
+/// point to writable memory for an `int`.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn foxdbg_add_channel(
-    topic_name: *const std::os::raw::c_char,
+    topic_name: *const c_char,
     channel_type: foxdbg_channel_type_t,
-    _target_hz: std::os::raw::c_int,
-) -> std::os::raw::c_int {
-    let topic_name_str = unsafe {
-        CStr::from_ptr(topic_name)
-            .to_str()
-            .unwrap_or("invalid_topic")
-    };
-    channels::manager::add_channel(topic_name_str, channel_type) as i32
+    target_hz: c_int,
+    channel_id: *mut c_int,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let topic_name = unsafe { str_arg(topic_name, "topic_name") }?;
+        let id = channels::manager::add_channel(&topic_name, channel_type, target_hz)?;
+        if let Some(channel_id) = unsafe { channel_id.as_mut() } {
+            *channel_id = id as c_int;
+        }
+        Ok(())
+    })
+}
+
+/// Removes a channel, unadvertising it to connected Foxglove clients and no longer
+/// recording it. Writing to the removed id afterwards returns
+/// `FOXDBG_RESULT_UNKNOWN_CHANNEL`, and its topic may be added again with any type.
+#[unsafe(no_mangle)]
+pub extern "C" fn foxdbg_remove_channel(channel_id: c_int) -> foxdbg_result_t {
+    ffi_result(|| channels::manager::remove_channel(ChannelId::new(channel_id as u64)))
 }
 
 /// Writes a data payload to a specified channel.
+///
+/// # Safety
+///
+/// `data` must point to at least `size` readable bytes laid out as the channel's type.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn foxdbg_write_channel(
     channel_id: c_int,
     data: *const std::os::raw::c_void,
     size: usize,
-) {
-    let channels = CHANNELS.lock().unwrap();
-    let channel_state = channels.get(&ChannelId::new(channel_id as u64)).unwrap();
-    unsafe { channels::writer::write_channel(channel_state, data, size) };
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let key = ChannelKey::Id(ChannelId::new(channel_id as u64));
+        unsafe { channels::write(key, None, data, size) }
+    })
 }
 
-/// Writes metadata for a channel, used for types like images to specify dimensions.
+/// Writes a data payload to a specified channel, stamped with the time the data was
+/// captured instead of the time of the call. The stamp is used both as the timestamp
+/// inside the message and as its log time in recordings. `nsec` may exceed one second, in
+/// which case the excess is carried into `sec`.
+///
+/// # Safety
+///
+/// `data` must point to at least `size` readable bytes laid out as the channel's type.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_write_channel_stamped(
+    channel_id: c_int,
+    sec: u32,
+    nsec: u32,
+    data: *const std::os::raw::c_void,
+    size: usize,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let timestamp = Timestamp::new_checked(sec, nsec)
+            .ok_or_else(|| Error::InvalidArgument("timestamp is out of range".to_owned()))?;
+        let key = ChannelKey::Id(ChannelId::new(channel_id as u64));
+        unsafe { channels::write(key, Some(timestamp), data, size) }
+    })
+}
+
+/// Writes metadata for a channel. Any channel takes a `foxdbg_channel_options_t` setting
+/// its display options, and image channels take a `foxdbg_image_info_t` giving the
+/// dimensions of their images. The struct passed is identified by `size`.
+///
+/// # Safety
+///
+/// `data` must point to at least `size` readable bytes laid out as the channel's info
+/// type, and the strings in a `foxdbg_channel_options_t` must be NULL or valid
+/// NUL-terminated C strings. They are copied, so need not outlive the call.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn foxdbg_write_channel_info(
     channel_id: c_int,
     data: *const std::os::raw::c_void,
     size: usize,
-) {
-    let mut channels = CHANNELS.lock().unwrap();
-    let mut channel_state = channels
-        .get_mut(&ChannelId::new(channel_id as u64))
-        .unwrap();
-    unsafe { channels::writer::write_channel_info(&mut channel_state, data, size) };
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let key = ChannelKey::Id(ChannelId::new(channel_id as u64));
+        unsafe { channels::write_info(key, data, size) }
+    })
+}
+
+/// Writes an image to an image channel together with its dimensions. Unlike a call to
+/// `foxdbg_write_channel_info` followed by `foxdbg_write_channel`, no write from another
+/// thread can change the dimensions before the image is written.
+///
+/// # Safety
+///
+/// `info` must point to a valid `foxdbg_image_info_t`, and `data` must point to at least
+/// `size` readable bytes of pixel data.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_write_image(
+    channel_id: c_int,
+    info: *const foxdbg_image_info_t,
+    data: *const std::os::raw::c_void,
+    size: usize,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let info = unsafe { info.as_ref() }
+            .ok_or_else(|| Error::InvalidArgument("info must not be NULL".to_owned()))?;
+        let key = ChannelKey::Id(ChannelId::new(channel_id as u64));
+        unsafe { channels::write_image(key, None, info, data, size) }
+    })
+}
+
+/// Writes an image like `foxdbg_write_image`, stamped with the time it was captured. See
+/// `foxdbg_write_channel_stamped`.
+///
+/// # Safety
+///
+/// `info` must point to a valid `foxdbg_image_info_t`, and `data` must point to at least
+/// `size` readable bytes of pixel data.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_write_image_stamped(
+    channel_id: c_int,
+    sec: u32,
+    nsec: u32,
+    info: *const foxdbg_image_info_t,
+    data: *const std::os::raw::c_void,
+    size: usize,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let timestamp = Timestamp::new_checked(sec, nsec)
+            .ok_or_else(|| Error::InvalidArgument("timestamp is out of range".to_owned()))?;
+        let info = unsafe { info.as_ref() }
+            .ok_or_else(|| Error::InvalidArgument("info must not be NULL".to_owned()))?;
+        let key = ChannelKey::Id(ChannelId::new(channel_id as u64));
+        unsafe { channels::write_image(key, Some(timestamp), info, data, size) }
+    })
+}
+
+/// Writes a data payload to the channel with the given topic, like the original foxdbg
+/// library. Prefer `foxdbg_write_channel` in hot loops, which skips the topic lookup.
+///
+/// # Safety
+///
+/// `topic_name` must be a valid NUL-terminated C string, and `data` must point to at
+/// least `size` readable bytes laid out as the channel's type.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_write_topic(
+    topic_name: *const c_char,
+    data: *const std::os::raw::c_void,
+    size: usize,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let topic_name = unsafe { str_arg(topic_name, "topic_name") }?;
+        unsafe { channels::write(ChannelKey::Topic(&topic_name), None, data, size) }
+    })
+}
+
+/// Writes metadata for the channel with the given topic. See `foxdbg_write_channel_info`.
+///
+/// # Safety
+///
+/// `topic_name` must be a valid NUL-terminated C string, and `data` must point to at
+/// least `size` readable bytes laid out as the channel's info type.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_write_topic_info(
+    topic_name: *const c_char,
+    data: *const std::os::raw::c_void,
+    size: usize,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let topic_name = unsafe { str_arg(topic_name, "topic_name") }?;
+        unsafe { channels::write_info(ChannelKey::Topic(&topic_name), data, size) }
+    })
+}
+
+/// Opens a frame on the calling thread, so that the writes it makes until
+/// `foxdbg_frame_commit` are published together. Writes in the frame are stamped with the
+/// given time unless made with `foxdbg_write_channel_stamped`, and their data is copied
+/// when they are made. Writes from other threads are not part of the frame.
+#[unsafe(no_mangle)]
+pub extern "C" fn foxdbg_frame_begin(sec: u32, nsec: u32) -> foxdbg_result_t {
+    ffi_result(|| {
+        let timestamp = Timestamp::new_checked(sec, nsec)
+            .ok_or_else(|| Error::InvalidArgument("timestamp is out of range".to_owned()))?;
+        channels::frame::begin(timestamp)
+    })
+}
+
+/// Publishes the writes queued since `foxdbg_frame_begin` on the calling thread, in the
+/// order they were made and without writes from other threads in between. Errors in
+/// individual writes were reported when they were made, and do not stop the commit.
+#[unsafe(no_mangle)]
+pub extern "C" fn foxdbg_frame_commit() -> foxdbg_result_t {
+    ffi_result(channels::frame::commit)
+}
+
+/// Closes the frame opened by `foxdbg_frame_begin` on the calling thread without publishing
+/// the writes queued in it, such as when the code producing them failed part way through.
+#[unsafe(no_mangle)]
+pub extern "C" fn foxdbg_frame_abort() -> foxdbg_result_t {
+    ffi_result(channels::frame::abort)
+}
+
+/// Stores the number of writes published to and dropped from a channel in `stats`. Writes
+/// are dropped when they arrive faster than the channel's target rate.
+///
+/// # Safety
+///
+/// `stats` must point to writable memory for a `foxdbg_channel_stats_t`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_get_channel_stats(
+    channel_id: c_int,
+    stats: *mut foxdbg_channel_stats_t,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let stats = unsafe { stats.as_mut() }
+            .ok_or_else(|| Error::InvalidArgument("stats must not be NULL".to_owned()))?;
+        *stats = channels::stats(ChannelKey::Id(ChannelId::new(channel_id as u64)))?;
+        Ok(())
+    })
+}
+
+/// Creates a receive (rx) channel, which Foxglove clients can publish to on `topic_name`,
+/// for example from the Publish panel. The channel ID for use with `foxdbg_get_rx_channel`
+/// is stored in `channel_id`. Rx channel IDs are separate from the IDs of the channels
+/// written to.
+///
+/// Boolean, float, integer and pose channels can be received. Clients publish JSON, either
+/// a bare value or one in a `value` or `data` field for the scalar types, and an object
+/// with a `position` and an `orientation` for poses. Adding a topic that already exists
+/// with the same type returns the existing channel.
+///
+/// # Safety
+///
+/// `topic_name` must be a valid NUL-terminated C string, and `channel_id` must be NULL or
+/// point to writable memory for an `int`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_add_rx_channel(
+    topic_name: *const c_char,
+    channel_type: foxdbg_channel_type_t,
+    channel_id: *mut c_int,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let topic_name = unsafe { str_arg(topic_name, "topic_name") }?;
+        let id = channels::rx::add_rx_channel(&topic_name, channel_type)?;
+        if let Some(channel_id) = unsafe { channel_id.as_mut() } {
+            *channel_id = id as c_int;
+        }
+        Ok(())
+    })
+}
+
+/// Copies the latest value received on an rx channel into `data`, without waiting for one.
+/// `size` must be the size of the channel's type: a `bool`, `float`, `int32_t` or
+/// `foxdbg_pose_t`. `data` is left untouched until the first value is received.
+///
+/// `is_new`, which may be NULL, is set to whether the value was received since the channel
+/// was last read.
+///
+/// # Safety
+///
+/// `data` must point to at least `size` writable bytes, and `is_new` must be NULL or point
+/// to writable memory for a `bool`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_get_rx_channel(
+    channel_id: c_int,
+    data: *mut std::os::raw::c_void,
+    size: usize,
+    is_new: *mut bool,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let new = unsafe { channels::rx::get_rx_channel(channel_id as u64, data, size) }?;
+        if let Some(is_new) = unsafe { is_new.as_mut() } {
+            *is_new = new.unwrap_or(false);
+        }
+        Ok(())
+    })
+}
+
+/// Calls `callback` with every message Foxglove clients publish on `topic_name`, in the
+/// order they were received, registering the rx channel like `foxdbg_add_rx_channel` if it
+/// does not exist yet. The ID to unsubscribe with is stored in `subscription_id`.
+///
+/// Callbacks run one at a time on a dedicated dispatch thread, never on the WebSocket
+/// server's threads, so they may block or call back into foxdbg. Messages wait for the
+/// dispatch thread in a queue whose depth and overflow policy are set by `rx_queue_depth`
+/// and `rx_overflow_policy` in `foxdbg_config_t`. Polling the channel with
+/// `foxdbg_get_rx_channel` keeps working alongside subscriptions.
+///
+/// # Safety
+///
+/// `topic_name` must be a valid NUL-terminated C string, and `subscription_id` must be NULL
+/// or point to writable memory for an `int`. `callback` must be safe to call with
+/// `user_data` from another thread until it is unsubscribed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_subscribe_rx(
+    topic_name: *const c_char,
+    channel_type: foxdbg_channel_type_t,
+    callback: foxdbg_rx_callback_t,
+    user_data: *mut std::os::raw::c_void,
+    subscription_id: *mut c_int,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let topic_name = unsafe { str_arg(topic_name, "topic_name") }?;
+        let callback = callback
+            .ok_or_else(|| Error::InvalidArgument("callback must not be NULL".to_owned()))?;
+        let id = channels::rx::subscribe(
+            &topic_name,
+            channel_type,
+            channels::dispatch::c_callback(callback, user_data),
+        )?;
+        if let Some(subscription_id) = unsafe { subscription_id.as_mut() } {
+            *subscription_id = id as c_int;
+        }
+        Ok(())
+    })
+}
+
+/// Removes a subscription made with `foxdbg_subscribe_rx`. Once this returns, its callback
+/// is not running and will not be called again, so `user_data` may be freed. A callback may
+/// unsubscribe itself, in which case this returns without waiting for it.
+#[unsafe(no_mangle)]
+pub extern "C" fn foxdbg_unsubscribe_rx(subscription_id: c_int) -> foxdbg_result_t {
+    ffi_result(|| channels::dispatch::unsubscribe(subscription_id as u64))
+}
+
+/// Registers a float parameter that can be tuned from the Parameters panel in Foxglove,
+/// starting at `default_value`. Values set from Foxglove are clamped to `min..=max`; pass
+/// `-INFINITY` and `INFINITY` to leave the parameter unbounded. Read the current value with
+/// `foxdbg_get_param_float`.
+///
+/// Registering a name that already exists with the same type keeps its current value.
+/// Every change made from Foxglove is logged on `/foxdbg/parameters`, so recordings show
+/// when each parameter was tuned.
+///
+/// # Safety
+///
+/// `name` must be a valid NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_add_param_float(
+    name: *const c_char,
+    default_value: f32,
+    min: f32,
+    max: f32,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let name = unsafe { str_arg(name, "name") }?;
+        params::add_param(
+            &name,
+            ParamValue::Float(default_value),
+            min.into(),
+            max.into(),
+        )
+    })
+}
+
+/// Registers an integer parameter. See `foxdbg_add_param_float`.
+///
+/// # Safety
+///
+/// `name` must be a valid NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_add_param_int(
+    name: *const c_char,
+    default_value: i32,
+    min: i32,
+    max: i32,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let name = unsafe { str_arg(name, "name") }?;
+        params::add_param(
+            &name,
+            ParamValue::Integer(default_value),
+            min.into(),
+            max.into(),
+        )
+    })
+}
+
+/// Registers a boolean parameter, shown as a toggle in Foxglove. See
+/// `foxdbg_add_param_float`.
+///
+/// # Safety
+///
+/// `name` must be a valid NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_add_param_bool(
+    name: *const c_char,
+    default_value: bool,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let name = unsafe { str_arg(name, "name") }?;
+        params::add_param(&name, ParamValue::Boolean(default_value), 0.0, 1.0)
+    })
+}
+
+/// Copies the current value of a float parameter into `value`. Reading is cheap enough to
+/// do on every iteration of a control loop, and always returns a value as a whole.
+///
+/// # Safety
+///
+/// `name` must be a valid NUL-terminated C string, and `value` must point to writable
+/// memory for a `float`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_get_param_float(
+    name: *const c_char,
+    value: *mut f32,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let name = unsafe { str_arg(name, "name") }?;
+        let value = unsafe { value.as_mut() }
+            .ok_or_else(|| Error::InvalidArgument("value must not be NULL".to_owned()))?;
+        *value = params::get_param(&name)?;
+        Ok(())
+    })
+}
+
+/// Copies the current value of an integer parameter into `value`. See
+/// `foxdbg_get_param_float`.
+///
+/// # Safety
+///
+/// `name` must be a valid NUL-terminated C string, and `value` must point to writable
+/// memory for an `int32_t`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_get_param_int(
+    name: *const c_char,
+    value: *mut i32,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let name = unsafe { str_arg(name, "name") }?;
+        let value = unsafe { value.as_mut() }
+            .ok_or_else(|| Error::InvalidArgument("value must not be NULL".to_owned()))?;
+        *value = params::get_param(&name)?;
+        Ok(())
+    })
+}
+
+/// Copies the current value of a boolean parameter into `value`. See
+/// `foxdbg_get_param_float`.
+///
+/// # Safety
+///
+/// `name` must be a valid NUL-terminated C string, and `value` must point to writable
+/// memory for a `bool`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_get_param_bool(
+    name: *const c_char,
+    value: *mut bool,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let name = unsafe { str_arg(name, "name") }?;
+        let value = unsafe { value.as_mut() }
+            .ok_or_else(|| Error::InvalidArgument("value must not be NULL".to_owned()))?;
+        *value = params::get_param(&name)?;
+        Ok(())
+    })
+}
+
+/// Writes the current value of every parameter to `path` as a JSON object keyed by name,
+/// which can be edited by hand and read back with `foxdbg_load_params`.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_save_params(path: *const c_char) -> foxdbg_result_t {
+    ffi_result(|| {
+        let path = unsafe { str_arg(path, "path") }?;
+        params::save_params(Path::new(path.as_ref()))
+    })
+}
+
+/// Sets parameters to the values in a JSON file written by `foxdbg_save_params`. Values are
+/// clamped to the range of their parameter, and values for parameters that are not
+/// registered yet replace their default when they are. Nothing is applied if the file holds
+/// a value of the wrong type for its parameter.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_load_params(path: *const c_char) -> foxdbg_result_t {
+    ffi_result(|| {
+        let path = unsafe { str_arg(path, "path") }?;
+        params::load_params(Path::new(path.as_ref()))
+    })
+}
+
+/// Registers a service named `name` that Foxglove clients can call, for example from the
+/// Service Call panel, to trigger an action in the application. Each request is passed to
+/// `callback` as JSON, and the JSON it writes back is sent to the client as the response.
+/// `request_schema` is a JSON Schema describing the request, shown to clients so they can
+/// build one, or NULL to leave the request undescribed.
+///
+/// Each service runs its callback on a thread of its own, one call at a time, never on the
+/// WebSocket server's threads. Callbacks of different services can therefore run at the
+/// same time, and must lock any state they share. A call that has not completed within `service_timeout_ms` of
+/// `foxdbg_config_t` is answered with an error, and its response is discarded once the
+/// callback returns. Services may be added before or after `foxdbg_init`, and stay
+/// registered for the lifetime of the process.
+///
+/// # Safety
+///
+/// `name` must be a valid NUL-terminated C string, and `request_schema` must be NULL or a
+/// valid NUL-terminated C string. `callback` must be safe to call with `user_data` from
+/// another thread for the lifetime of the process.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn foxdbg_add_service(
+    name: *const c_char,
+    request_schema: *const c_char,
+    callback: foxdbg_service_callback_t,
+    user_data: *mut std::os::raw::c_void,
+) -> foxdbg_result_t {
+    ffi_result(|| {
+        let name = unsafe { str_arg(name, "name") }?;
+        let request_schema = unsafe { config::string_from_c(request_schema) };
+        let callback = callback
+            .ok_or_else(|| Error::InvalidArgument("callback must not be NULL".to_owned()))?;
+        services::add_service(
+            &name,
+            request_schema.as_deref(),
+            services::c_handler(callback, user_data),
+        )
+    })
+}
+
+/// Installs the logger. The global logger can only be set once per process, so repeated
+/// calls after the first are ignored.
+pub(crate) fn init_logger() {
+    let env = env_logger::Env::default().default_filter_or("info");
+    let _ = env_logger::try_init_from_env(env);
+}
+
+/// Borrows a C string argument, replacing invalid UTF-8. Fails if `ptr` is NULL.
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Result<Cow<'a, str>> {
+    if ptr.is_null() {
+        return Err(Error::InvalidArgument(format!("{} must not be NULL", name)));
+    }
+    Ok(unsafe { CStr::from_ptr(ptr) }.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::c_void;
+    use std::{mem, ptr};
+
+    fn add_channel(topic: &CStr, channel_type: foxdbg_channel_type_t) -> c_int {
+        let mut channel_id = 0;
+        let result =
+            unsafe { foxdbg_add_channel(topic.as_ptr(), channel_type, 0, &mut channel_id) };
+        assert_eq!(result, foxdbg_result_t::FOXDBG_RESULT_OK);
+        channel_id
+    }
+
+    #[test]
+    fn keeps_working_after_a_panic_while_holding_the_channels_lock() {
+        let _ = std::thread::spawn(|| {
+            let _channels = state::CHANNELS.lock();
+            panic!("poisoning the channels lock");
+        })
+        .join();
+        assert!(state::CHANNELS.is_poisoned());
+
+        let channel_id = add_channel(
+            c"/test/ffi/after_poison",
+            foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_FLOAT,
+        );
+        let value = 1.0f32;
+        let result = unsafe {
+            foxdbg_write_channel(
+                channel_id,
+                &value as *const f32 as *const c_void,
+                mem::size_of::<f32>(),
+            )
+        };
+        assert_eq!(result, foxdbg_result_t::FOXDBG_RESULT_OK);
+    }
+
+    #[test]
+    fn writes_an_empty_point_cloud_from_a_null_pointer() {
+        let channel_id = add_channel(
+            c"/test/ffi/empty_cloud",
+            foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_POINTCLOUD,
+        );
+        let result = unsafe { foxdbg_write_channel(channel_id, ptr::null(), 0) };
+        assert_eq!(result, foxdbg_result_t::FOXDBG_RESULT_OK);
+    }
+
+    #[test]
+    fn reports_a_zero_size_image_from_a_null_pointer_without_aborting() {
+        let channel_id = add_channel(
+            c"/test/ffi/empty_image",
+            foxdbg_channel_type_t::FOXDBG_CHANNEL_TYPE_IMAGE,
+        );
+        let info = foxdbg_image_info_t {
+            width: 0,
+            height: 0,
+            channels: 3,
+        };
+        // An empty image cannot be compressed, but must fail through the result.
+        let result = unsafe { foxdbg_write_image(channel_id, &info, ptr::null(), 0) };
+        assert_ne!(result, foxdbg_result_t::FOXDBG_RESULT_INTERNAL_ERROR);
+    }
 }
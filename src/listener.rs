@@ -0,0 +1,33 @@
+use crate::channels::rx;
+use crate::params;
+use foxglove::websocket::{Client, ClientChannel, Parameter, ServerListener};
+
+/// Receives the events of connected Foxglove clients and routes them to the parts of
+/// foxdbg-rs that handle them.
+///
+/// The callbacks run on the server's event loop, so they must not block.
+pub struct Listener;
+
+impl ServerListener for Listener {
+    fn on_message_data(&self, _client: Client, client_channel: &ClientChannel, payload: &[u8]) {
+        rx::receive(&client_channel.topic, &client_channel.encoding, payload);
+    }
+
+    fn on_get_parameters(
+        &self,
+        _client: Client,
+        param_names: Vec<String>,
+        _request_id: Option<&str>,
+    ) -> Vec<Parameter> {
+        params::parameters(&param_names)
+    }
+
+    fn on_set_parameters(
+        &self,
+        _client: Client,
+        parameters: Vec<Parameter>,
+        _request_id: Option<&str>,
+    ) -> Vec<Parameter> {
+        params::set_parameters(parameters)
+    }
+}
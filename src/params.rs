@@ -0,0 +1,561 @@
+use crate::channels::schemas::ParameterChange;
+use crate::clock;
+use crate::error::{Error, Result};
+use crate::state::{self, ParamRegistry, ParamState};
+use foxglove::Channel;
+use foxglove::schemas::Timestamp;
+use foxglove::websocket::{Parameter, ParameterValue};
+use mcap::records::Metadata;
+use once_cell::sync::Lazy;
+use serde_json::{Map, Number, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::PoisonError;
+
+/// Topic every change made to a parameter by a Foxglove client is logged on.
+pub const PARAMS_TOPIC: &str = "/foxdbg/parameters";
+
+/// Name of the metadata record holding the parameter values each recording started with.
+pub const SNAPSHOT_METADATA_NAME: &str = "foxdbg.parameters";
+
+/// Created on the first change, so sessions without tuning record no empty channel.
+static CHANGES: Lazy<Channel<ParameterChange>> = Lazy::new(|| Channel::new(PARAMS_TOPIC));
+
+/// The value of a tunable parameter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamValue {
+    Float(f32),
+    Integer(i32),
+    Boolean(bool),
+}
+
+impl ParamValue {
+    fn type_name(self) -> &'static str {
+        match self {
+            ParamValue::Float(_) => "float",
+            ParamValue::Integer(_) => "integer",
+            ParamValue::Boolean(_) => "boolean",
+        }
+    }
+
+    pub(crate) fn as_f64(self) -> f64 {
+        match self {
+            // Widened through its shortest decimal form, so 0.1 is shown as 0.1 rather than
+            // 0.10000000149011612.
+            ParamValue::Float(value) => value.to_string().parse().unwrap_or(value.into()),
+            ParamValue::Integer(value) => value.into(),
+            ParamValue::Boolean(value) => f64::from(u8::from(value)),
+        }
+    }
+
+    fn to_json(self) -> Value {
+        match self {
+            ParamValue::Float(_) => {
+                Number::from_f64(self.as_f64()).map_or(Value::Null, Value::Number)
+            }
+            ParamValue::Integer(value) => value.into(),
+            ParamValue::Boolean(value) => value.into(),
+        }
+    }
+
+    fn to_parameter(self, name: &str) -> Parameter {
+        match self {
+            ParamValue::Float(_) => Parameter::float64(name, self.as_f64()),
+            ParamValue::Integer(value) => Parameter::integer(name, value.into()),
+            ParamValue::Boolean(value) => Parameter::bool(name, value),
+        }
+    }
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamValue::Float(value) => write!(f, "{}", value),
+            ParamValue::Integer(value) => write!(f, "{}", value),
+            ParamValue::Boolean(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// A type parameters can hold.
+pub trait ParamType: Copy {
+    fn into_value(self) -> ParamValue;
+    fn from_value(value: ParamValue) -> Option<Self>;
+}
+
+impl ParamType for f32 {
+    fn into_value(self) -> ParamValue {
+        ParamValue::Float(self)
+    }
+
+    fn from_value(value: ParamValue) -> Option<Self> {
+        match value {
+            ParamValue::Float(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl ParamType for i32 {
+    fn into_value(self) -> ParamValue {
+        ParamValue::Integer(self)
+    }
+
+    fn from_value(value: ParamValue) -> Option<Self> {
+        match value {
+            ParamValue::Integer(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl ParamType for bool {
+    fn into_value(self) -> ParamValue {
+        ParamValue::Boolean(self)
+    }
+
+    fn from_value(value: ParamValue) -> Option<Self> {
+        match value {
+            ParamValue::Boolean(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Registers a parameter that Foxglove clients can tune from the Parameters panel, starting
+/// at `default`. Values set by clients are clamped to `min..=max`, which is ignored for
+/// boolean parameters.
+///
+/// Registering a name that already exists with the same type keeps its current value, so
+/// a value tuned earlier survives the parameter being registered again.
+///
+/// # Errors
+///
+/// Returns an error if the name is empty, the range is empty or does not contain
+/// `default`, or the name is already registered with a different type.
+pub fn add_param(name: &str, default: ParamValue, min: f64, max: f64) -> Result<()> {
+    if name.is_empty() {
+        return Err(Error::InvalidArgument(
+            "parameter name must not be empty".to_owned(),
+        ));
+    }
+    if min.is_nan() || max.is_nan() || min > max {
+        return Err(Error::InvalidArgument(format!(
+            "parameter '{}' has an empty range {}..={}",
+            name, min, max
+        )));
+    }
+    if !matches!(default, ParamValue::Boolean(_)) && !(min..=max).contains(&default.as_f64()) {
+        return Err(Error::InvalidArgument(format!(
+            "default of parameter '{}' is outside its range {}..={}",
+            name, min, max
+        )));
+    }
+
+    let mut params = state::PARAMS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(existing) = params.get(name) {
+        if existing.value.type_name() != default.type_name() {
+            return Err(Error::InvalidArgument(format!(
+                "parameter '{}' is already registered with type {}",
+                name,
+                existing.value.type_name()
+            )));
+        }
+        return Ok(());
+    }
+
+    let mut state = ParamState {
+        value: default,
+        min,
+        max,
+    };
+    // A value loaded before the parameter was registered replaces its default.
+    if let Some(loaded) = params.take_pending(name) {
+        match coerce(&state, name, from_json(&loaded).as_ref()) {
+            Ok(value) => state.value = value,
+            Err(e) => log::warn!("Ignoring the loaded value of '{}': {}", name, e),
+        }
+    }
+    params.insert(name, state);
+    Ok(())
+}
+
+/// Returns the current value of a parameter.
+///
+/// # Errors
+///
+/// Returns an error if no parameter has the given name, or it holds another type.
+pub fn get_param<T: ParamType>(name: &str) -> Result<T> {
+    let value = state::PARAMS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(name)
+        .map(|state| state.value)
+        .ok_or_else(|| Error::UnknownParameter(name.to_owned()))?;
+    T::from_value(value).ok_or_else(|| {
+        Error::InvalidArgument(format!(
+            "parameter '{}' has type {}",
+            name,
+            value.type_name()
+        ))
+    })
+}
+
+/// Returns the parameters with the given names, or every parameter if `names` is empty.
+/// Names that are not registered are skipped.
+pub fn parameters(names: &[String]) -> Vec<Parameter> {
+    let params = state::PARAMS.lock().unwrap_or_else(PoisonError::into_inner);
+    if names.is_empty() {
+        return params
+            .iter()
+            .map(|(name, state)| state.value.to_parameter(name))
+            .collect();
+    }
+    names
+        .iter()
+        .filter_map(|name| Some(params.get(name)?.value.to_parameter(name)))
+        .collect()
+}
+
+/// Applies values set by a Foxglove client, returning the resulting value of each
+/// parameter so the client shows what was actually applied.
+///
+/// The values are applied together under one lock, so the application never reads some of
+/// them without the others. If any of them cannot be applied, none are, and the current
+/// values are returned instead. Each change is logged on `PARAMS_TOPIC`.
+pub fn set_parameters(parameters: Vec<Parameter>) -> Vec<Parameter> {
+    // Read before locking, since a custom clock calls into the application.
+    let timestamp = clock::now();
+
+    let mut params = state::PARAMS.lock().unwrap_or_else(PoisonError::into_inner);
+    let updates: Result<Vec<_>> = parameters
+        .iter()
+        .map(|parameter| {
+            let state = params
+                .get(&parameter.name)
+                .ok_or_else(|| Error::UnknownParameter(parameter.name.clone()))?;
+            let value = coerce(state, &parameter.name, parameter.value.as_ref())?;
+            Ok((parameter.name.clone(), value))
+        })
+        .collect();
+    let changes = match updates {
+        Ok(updates) => apply(&mut params, updates, timestamp),
+        Err(e) => {
+            log::warn!("Rejecting parameters set by a client: {}", e);
+            Vec::new()
+        }
+    };
+
+    let applied = parameters
+        .iter()
+        .filter_map(|parameter| {
+            let state = params.get(&parameter.name)?;
+            Some(state.value.to_parameter(&parameter.name))
+        })
+        .collect();
+    drop(params);
+
+    log_changes(changes, timestamp);
+    applied
+}
+
+/// Writes the current value of every parameter to `path` as a JSON object keyed by name.
+///
+/// Values loaded for parameters that have not been registered since are saved too, so a
+/// run that skips part of the application does not drop its tuning from the file.
+///
+/// # Errors
+///
+/// Returns an error if the file could not be written.
+pub fn save_params(path: &Path) -> Result<()> {
+    let json = {
+        let params = state::PARAMS.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut values: Map<String, Value> = params
+            .pending()
+            .map(|(name, value)| (name.to_owned(), value.clone()))
+            .collect();
+        for (name, state) in params.iter() {
+            values.insert(name.to_owned(), state.value.to_json());
+        }
+        serde_json::to_string_pretty(&values)
+            .map_err(|e| Error::Encode(format!("failed to encode parameters: {}", e)))?
+    };
+
+    std::fs::write(path, json + "\n").map_err(|e| {
+        Error::Io(io::Error::new(
+            e.kind(),
+            format!("Failed to write '{}': {}", path.display(), e),
+        ))
+    })?;
+    log::info!("Saved parameters to '{}'", path.display());
+    Ok(())
+}
+
+/// Sets parameters to the values in a JSON file written by `save_params`.
+///
+/// Values are clamped to the range of their parameter and applied together, like values
+/// set by a Foxglove client, and each change is logged on `PARAMS_TOPIC`. Values for
+/// parameters that are not registered yet are kept, and replace the default when the
+/// parameter is registered.
+///
+/// # Errors
+///
+/// Returns an error, without applying any of the values, if the file could not be read,
+/// is not a JSON object, or holds a value of the wrong type for its parameter.
+pub fn load_params(path: &Path) -> Result<()> {
+    let json = std::fs::read_to_string(path).map_err(|e| {
+        Error::Io(io::Error::new(
+            e.kind(),
+            format!("Failed to read '{}': {}", path.display(), e),
+        ))
+    })?;
+    let values: Map<String, Value> = serde_json::from_str(&json).map_err(|e| {
+        Error::InvalidArgument(format!(
+            "'{}' is not a JSON object of parameters: {}",
+            path.display(),
+            e
+        ))
+    })?;
+
+    // Read before locking, since a custom clock calls into the application.
+    let timestamp = clock::now();
+
+    let mut params = state::PARAMS.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut updates = Vec::new();
+    let mut pending = Vec::new();
+    for (name, value) in values {
+        match params.get(&name) {
+            Some(state) => {
+                let value = coerce(state, &name, from_json(&value).as_ref())?;
+                updates.push((name, value));
+            }
+            None => pending.push((name, value)),
+        }
+    }
+
+    if !pending.is_empty() {
+        log::info!(
+            "{} parameters loaded from '{}' are not registered yet",
+            pending.len(),
+            path.display()
+        );
+    }
+    for (name, value) in pending {
+        params.insert_pending(name, value);
+    }
+    let changes = apply(&mut params, updates, timestamp);
+    let changed = changes
+        .iter()
+        .filter_map(|change| Some(params.get(&change.name)?.value.to_parameter(&change.name)))
+        .collect();
+    drop(params);
+
+    log::info!("Loaded parameters from '{}'", path.display());
+    // Clients only ask for parameters when they open the panel, so are told of the change.
+    state::SERVER_STATE.publish_parameter_values(changed);
+    log_changes(changes, timestamp);
+    Ok(())
+}
+
+/// Returns a metadata record of the current value of every parameter, written into each
+/// new recording so it documents the values it started with. Returns `None` if no
+/// parameters are registered.
+pub fn snapshot() -> Option<Metadata> {
+    let params = state::PARAMS.lock().unwrap_or_else(PoisonError::into_inner);
+    let entries: BTreeMap<String, String> = params
+        .iter()
+        .map(|(name, state)| (name.to_owned(), state.value.to_string()))
+        .collect();
+    (!entries.is_empty()).then(|| Metadata {
+        name: SNAPSHOT_METADATA_NAME.to_owned(),
+        metadata: entries,
+    })
+}
+
+/// Sets parameters to already validated values, returning a record of each change.
+fn apply(
+    params: &mut ParamRegistry,
+    updates: Vec<(String, ParamValue)>,
+    timestamp: Timestamp,
+) -> Vec<ParameterChange> {
+    let mut changes = Vec::new();
+    for (name, value) in updates {
+        let Some(state) = params.get_mut(&name).filter(|state| state.value != value) else {
+            continue;
+        };
+        changes.push(ParameterChange {
+            timestamp: timestamp.into(),
+            value: value.as_f64(),
+            previous_value: state.value.as_f64(),
+            name,
+        });
+        state.value = value;
+    }
+    changes
+}
+
+fn log_changes(changes: Vec<ParameterChange>, timestamp: Timestamp) {
+    for change in changes {
+        log::info!(
+            "Parameter '{}' changed from {} to {}",
+            change.name,
+            change.previous_value,
+            change.value
+        );
+        CHANGES.log_with_time(&change, timestamp);
+    }
+}
+
+/// Converts a value set by a client or loaded from a file into the type of its parameter,
+/// clamped to its range. Whole numbers are accepted for every numeric parameter, since
+/// JSON does not tell them apart.
+fn coerce(state: &ParamState, name: &str, value: Option<&ParameterValue>) -> Result<ParamValue> {
+    let value = match (state.value, value) {
+        (ParamValue::Float(_), Some(ParameterValue::Float64(value))) if !value.is_nan() => {
+            ParamValue::Float(value.clamp(state.min, state.max) as f32)
+        }
+        (ParamValue::Float(_), Some(ParameterValue::Integer(value))) => {
+            ParamValue::Float((*value as f64).clamp(state.min, state.max) as f32)
+        }
+        (ParamValue::Integer(_), Some(ParameterValue::Integer(value))) => {
+            ParamValue::Integer((*value as f64).clamp(state.min, state.max) as i32)
+        }
+        (ParamValue::Integer(_), Some(ParameterValue::Float64(value))) if value.fract() == 0.0 => {
+            ParamValue::Integer(value.clamp(state.min, state.max) as i32)
+        }
+        (ParamValue::Boolean(_), Some(ParameterValue::Bool(value))) => ParamValue::Boolean(*value),
+        (current, _) => {
+            return Err(Error::InvalidArgument(format!(
+                "parameter '{}' must be set to a value of type {}",
+                name,
+                current.type_name()
+            )));
+        }
+    };
+    Ok(value)
+}
+
+/// Converts a JSON value into the parameter value a client would have sent for it.
+fn from_json(value: &Value) -> Option<ParameterValue> {
+    match value {
+        Value::Bool(value) => Some(ParameterValue::Bool(*value)),
+        Value::Number(number) => number
+            .as_i64()
+            .map(ParameterValue::Integer)
+            .or_else(|| number.as_f64().map(ParameterValue::Float64)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(value: ParamValue, min: f64, max: f64) -> ParamState {
+        ParamState { value, min, max }
+    }
+
+    fn coerce_to(state: &ParamState, value: ParameterValue) -> Result<ParamValue> {
+        coerce(state, "test", Some(&value))
+    }
+
+    #[test]
+    fn coerces_numbers_to_float_parameters() {
+        let state = param(ParamValue::Float(0.0), -10.0, 10.0);
+        assert_eq!(
+            coerce_to(&state, ParameterValue::Float64(2.5)).unwrap(),
+            ParamValue::Float(2.5)
+        );
+        assert_eq!(
+            coerce_to(&state, ParameterValue::Integer(3)).unwrap(),
+            ParamValue::Float(3.0)
+        );
+    }
+
+    #[test]
+    fn coerces_whole_numbers_to_integer_parameters() {
+        let state = param(ParamValue::Integer(0), -10.0, 10.0);
+        assert_eq!(
+            coerce_to(&state, ParameterValue::Integer(4)).unwrap(),
+            ParamValue::Integer(4)
+        );
+        assert_eq!(
+            coerce_to(&state, ParameterValue::Float64(-2.0)).unwrap(),
+            ParamValue::Integer(-2)
+        );
+        assert!(coerce_to(&state, ParameterValue::Float64(2.5)).is_err());
+    }
+
+    #[test]
+    fn clamps_numbers_to_the_range() {
+        let float = param(ParamValue::Float(0.0), 0.0, 1.0);
+        assert_eq!(
+            coerce_to(&float, ParameterValue::Float64(5.0)).unwrap(),
+            ParamValue::Float(1.0)
+        );
+        assert_eq!(
+            coerce_to(&float, ParameterValue::Integer(-5)).unwrap(),
+            ParamValue::Float(0.0)
+        );
+
+        let integer = param(ParamValue::Integer(0), -3.0, 3.0);
+        assert_eq!(
+            coerce_to(&integer, ParameterValue::Integer(i64::MAX)).unwrap(),
+            ParamValue::Integer(3)
+        );
+        assert_eq!(
+            coerce_to(&integer, ParameterValue::Float64(-100.0)).unwrap(),
+            ParamValue::Integer(-3)
+        );
+    }
+
+    #[test]
+    fn clamps_integers_to_the_i32_range_by_default() {
+        let state = param(ParamValue::Integer(0), i32::MIN.into(), i32::MAX.into());
+        assert_eq!(
+            coerce_to(&state, ParameterValue::Integer(i64::MIN)).unwrap(),
+            ParamValue::Integer(i32::MIN)
+        );
+    }
+
+    #[test]
+    fn only_accepts_booleans_for_boolean_parameters() {
+        let state = param(ParamValue::Boolean(false), 0.0, 0.0);
+        assert_eq!(
+            coerce_to(&state, ParameterValue::Bool(true)).unwrap(),
+            ParamValue::Boolean(true)
+        );
+        assert!(coerce_to(&state, ParameterValue::Integer(1)).is_err());
+        assert!(coerce_to(&state, ParameterValue::Float64(0.0)).is_err());
+    }
+
+    #[test]
+    fn rejects_mistyped_and_missing_values() {
+        let float = param(ParamValue::Float(0.0), -1.0, 1.0);
+        assert!(coerce_to(&float, ParameterValue::Bool(true)).is_err());
+        assert!(coerce_to(&float, ParameterValue::Float64(f64::NAN)).is_err());
+        assert!(coerce(&float, "test", None).is_err());
+
+        let integer = param(ParamValue::Integer(0), -1.0, 1.0);
+        assert!(coerce_to(&integer, ParameterValue::Bool(false)).is_err());
+    }
+
+    #[test]
+    fn reads_json_values_as_client_values() {
+        assert!(matches!(
+            from_json(&serde_json::json!(3)),
+            Some(ParameterValue::Integer(3))
+        ));
+        assert!(matches!(
+            from_json(&serde_json::json!(0.5)),
+            Some(ParameterValue::Float64(0.5))
+        ));
+        assert!(matches!(
+            from_json(&serde_json::json!(true)),
+            Some(ParameterValue::Bool(true))
+        ));
+        assert!(from_json(&serde_json::json!("3")).is_none());
+    }
+}
@@ -0,0 +1,351 @@
+use foxglove::bytes::Bytes;
+use foxglove::{ChannelId, FoxgloveError, Metadata, RawChannel, Sink, SinkId};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use super::file::RecordingFile;
+use crate::state::SESSION_RECORDS;
+
+/// Bounds on the amount of data held by a [`BlackboxSink`].
+///
+/// Messages are evicted oldest-first once either limit is exceeded. A limit of `None`
+/// leaves that dimension unbounded.
+#[derive(Debug, Clone, Copy)]
+pub struct BlackboxLimits {
+    pub max_duration: Option<Duration>,
+    pub max_bytes: Option<u64>,
+}
+
+/// A foxglove [`Sink`] that keeps the most recent messages of every channel in memory.
+///
+/// Nothing is written to disk until [`BlackboxSink::dump`] is called, at which point the
+/// buffered messages are written out as a complete MCAP file together with the
+/// definitions of every channel that has been advertised on the context.
+pub struct BlackboxSink {
+    sink_id: SinkId,
+    limits: BlackboxLimits,
+    options: mcap::WriteOptions,
+    inner: Mutex<RingBuffer>,
+}
+
+struct RingBuffer {
+    channels: HashMap<ChannelId, Arc<RawChannel>>,
+    messages: VecDeque<BufferedMessage>,
+    bytes: u64,
+    /// The latest log time seen, which the time limit is measured back from. Messages can
+    /// arrive out of log time order, so this is not necessarily the last message's.
+    newest_log_time: u64,
+}
+
+/// A message held in the ring buffer. The channel is kept alongside the message so that
+/// it can still be written if the channel is removed before the buffer is dumped.
+#[derive(Clone)]
+struct BufferedMessage {
+    channel: Arc<RawChannel>,
+    log_time: u64,
+    data: Bytes,
+}
+
+impl BlackboxSink {
+    /// Creates an empty blackbox. Dumps are written using the given MCAP `options`.
+    pub fn new(limits: BlackboxLimits, options: mcap::WriteOptions) -> Arc<Self> {
+        Arc::new(BlackboxSink {
+            sink_id: SinkId::next(),
+            limits,
+            options,
+            inner: Mutex::new(RingBuffer {
+                channels: HashMap::new(),
+                messages: VecDeque::new(),
+                bytes: 0,
+                newest_log_time: 0,
+            }),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RingBuffer> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Writes the current contents of the ring buffer to a new MCAP file at `path`.
+    ///
+    /// The buffer is snapshotted before writing, so logging continues unblocked while the
+    /// file is written and the buffer itself is left untouched.
+    pub fn dump(&self, path: &Path) -> Result<(), FoxgloveError> {
+        let (channels, messages) = {
+            let state = self.lock();
+            let channels: Vec<_> = state.channels.values().cloned().collect();
+            (channels, state.messages.clone())
+        };
+
+        let mut file = RecordingFile::create(path, self.options.clone())?;
+        file.write_records(
+            &SESSION_RECORDS
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        )?;
+        for channel in &channels {
+            file.add_channel(channel)?;
+        }
+        for message in &messages {
+            file.write(&message.channel, &message.data, message.log_time)?;
+        }
+        file.finish()?;
+
+        log::info!(
+            "Dumped {} blackbox messages to '{}'",
+            messages.len(),
+            path.display()
+        );
+        Ok(())
+    }
+}
+
+impl RingBuffer {
+    fn push(&mut self, message: BufferedMessage) {
+        self.bytes += message.data.len() as u64;
+        self.newest_log_time = self.newest_log_time.max(message.log_time);
+        self.messages.push_back(message);
+    }
+
+    fn evict(&mut self, limits: &BlackboxLimits) {
+        if let Some(max_bytes) = limits.max_bytes {
+            while self.bytes > max_bytes {
+                self.pop_front();
+            }
+        }
+
+        if let Some(max_duration) = limits.max_duration {
+            let cutoff = self
+                .newest_log_time
+                .saturating_sub(max_duration.as_nanos() as u64);
+            while self
+                .messages
+                .front()
+                .is_some_and(|message| message.log_time < cutoff)
+            {
+                self.pop_front();
+            }
+        }
+    }
+
+    fn pop_front(&mut self) {
+        if let Some(message) = self.messages.pop_front() {
+            self.bytes -= message.data.len() as u64;
+        }
+    }
+}
+
+impl Sink for BlackboxSink {
+    fn id(&self) -> SinkId {
+        self.sink_id
+    }
+
+    fn log(
+        &self,
+        channel: &RawChannel,
+        msg: &[u8],
+        metadata: &Metadata,
+    ) -> Result<(), FoxgloveError> {
+        let mut state = self.lock();
+        // Messages are only accepted for channels advertised to this sink, which gives
+        // access to the shared channel handle needed to write them out later.
+        let Some(channel) = state.channels.get(&channel.id()).cloned() else {
+            return Ok(());
+        };
+
+        state.push(BufferedMessage {
+            channel,
+            log_time: metadata.log_time,
+            data: Bytes::copy_from_slice(msg),
+        });
+        state.evict(&self.limits);
+        Ok(())
+    }
+
+    fn add_channels(&self, channels: &[&Arc<RawChannel>]) -> Option<Vec<ChannelId>> {
+        let mut state = self.lock();
+        for channel in channels {
+            state.channels.insert(channel.id(), Arc::clone(channel));
+        }
+        None
+    }
+
+    fn remove_channel(&self, channel: &RawChannel) {
+        self.lock().channels.remove(&channel.id());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use foxglove::{ChannelBuilder, Context};
+    use std::fs;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn channel(topic: &str) -> Arc<RawChannel> {
+        ChannelBuilder::new(topic)
+            .message_encoding("json")
+            .context(&Context::new())
+            .build_raw()
+            .unwrap()
+    }
+
+    fn ring_buffer() -> RingBuffer {
+        RingBuffer {
+            channels: HashMap::new(),
+            messages: VecDeque::new(),
+            bytes: 0,
+            newest_log_time: 0,
+        }
+    }
+
+    fn push(buffer: &mut RingBuffer, channel: &Arc<RawChannel>, log_time: u64, size: usize) {
+        buffer.push(BufferedMessage {
+            channel: Arc::clone(channel),
+            log_time,
+            data: Bytes::from(vec![0; size]),
+        });
+    }
+
+    fn log_times(buffer: &RingBuffer) -> Vec<u64> {
+        buffer
+            .messages
+            .iter()
+            .map(|message| message.log_time)
+            .collect()
+    }
+
+    #[test]
+    fn evicts_the_oldest_messages_beyond_the_byte_limit() {
+        let channel = channel("/a");
+        let limits = BlackboxLimits {
+            max_duration: None,
+            max_bytes: Some(25),
+        };
+        let mut buffer = ring_buffer();
+        for log_time in 1..=4 {
+            push(&mut buffer, &channel, log_time, 10);
+            buffer.evict(&limits);
+        }
+        assert_eq!(log_times(&buffer), [3, 4]);
+        assert_eq!(buffer.bytes, 20);
+    }
+
+    #[test]
+    fn keeps_every_message_without_limits() {
+        let channel = channel("/a");
+        let limits = BlackboxLimits {
+            max_duration: None,
+            max_bytes: None,
+        };
+        let mut buffer = ring_buffer();
+        for log_time in 0..100 {
+            push(&mut buffer, &channel, log_time * SECOND, 1);
+            buffer.evict(&limits);
+        }
+        assert_eq!(buffer.messages.len(), 100);
+        assert_eq!(buffer.bytes, 100);
+    }
+
+    #[test]
+    fn evicts_messages_older_than_the_time_limit() {
+        let channel = channel("/a");
+        let limits = BlackboxLimits {
+            max_duration: Some(Duration::from_secs(2)),
+            max_bytes: None,
+        };
+        let mut buffer = ring_buffer();
+        for log_time in 0..=5 {
+            push(&mut buffer, &channel, log_time * SECOND, 1);
+            buffer.evict(&limits);
+        }
+        assert_eq!(log_times(&buffer), [3 * SECOND, 4 * SECOND, 5 * SECOND]);
+        assert_eq!(buffer.bytes, 3);
+    }
+
+    #[test]
+    fn measures_the_time_limit_from_the_newest_log_time() {
+        let channel = channel("/a");
+        let limits = BlackboxLimits {
+            max_duration: Some(Duration::from_secs(2)),
+            max_bytes: None,
+        };
+        let mut buffer = ring_buffer();
+        push(&mut buffer, &channel, 0, 1);
+        push(&mut buffer, &channel, 10 * SECOND, 1);
+        // A late message with an old log time must not pull the window back.
+        push(&mut buffer, &channel, SECOND, 1);
+        buffer.evict(&limits);
+        assert_eq!(log_times(&buffer), [10 * SECOND, SECOND]);
+    }
+
+    /// Dumps `sink` to a temporary file and reads back its messages.
+    fn dump(sink: &BlackboxSink, name: &str) -> Vec<(String, u64, Vec<u8>)> {
+        let path = std::env::temp_dir().join(format!(
+            "foxdbg-blackbox-{}-{}.mcap",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        sink.dump(&path).unwrap();
+
+        let file = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        mcap::MessageStream::new(&file)
+            .unwrap()
+            .map(|message| {
+                let message = message.unwrap();
+                (
+                    message.channel.topic.clone(),
+                    message.log_time,
+                    message.data.to_vec(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dumps_the_buffered_messages_with_their_channels() {
+        let limits = BlackboxLimits {
+            max_duration: None,
+            max_bytes: Some(2),
+        };
+        let sink = BlackboxSink::new(limits, mcap::WriteOptions::default());
+        let (a, b) = (channel("/a"), channel("/b"));
+        sink.add_channels(&[&a, &b]);
+        for (channel, log_time, data) in [(&a, 1, b"1"), (&b, 2, b"2"), (&a, 3, b"3")] {
+            sink.log(channel, data, &Metadata { log_time }).unwrap();
+        }
+
+        assert_eq!(
+            dump(&sink, "dump"),
+            [
+                ("/b".to_owned(), 2, b"2".to_vec()),
+                ("/a".to_owned(), 3, b"3".to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn buffers_advertised_channels_and_keeps_messages_of_removed_ones() {
+        let limits = BlackboxLimits {
+            max_duration: None,
+            max_bytes: None,
+        };
+        let sink = BlackboxSink::new(limits, mcap::WriteOptions::default());
+        let (a, b) = (channel("/a"), channel("/b"));
+        sink.add_channels(&[&a]);
+        sink.log(&a, b"1", &Metadata { log_time: 1 }).unwrap();
+        sink.log(&b, b"2", &Metadata { log_time: 2 }).unwrap();
+        sink.remove_channel(&a);
+        sink.log(&a, b"3", &Metadata { log_time: 3 }).unwrap();
+
+        let expected = [("/a".to_owned(), 1, b"1".to_vec())];
+        assert_eq!(dump(&sink, "removed"), expected);
+        // Dumping leaves the buffer as it was.
+        assert_eq!(dump(&sink, "removed"), expected);
+    }
+}
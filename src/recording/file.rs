@@ -0,0 +1,219 @@
+use foxglove::{ChannelId, FoxgloveError, RawChannel};
+use mcap::Attachment;
+use mcap::records::Metadata;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use super::SessionRecords;
+use crate::params;
+
+/// Size of an MCAP message record excluding its payload: opcode, record length, channel id,
+/// sequence, log time and publish time.
+const MESSAGE_RECORD_OVERHEAD: u64 = 1 + 8 + 2 + 4 + 8 + 8;
+
+/// A single open MCAP file belonging to a recording session.
+///
+/// Channels and schemas are tracked per file, since every file must be self-contained
+/// and carry its own definitions.
+pub(super) struct RecordingFile {
+    path: PathBuf,
+    writer: mcap::Writer<SizeTrackingWriter<BufWriter<File>>>,
+    size: Arc<AtomicU64>,
+    // Bytes of message data logged since the file last grew. Compressed chunks only reach
+    // the file once they are complete, so this is added to the file size to estimate the
+    // final size of the file.
+    pending: u64,
+    flushed: u64,
+    opened_at: Instant,
+    flushed_at: Instant,
+    // ChannelId -> mcap file channel id.
+    channel_map: HashMap<ChannelId, u16>,
+    // Current message sequence number for each mcap file channel.
+    channel_sequence: HashMap<u16, u32>,
+}
+
+impl RecordingFile {
+    /// Creates a new file at `path` and writes the MCAP header to it.
+    ///
+    /// Fails with [`AlreadyExists`](io::ErrorKind::AlreadyExists) if the file already exists.
+    pub fn create(path: &Path, options: mcap::WriteOptions) -> Result<Self, FoxgloveError> {
+        let file = File::create_new(path)?;
+        let size = Arc::new(AtomicU64::new(0));
+        let writer = SizeTrackingWriter::new(BufWriter::new(file), size.clone());
+
+        Ok(RecordingFile {
+            path: path.to_path_buf(),
+            writer: options.create(writer)?,
+            size,
+            pending: 0,
+            flushed: 0,
+            opened_at: Instant::now(),
+            flushed_at: Instant::now(),
+            channel_map: HashMap::new(),
+            channel_sequence: HashMap::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Estimated size of the file, counting data written to disk and message data still
+    /// buffered in the open chunk.
+    pub fn size(&self) -> u64 {
+        self.flushed + self.pending
+    }
+
+    /// Wall-clock time since the file was opened.
+    pub fn age(&self) -> Duration {
+        self.opened_at.elapsed()
+    }
+
+    /// Wall-clock time since the file was opened or last flushed.
+    pub fn since_flush(&self) -> Duration {
+        self.flushed_at.elapsed()
+    }
+
+    /// Completes the open chunk and flushes everything written so far to disk, so that it
+    /// can be recovered even if the file is never finished.
+    pub fn flush(&mut self) -> Result<(), FoxgloveError> {
+        self.writer.flush()?;
+        self.flushed_at = Instant::now();
+        Ok(())
+    }
+
+    /// Writes the schema and channel records for `channel`, returning its mcap channel id.
+    /// Adding a channel that is already present in the file is a no-op.
+    pub fn add_channel(&mut self, channel: &RawChannel) -> Result<u16, FoxgloveError> {
+        if let Some(id) = self.channel_map.get(&channel.id()) {
+            return Ok(*id);
+        }
+
+        let schema_id = match channel.schema() {
+            Some(schema) => self
+                .writer
+                .add_schema(&schema.name, &schema.encoding, &schema.data)?,
+            None => 0, // 0 indicates a channel without a schema
+        };
+
+        let mcap_channel_id = self.writer.add_channel(
+            schema_id,
+            channel.topic(),
+            channel.message_encoding(),
+            channel.metadata(),
+        )?;
+
+        self.channel_map.insert(channel.id(), mcap_channel_id);
+        Ok(mcap_channel_id)
+    }
+
+    pub fn write(
+        &mut self,
+        channel: &RawChannel,
+        msg: &[u8],
+        log_time: u64,
+    ) -> Result<(), FoxgloveError> {
+        let channel_id = self.add_channel(channel)?;
+        let sequence = self
+            .channel_sequence
+            .entry(channel_id)
+            .and_modify(|seq| *seq += 1)
+            .or_insert(1);
+
+        self.writer.write_to_known_channel(
+            &mcap::records::MessageHeader {
+                channel_id,
+                sequence: *sequence,
+                log_time,
+                publish_time: log_time,
+            },
+            msg,
+        )?;
+
+        let flushed = self.size.load(Ordering::Relaxed);
+        if flushed != self.flushed {
+            self.flushed = flushed;
+            self.pending = 0;
+        } else {
+            self.pending += MESSAGE_RECORD_OVERHEAD + msg.len() as u64;
+        }
+        Ok(())
+    }
+
+    pub fn write_metadata(&mut self, metadata: &Metadata) -> Result<(), FoxgloveError> {
+        self.writer.write_metadata(metadata)?;
+        Ok(())
+    }
+
+    pub fn attach(&mut self, attachment: &Attachment) -> Result<(), FoxgloveError> {
+        self.writer.attach(attachment)?;
+        Ok(())
+    }
+
+    /// Writes every metadata record and attachment registered for the session, and a
+    /// snapshot of the current parameter values.
+    pub fn write_records(&mut self, records: &SessionRecords) -> Result<(), FoxgloveError> {
+        for metadata in records.metadata() {
+            self.write_metadata(metadata)?;
+        }
+        if let Some(snapshot) = params::snapshot() {
+            self.write_metadata(&snapshot)?;
+        }
+        for attachment in records.attachments() {
+            self.attach(attachment)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the summary section and flushes the file to disk.
+    pub fn finish(mut self) -> Result<(), FoxgloveError> {
+        self.writer.finish()?;
+        self.writer.into_inner().flush()?;
+        Ok(())
+    }
+}
+
+/// Wraps a writer and publishes the length of the data written through it.
+///
+/// The MCAP writer seeks backwards to patch chunk headers, so the length is tracked as
+/// the furthest position reached rather than the number of bytes passed to `write`.
+struct SizeTrackingWriter<W> {
+    inner: W,
+    position: u64,
+    size: Arc<AtomicU64>,
+}
+
+impl<W> SizeTrackingWriter<W> {
+    fn new(inner: W, size: Arc<AtomicU64>) -> Self {
+        SizeTrackingWriter {
+            inner,
+            position: 0,
+            size,
+        }
+    }
+}
+
+impl<W: Write> Write for SizeTrackingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.position += written as u64;
+        self.size.fetch_max(self.position, Ordering::Relaxed);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Seek> Seek for SizeTrackingWriter<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = self.inner.seek(pos)?;
+        Ok(self.position)
+    }
+}
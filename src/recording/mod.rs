@@ -0,0 +1,160 @@
+//! MCAP recording to disk.
+//!
+//! Recording is implemented as a foxglove `Sink` rather than through `foxglove::McapWriter`
+//! so that the sink can roll over to a new file without losing track of the channels that
+//! have been advertised on the context. The same file writer backs the in-memory blackbox,
+//! which only touches the disk when it is dumped.
+
+mod blackbox;
+mod file;
+mod records;
+mod sink;
+
+pub use blackbox::{BlackboxLimits, BlackboxSink};
+pub use records::SessionRecords;
+pub use sink::RecordingSink;
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Library identifier written into the header of every MCAP file.
+const LIBRARY_NAME: &str = concat!("foxdbg-rs/", env!("CARGO_PKG_VERSION"));
+
+/// Default target uncompressed size of each chunk, matching the mcap crate.
+pub(crate) const DEFAULT_CHUNK_SIZE: u64 = 768 * 1024;
+
+/// Compression and chunking options for the files of a recording.
+#[derive(Debug, Clone, Copy)]
+pub struct McapOptions {
+    pub compression: Option<mcap::Compression>,
+    /// Compression level, where 0 selects the compressor's default.
+    pub compression_level: u32,
+    pub chunk_size: u64,
+    pub emit_message_indexes: bool,
+}
+
+impl Default for McapOptions {
+    fn default() -> Self {
+        McapOptions {
+            compression: Some(mcap::Compression::Zstd),
+            compression_level: 0,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            emit_message_indexes: true,
+        }
+    }
+}
+
+impl McapOptions {
+    pub(crate) fn write_options(&self) -> mcap::WriteOptions {
+        mcap::WriteOptions::new()
+            .library(LIBRARY_NAME)
+            .compression(self.compression)
+            .compression_level(self.compression_level)
+            .chunk_size(Some(self.chunk_size))
+            .emit_message_indexes(self.emit_message_indexes)
+    }
+}
+
+/// Conditions under which a recording rolls over to a new file.
+///
+/// A limit of `None` disables rotation on that criterion. When both are `None` the whole
+/// session is written to a single file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RotationPolicy {
+    pub max_file_size: Option<u64>,
+    pub max_file_duration: Option<Duration>,
+}
+
+impl RotationPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_file_size.is_some() || self.max_file_duration.is_some()
+    }
+}
+
+/// Identifies the set of files written by one recording.
+///
+/// Without rotation the session is written to `{dir}/{name}.mcap`. With rotation enabled
+/// each file is named `{dir}/{name}_{index}.mcap`, with the index starting at 0.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub dir: PathBuf,
+    pub name: String,
+}
+
+impl Session {
+    pub fn new(dir: impl AsRef<Path>, name: impl Into<String>) -> Self {
+        Session {
+            dir: dir.as_ref().to_path_buf(),
+            name: name.into(),
+        }
+    }
+
+    /// Creates a session from a file path, using the file stem as the session name and the
+    /// parent directory as the directory.
+    pub fn from_path(path: &Path) -> Self {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let name = path.file_stem().map_or_else(
+            || "recording".to_owned(),
+            |stem| stem.to_string_lossy().into_owned(),
+        );
+        Session::new(dir, name)
+    }
+
+    fn file_path(&self, index: Option<u32>) -> PathBuf {
+        match index {
+            Some(index) => self.dir.join(format!("{}_{}.mcap", self.name, index)),
+            None => self.dir.join(format!("{}.mcap", self.name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_the_session_name_from_the_file_stem() {
+        let session = Session::from_path(Path::new("logs/run/foo.mcap"));
+        assert_eq!(session.dir, Path::new("logs/run"));
+        assert_eq!(session.name, "foo");
+
+        let session = Session::from_path(Path::new("foo.tar.mcap"));
+        assert_eq!(session.dir, Path::new(""));
+        assert_eq!(session.name, "foo.tar");
+    }
+
+    #[test]
+    fn names_a_path_without_a_stem_recording() {
+        let session = Session::from_path(Path::new("/"));
+        assert_eq!(session.dir, Path::new(""));
+        assert_eq!(session.name, "recording");
+    }
+
+    #[test]
+    fn names_a_single_file_after_the_session() {
+        let session = Session::from_path(Path::new("logs/foo.mcap"));
+        assert_eq!(session.file_path(None), Path::new("logs/foo.mcap"));
+    }
+
+    #[test]
+    fn numbers_rotated_files_from_zero() {
+        let session = Session::from_path(Path::new("logs/foo.mcap"));
+        assert_eq!(session.file_path(Some(0)), Path::new("logs/foo_0.mcap"));
+        assert_eq!(session.file_path(Some(12)), Path::new("logs/foo_12.mcap"));
+    }
+
+    #[test]
+    fn rotates_when_either_limit_is_set() {
+        assert!(!RotationPolicy::default().is_enabled());
+        let by_size = RotationPolicy {
+            max_file_size: Some(1024),
+            ..RotationPolicy::default()
+        };
+        assert!(by_size.is_enabled());
+        let by_duration = RotationPolicy {
+            max_file_duration: Some(Duration::from_secs(60)),
+            ..RotationPolicy::default()
+        };
+        assert!(by_duration.is_enabled());
+    }
+}
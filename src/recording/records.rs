@@ -0,0 +1,49 @@
+use mcap::Attachment;
+use mcap::records::Metadata;
+
+/// Metadata and attachments that describe a session rather than a single file.
+///
+/// Every recording file, including files opened by rotation and blackbox dumps, starts
+/// with the records held here, so each file documents the build, vehicle and parameters
+/// that produced it.
+#[derive(Debug, Default)]
+pub struct SessionRecords {
+    metadata: Vec<Metadata>,
+    attachments: Vec<Attachment<'static>>,
+}
+
+impl SessionRecords {
+    pub const fn new() -> Self {
+        SessionRecords {
+            metadata: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    /// Adds a metadata record, replacing any previous record with the same name.
+    pub fn add_metadata(&mut self, metadata: Metadata) {
+        self.metadata
+            .retain(|existing| existing.name != metadata.name);
+        self.metadata.push(metadata);
+    }
+
+    /// Adds an attachment, replacing any previous attachment with the same name.
+    pub fn add_attachment(&mut self, attachment: Attachment<'static>) {
+        self.attachments
+            .retain(|existing| existing.name != attachment.name);
+        self.attachments.push(attachment);
+    }
+
+    pub fn metadata(&self) -> &[Metadata] {
+        &self.metadata
+    }
+
+    pub fn attachments(&self) -> &[Attachment<'static>] {
+        &self.attachments
+    }
+
+    pub fn clear(&mut self) {
+        self.metadata.clear();
+        self.attachments.clear();
+    }
+}
@@ -0,0 +1,321 @@
+use foxglove::{ChannelId, FoxgloveError, Metadata, RawChannel, Sink, SinkId};
+use mcap::Attachment;
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use super::file::RecordingFile;
+use super::{RotationPolicy, Session};
+use crate::state::SESSION_RECORDS;
+
+/// A foxglove [`Sink`] that records every channel on the context to MCAP files.
+///
+/// The sink keeps its own registry of the channels advertised on the context. This lets
+/// it write the complete set of channel and schema definitions at the start of every
+/// file, including files opened later by rotation, rather than only once a channel first
+/// logs a message.
+pub struct RecordingSink {
+    sink_id: SinkId,
+    session: Session,
+    rotation: RotationPolicy,
+    options: mcap::WriteOptions,
+    flush_interval: Option<Duration>,
+    inner: Mutex<SinkState>,
+}
+
+struct SinkState {
+    channels: HashMap<ChannelId, Arc<RawChannel>>,
+    file: Option<RecordingFile>,
+    next_index: u32,
+}
+
+impl RecordingSink {
+    /// Creates a recording sink and opens the first file of the session.
+    ///
+    /// If a file with the session's name already exists, a numeric suffix is appended to
+    /// the session name until a free name is found. The name is settled here and kept by
+    /// every file opened later by rotation. The sink does not receive any data until it is
+    /// added to a foxglove `Context`.
+    ///
+    /// With a `flush_interval`, the open chunk is written to disk whenever a message is
+    /// logged at least that long after the last flush, bounding the data lost if the
+    /// process dies before the file is finished.
+    pub fn create(
+        session: Session,
+        rotation: RotationPolicy,
+        options: mcap::WriteOptions,
+        flush_interval: Option<Duration>,
+    ) -> Result<Arc<Self>, FoxgloveError> {
+        let base_name = session.name.clone();
+        let mut sink = RecordingSink {
+            sink_id: SinkId::next(),
+            session,
+            rotation,
+            options,
+            flush_interval,
+            inner: Mutex::new(SinkState {
+                channels: HashMap::new(),
+                file: None,
+                next_index: 0,
+            }),
+        };
+
+        let mut suffix = 0;
+        let file = loop {
+            match sink.open_file(0) {
+                Err(FoxgloveError::IoError(e)) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    suffix += 1;
+                    sink.session.name = format!("{}-{}", base_name, suffix);
+                }
+                result => break result?,
+            }
+        };
+
+        log::info!("MCAP writer started writing to '{}'", file.path().display());
+        let state = sink
+            .inner
+            .get_mut()
+            .expect("Failed to lock recording mutex");
+        state.file = Some(file);
+        state.next_index = 1;
+        Ok(Arc::new(sink))
+    }
+
+    /// Writes a metadata record into the current file.
+    pub fn write_metadata(&self, metadata: &mcap::records::Metadata) -> Result<(), FoxgloveError> {
+        match self.lock().file.as_mut() {
+            Some(file) => file.write_metadata(metadata),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes an attachment into the current file.
+    pub fn attach(&self, attachment: &Attachment) -> Result<(), FoxgloveError> {
+        match self.lock().file.as_mut() {
+            Some(file) => file.attach(attachment),
+            None => Ok(()),
+        }
+    }
+
+    /// Finalises the current file. Messages logged afterwards are discarded.
+    pub fn finish(&self) -> Result<(), FoxgloveError> {
+        let file = self.lock().file.take();
+        match file {
+            Some(file) => {
+                log::info!("MCAP writer finished writing '{}'", file.path().display());
+                file.finish()
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Finalises the current file like [`finish`](Self::finish), unless another thread is
+    /// holding the sink's lock, in which case nothing is done.
+    pub fn try_finish(&self) -> Result<(), FoxgloveError> {
+        let file = match self.inner.try_lock() {
+            Ok(mut state) => state.file.take(),
+            Err(_) => None,
+        };
+        match file {
+            Some(file) => file.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the open chunk of the current file to disk, unless another thread is holding
+    /// the sink's lock, in which case nothing is done.
+    pub fn try_flush(&self) -> Result<(), FoxgloveError> {
+        match self.inner.try_lock() {
+            Ok(mut state) => match state.file.as_mut() {
+                Some(file) => file.flush(),
+                None => Ok(()),
+            },
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SinkState> {
+        self.inner.lock().expect("Failed to lock recording mutex")
+    }
+
+    /// Creates the file with the given index and writes the session's metadata and
+    /// attachments into it.
+    fn open_file(&self, index: u32) -> Result<RecordingFile, FoxgloveError> {
+        let index = self.rotation.is_enabled().then_some(index);
+        let mut file = RecordingFile::create(&self.session.file_path(index), self.options.clone())?;
+        let records = SESSION_RECORDS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        file.write_records(&records)?;
+        Ok(file)
+    }
+
+    fn should_rotate(&self, file: &RecordingFile) -> bool {
+        let size_exceeded = self
+            .rotation
+            .max_file_size
+            .is_some_and(|max| file.size() >= max);
+        let duration_exceeded = self
+            .rotation
+            .max_file_duration
+            .is_some_and(|max| file.age() >= max);
+        size_exceeded || duration_exceeded
+    }
+
+    /// Closes the current file and opens the next one in the session, writing the
+    /// definitions of every known channel into it.
+    fn rotate(&self, state: &mut SinkState) -> Result<(), FoxgloveError> {
+        if let Some(file) = state.file.take() {
+            log::info!("MCAP writer finished writing '{}'", file.path().display());
+            if let Err(e) = file.finish() {
+                log::error!("Failed to close MCAP file during rotation: {}", e);
+            }
+        }
+
+        // Files left by an earlier run are skipped rather than renaming the session part
+        // way through, so every file of the session still matches `{name}_*.mcap`.
+        let mut file = loop {
+            let index = state.next_index;
+            state.next_index += 1;
+            match self.open_file(index) {
+                Err(FoxgloveError::IoError(e)) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    log::warn!(
+                        "Skipping '{}', which already exists",
+                        self.session.file_path(Some(index)).display()
+                    );
+                }
+                result => break result?,
+            }
+        };
+        for channel in state.channels.values() {
+            file.add_channel(channel)?;
+        }
+
+        log::info!("MCAP writer rotated to '{}'", file.path().display());
+        state.file = Some(file);
+        Ok(())
+    }
+}
+
+impl Sink for RecordingSink {
+    fn id(&self) -> SinkId {
+        self.sink_id
+    }
+
+    fn log(
+        &self,
+        channel: &RawChannel,
+        msg: &[u8],
+        metadata: &Metadata,
+    ) -> Result<(), FoxgloveError> {
+        let mut state = self.lock();
+
+        if state
+            .file
+            .as_ref()
+            .is_some_and(|file| self.should_rotate(file))
+            && let Err(e) = self.rotate(&mut state)
+        {
+            log::error!("Failed to rotate MCAP file, recording stopped: {}", e);
+            return Err(e);
+        }
+
+        let Some(file) = state.file.as_mut() else {
+            return Ok(());
+        };
+        file.write(channel, msg, metadata.log_time)?;
+        if self
+            .flush_interval
+            .is_some_and(|interval| file.since_flush() >= interval)
+        {
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    fn add_channels(&self, channels: &[&Arc<RawChannel>]) -> Option<Vec<ChannelId>> {
+        let mut state = self.lock();
+        for channel in channels {
+            state.channels.insert(channel.id(), Arc::clone(channel));
+            if let Some(file) = state.file.as_mut()
+                && let Err(e) = file.add_channel(channel)
+            {
+                log::error!(
+                    "Failed to add channel '{}' to MCAP file: {}",
+                    channel.topic(),
+                    e
+                );
+            }
+        }
+        None
+    }
+
+    fn remove_channel(&self, channel: &RawChannel) {
+        self.lock().channels.remove(&channel.id());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Creates an empty directory of its own for a test.
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("foxdbg-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn rotating() -> RotationPolicy {
+        RotationPolicy {
+            max_file_size: Some(1 << 20),
+            ..RotationPolicy::default()
+        }
+    }
+
+    fn current_file(sink: &RecordingSink) -> PathBuf {
+        sink.lock().file.as_ref().unwrap().path().to_path_buf()
+    }
+
+    #[test]
+    fn settles_a_free_session_name_when_the_recording_starts() {
+        let dir = temp_dir("sink-clash");
+        fs::write(dir.join("foo.mcap"), b"earlier run").unwrap();
+
+        let session = Session::new(&dir, "foo");
+        let sink =
+            RecordingSink::create(session, RotationPolicy::default(), Default::default(), None)
+                .unwrap();
+        assert_eq!(current_file(&sink), dir.join("foo-1.mcap"));
+        sink.finish().unwrap();
+        assert_eq!(fs::read(dir.join("foo.mcap")).unwrap(), b"earlier run");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_session_name_when_a_rotated_file_clashes() {
+        let dir = temp_dir("sink-rotate-clash");
+        fs::write(dir.join("foo_1.mcap"), b"earlier run").unwrap();
+
+        let sink = RecordingSink::create(
+            Session::new(&dir, "foo"),
+            rotating(),
+            Default::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(current_file(&sink), dir.join("foo_0.mcap"));
+
+        sink.rotate(&mut sink.lock()).unwrap();
+        assert_eq!(current_file(&sink), dir.join("foo_2.mcap"));
+        sink.rotate(&mut sink.lock()).unwrap();
+        assert_eq!(current_file(&sink), dir.join("foo_3.mcap"));
+        sink.finish().unwrap();
+        assert_eq!(fs::read(dir.join("foo_1.mcap")).unwrap(), b"earlier run");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,507 @@
+use crate::error::{Error, Result};
+use crate::state;
+use crate::util::{LazyThread, UserData};
+use foxglove::Schema;
+use foxglove::websocket::service::{Handler, Request, Responder, Service, ServiceSchema};
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::ffi::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Encoding of service requests and responses.
+const SERVICE_ENCODING: &str = "json";
+
+/// Encoding of the request schemas services are registered with.
+const SCHEMA_ENCODING: &str = "jsonschema";
+
+/// Name of the threads service callbacks run on, one for each service.
+const WORKER_THREAD_NAME: &str = "foxdbg-service";
+
+/// Name of the thread that answers calls which have not completed in time.
+const TIMEOUT_THREAD_NAME: &str = "foxdbg-service-timeout";
+
+/// Size of the buffer C callbacks write their response into.
+const RESPONSE_CAPACITY: usize = 64 * 1024;
+
+/// Default time a call may take before its client is sent an error, used until foxdbg is
+/// initialised.
+const DEFAULT_TIMEOUT_MS: u64 = 5000;
+
+/// A service's handler, called with the JSON request and returning the JSON response, or
+/// an error message to send to the client instead.
+pub type ServiceFn = Box<dyn FnMut(&[u8]) -> std::result::Result<Vec<u8>, String> + Send>;
+
+struct ServiceState {
+    request_schema: Option<String>,
+    /// Queues calls for the thread running the service's handler.
+    worker: Sender<Job>,
+}
+
+/// Sends the answer to a call, the JSON response or an error message, to its client.
+type Reply = Box<dyn FnOnce(std::result::Result<Vec<u8>, String>) + Send>;
+
+/// A call waiting for its service's worker thread.
+struct Job {
+    request: Vec<u8>,
+    call: Arc<PendingCall>,
+}
+
+/// A call that is answered either by its service's worker thread or, if it does not
+/// complete in time, by the timeout thread, whichever comes first.
+struct PendingCall {
+    service: String,
+    timeout: Duration,
+    /// Key of the call in `DEADLINES`.
+    deadline: (Instant, u64),
+    /// Taken by whichever thread answers the call.
+    reply: Mutex<Option<Reply>>,
+}
+
+impl PendingCall {
+    fn new(service: &str, timeout: Duration, reply: Reply) -> Arc<Self> {
+        Arc::new(PendingCall {
+            service: service.to_owned(),
+            timeout,
+            deadline: (
+                Instant::now() + timeout,
+                NEXT_CALL_ID.fetch_add(1, Ordering::Relaxed),
+            ),
+            reply: Mutex::new(Some(reply)),
+        })
+    }
+
+    /// Sends `result` to the client, unless the call was answered already. Returns whether
+    /// it was sent.
+    fn respond(&self, result: std::result::Result<Vec<u8>, String>) -> bool {
+        let reply = self
+            .reply
+            .lock()
+            .expect("Failed to lock service call mutex")
+            .take();
+        match reply {
+            Some(reply) => {
+                reply(result);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Answers the call from outside the timeout thread like `respond`, removing its
+    /// deadline so answered calls do not pile up until they would have timed out.
+    fn complete(&self, result: std::result::Result<Vec<u8>, String>) -> bool {
+        let sent = self.respond(result);
+        DEADLINES
+            .lock()
+            .expect("Failed to lock service deadlines mutex")
+            .remove(&self.deadline);
+        sent
+    }
+
+    fn is_answered(&self) -> bool {
+        self.reply
+            .lock()
+            .expect("Failed to lock service call mutex")
+            .is_none()
+    }
+}
+
+static SERVICES: Lazy<Mutex<BTreeMap<String, ServiceState>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+static TIMEOUT_MS: AtomicU64 = AtomicU64::new(DEFAULT_TIMEOUT_MS);
+
+/// Calls waiting to complete, keyed by their deadline and a sequence number to keep calls
+/// with the same deadline apart.
+static DEADLINES: Mutex<BTreeMap<(Instant, u64), Arc<PendingCall>>> = Mutex::new(BTreeMap::new());
+
+static NEXT_CALL_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Signalled whenever a call is added to `DEADLINES`.
+static DEADLINE_ADDED: Condvar = Condvar::new();
+
+static TIMEOUT_THREAD: LazyThread = LazyThread::new(TIMEOUT_THREAD_NAME);
+
+/// Routes the calls of one service from the WebSocket server to its worker thread.
+struct CallHandler {
+    name: String,
+    worker: Sender<Job>,
+}
+
+impl Handler for CallHandler {
+    fn call(&self, request: Request, responder: Responder) {
+        let timeout = Duration::from_millis(TIMEOUT_MS.load(Ordering::Relaxed));
+        let reply = Box::new(move |result| responder.respond(result));
+        let call = PendingCall::new(&self.name, timeout, reply);
+        if let Err(e) = watch_deadline(&call) {
+            log::error!("Failed to call service '{}': {}", self.name, e);
+            call.complete(Err(e.to_string()));
+            return;
+        }
+
+        let job = Job {
+            request: request.into_payload().to_vec(),
+            call,
+        };
+        if let Err(mpsc::SendError(job)) = self.worker.send(job) {
+            log::error!("Service '{}' has stopped", self.name);
+            job.call.complete(Err("the service has stopped".to_owned()));
+        }
+    }
+}
+
+/// Wraps a C callback and the `user_data` it was registered with.
+pub fn c_handler(
+    callback: unsafe extern "C" fn(
+        *const c_char,
+        usize,
+        *mut c_char,
+        usize,
+        *mut usize,
+        *mut c_void,
+    ) -> bool,
+    user_data: *mut c_void,
+) -> ServiceFn {
+    let user_data = UserData(user_data);
+    let mut response = vec![0u8; RESPONSE_CAPACITY];
+    Box::new(move |request| {
+        // NUL-terminated, so the callback can read the request as a string.
+        let mut request = request.to_vec();
+        request.push(0);
+        let mut response_size = 0;
+        let ok = unsafe {
+            callback(
+                request.as_ptr().cast(),
+                request.len() - 1,
+                response.as_mut_ptr().cast(),
+                response.len(),
+                &mut response_size,
+                user_data.get(),
+            )
+        };
+
+        if response_size > response.len() {
+            return Err(format!(
+                "response of {} bytes exceeds the {} byte response buffer",
+                response_size,
+                response.len()
+            ));
+        }
+        let data = &response[..response_size];
+        match ok {
+            true => Ok(data.to_vec()),
+            false if data.is_empty() => Err("the service failed".to_owned()),
+            false => Err(String::from_utf8_lossy(data).into_owned()),
+        }
+    })
+}
+
+/// Sets how long a call may take before its client is sent an error.
+pub fn configure(timeout: Duration) {
+    TIMEOUT_MS.store(timeout.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// Registers a service Foxglove clients can call with a JSON request, answered by
+/// `handler`. `request_schema` is a JSON Schema describing the request, shown to clients so
+/// they can build one. The service is advertised straight away if the server is running,
+/// and to every server started afterwards.
+///
+/// # Errors
+///
+/// Returns an error if the name is empty or already registered, the schema is not valid
+/// JSON, or the running server could not advertise the service.
+pub fn add_service(name: &str, request_schema: Option<&str>, handler: ServiceFn) -> Result<()> {
+    if name.is_empty() {
+        return Err(Error::InvalidArgument(
+            "service name must not be empty".to_owned(),
+        ));
+    }
+    if let Some(schema) = request_schema {
+        serde_json::from_str::<serde_json::Value>(schema).map_err(|e| {
+            Error::InvalidArgument(format!(
+                "request schema of service '{}' is not valid JSON: {}",
+                name, e
+            ))
+        })?;
+    }
+
+    let mut services = SERVICES.lock().expect("Failed to lock services mutex");
+    if services.contains_key(name) {
+        return Err(Error::InvalidArgument(format!(
+            "service '{}' is already registered",
+            name
+        )));
+    }
+    let state = ServiceState {
+        request_schema: request_schema.map(str::to_owned),
+        worker: start_worker(handler)?,
+    };
+    state::SERVER_STATE
+        .add_services(vec![build(name, &state)])
+        .map_err(Error::Server)?;
+    services.insert(name.to_owned(), state);
+    Ok(())
+}
+
+/// Builds the services to advertise from a newly started server.
+pub fn services() -> Vec<Service> {
+    SERVICES
+        .lock()
+        .expect("Failed to lock services mutex")
+        .iter()
+        .map(|(name, state)| build(name, state))
+        .collect()
+}
+
+/// Services are rebuilt for every server, since each server takes ownership of them.
+fn build(name: &str, state: &ServiceState) -> Service {
+    let mut schema = ServiceSchema::new(name);
+    if let Some(request_schema) = &state.request_schema {
+        schema = schema.with_request(
+            SERVICE_ENCODING,
+            Schema::new(
+                format!("{}Request", name),
+                SCHEMA_ENCODING,
+                request_schema.clone().into_bytes(),
+            ),
+        );
+    }
+    Service::builder(name, schema).handler(CallHandler {
+        name: name.to_owned(),
+        worker: state.worker.clone(),
+    })
+}
+
+/// Starts the thread that runs a service's handler, one call at a time, so a slow service
+/// does not hold up the others. The thread stops once the service is dropped, which only
+/// happens if it could not be registered.
+fn start_worker(mut handler: ServiceFn) -> Result<Sender<Job>> {
+    let (worker, jobs) = mpsc::channel();
+    thread::Builder::new()
+        .name(WORKER_THREAD_NAME.to_owned())
+        .spawn(move || {
+            for job in jobs {
+                run(&mut handler, job);
+            }
+        })
+        .map_err(|e| Error::Internal(format!("failed to start service thread: {}", e)))?;
+    Ok(worker)
+}
+
+/// Calls a service's handler with a queued request and answers the call with its response.
+fn run(handler: &mut ServiceFn, job: Job) {
+    let call = job.call;
+    // Its client has already been sent an error, and would ignore a late response.
+    if call.is_answered() {
+        log::warn!(
+            "Skipping a call to service '{}' that timed out while queued",
+            call.service
+        );
+        return;
+    }
+
+    let result = match panic::catch_unwind(AssertUnwindSafe(|| handler(&job.request))) {
+        // An empty response is not valid JSON.
+        Ok(Ok(response)) if response.is_empty() => Ok(b"{}".to_vec()),
+        Ok(result) => result,
+        Err(_) => {
+            log::error!("Service '{}' panicked in its handler", call.service);
+            Err("the service panicked".to_owned())
+        }
+    };
+    if !call.complete(result) {
+        log::debug!("Dropping the late response of service '{}'", call.service);
+    }
+}
+
+/// Has the timeout thread answer `call` with an error if it has not completed by its
+/// deadline, starting the thread on the first call.
+fn watch_deadline(call: &Arc<PendingCall>) -> Result<()> {
+    TIMEOUT_THREAD.ensure_started(timeout_loop)?;
+
+    DEADLINES
+        .lock()
+        .expect("Failed to lock service deadlines mutex")
+        .insert(call.deadline, Arc::clone(call));
+    DEADLINE_ADDED.notify_one();
+    Ok(())
+}
+
+/// Answers every call still outstanding at its deadline with a timeout error.
+fn timeout_loop() {
+    let mut deadlines = DEADLINES
+        .lock()
+        .expect("Failed to lock service deadlines mutex");
+    loop {
+        let now = Instant::now();
+        while let Some(entry) = deadlines.first_entry()
+            && entry.key().0 <= now
+        {
+            let call = entry.remove();
+            let timeout = call.timeout.as_millis();
+            if call.respond(Err(format!("timed out after {} ms", timeout))) {
+                log::warn!(
+                    "Service '{}' did not respond within {} ms",
+                    call.service,
+                    timeout
+                );
+            }
+        }
+
+        deadlines = match deadlines.keys().next() {
+            Some(&(deadline, _)) => {
+                DEADLINE_ADDED
+                    .wait_timeout(deadlines, deadline - now)
+                    .expect("Failed to lock service deadlines mutex")
+                    .0
+            }
+            None => DEADLINE_ADDED
+                .wait(deadlines)
+                .expect("Failed to lock service deadlines mutex"),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Receiver;
+
+    type Answer = std::result::Result<Vec<u8>, String>;
+
+    /// Creates a call whose answer is sent to the returned receiver.
+    fn call(timeout: Duration) -> (Arc<PendingCall>, Receiver<Answer>) {
+        let (answers, answer) = mpsc::channel();
+        let reply = Box::new(move |result| {
+            let _ = answers.send(result);
+        });
+        (PendingCall::new("test", timeout, reply), answer)
+    }
+
+    fn is_watched(call: &PendingCall) -> bool {
+        DEADLINES
+            .lock()
+            .expect("Failed to lock service deadlines mutex")
+            .contains_key(&call.deadline)
+    }
+
+    /// Runs `handler` on a call to it, returning the answer sent to the client.
+    fn run_call(mut handler: ServiceFn, request: &[u8]) -> Answer {
+        let (call, answer) = call(Duration::from_secs(60));
+        let job = Job {
+            request: request.to_vec(),
+            call,
+        };
+        run(&mut handler, job);
+        answer.try_recv().unwrap()
+    }
+
+    /// Echoes the request back as the response, or fails without a message if it is empty.
+    unsafe extern "C" fn echo(
+        request: *const c_char,
+        request_size: usize,
+        response: *mut c_char,
+        response_capacity: usize,
+        response_size: *mut usize,
+        _user_data: *mut c_void,
+    ) -> bool {
+        assert!(request_size <= response_capacity);
+        unsafe {
+            assert_eq!(*request.add(request_size), 0);
+            std::ptr::copy_nonoverlapping(request, response, request_size);
+            *response_size = request_size;
+        }
+        request_size > 0
+    }
+
+    /// Claims a response larger than the buffer it was given.
+    unsafe extern "C" fn overflow(
+        _request: *const c_char,
+        _request_size: usize,
+        _response: *mut c_char,
+        response_capacity: usize,
+        response_size: *mut usize,
+        _user_data: *mut c_void,
+    ) -> bool {
+        unsafe { *response_size = response_capacity + 1 };
+        true
+    }
+
+    #[test]
+    fn answers_with_the_handler_response() {
+        let handler: ServiceFn = Box::new(|request| Ok(request.to_vec()));
+        assert_eq!(run_call(handler, b"{\"a\":1}"), Ok(b"{\"a\":1}".to_vec()));
+    }
+
+    #[test]
+    fn answers_an_empty_response_with_an_empty_object() {
+        let handler: ServiceFn = Box::new(|_| Ok(Vec::new()));
+        assert_eq!(run_call(handler, b"{}"), Ok(b"{}".to_vec()));
+    }
+
+    #[test]
+    fn answers_handler_errors_and_panics_with_an_error() {
+        let handler: ServiceFn = Box::new(|_| Err("no estimator".to_owned()));
+        assert_eq!(run_call(handler, b"{}"), Err("no estimator".to_owned()));
+
+        let handler: ServiceFn = Box::new(|_| panic!("handler panicked"));
+        assert_eq!(
+            run_call(handler, b"{}"),
+            Err("the service panicked".to_owned())
+        );
+    }
+
+    #[test]
+    fn passes_requests_and_responses_through_c_callbacks() {
+        let handler = c_handler(echo, std::ptr::null_mut());
+        assert_eq!(run_call(handler, b"{\"a\":1}"), Ok(b"{\"a\":1}".to_vec()));
+
+        let handler = c_handler(echo, std::ptr::null_mut());
+        assert_eq!(run_call(handler, b""), Err("the service failed".to_owned()));
+
+        let handler = c_handler(overflow, std::ptr::null_mut());
+        assert!(run_call(handler, b"{}").is_err());
+    }
+
+    #[test]
+    fn times_out_calls_that_are_not_answered_in_time() {
+        let (call, answer) = call(Duration::from_millis(10));
+        watch_deadline(&call).unwrap();
+
+        let answer = answer.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(answer, Err("timed out after 10 ms".to_owned()));
+        assert!(!is_watched(&call));
+        assert!(!call.complete(Ok(b"{}".to_vec())));
+    }
+
+    #[test]
+    fn skips_calls_that_timed_out_while_queued() {
+        let (call, answer) = call(Duration::from_secs(60));
+        assert!(call.respond(Err("timed out".to_owned())));
+
+        let mut handler: ServiceFn = Box::new(|_| panic!("the handler must not be called"));
+        let job = Job {
+            request: b"{}".to_vec(),
+            call,
+        };
+        run(&mut handler, job);
+        assert_eq!(answer.try_recv().unwrap(), Err("timed out".to_owned()));
+        assert!(answer.try_recv().is_err());
+    }
+
+    #[test]
+    fn answered_calls_stop_being_watched() {
+        let (call, answer) = call(Duration::from_secs(60));
+        watch_deadline(&call).unwrap();
+        assert!(is_watched(&call));
+
+        assert!(call.complete(Ok(b"{}".to_vec())));
+        assert!(!is_watched(&call));
+        assert_eq!(answer.recv().unwrap(), Ok(b"{}".to_vec()));
+    }
+}
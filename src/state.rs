@@ -1,67 +1,463 @@
-use crate::{foxdbg_channel_type_t, foxdbg_image_info_t};
-use foxglove::{ChannelId, McapWriterHandle};
+use crate::channels::ChannelOptions;
+use crate::channels::rate_limit::RateLimiter;
+use crate::config::Config;
+use crate::params::ParamValue;
+use crate::recording::{BlackboxSink, RecordingSink, SessionRecords};
+use crate::{foxdbg_channel_type_t, foxdbg_image_info_t, foxdbg_pose_t};
+use foxglove::websocket::Parameter;
+use foxglove::websocket::service::Service;
+use foxglove::{ChannelId, Context, FoxgloveError, WebSocketServerHandle};
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufWriter;
-use std::sync::{Mutex, OnceLock};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+// The locks on this state recover from poisoning rather than panicking. A panic caught at
+// the FFI boundary can poison a lock, and the library must keep working afterwards.
 
 /// Global store mapping shannel id's to extra information needed for channels
-pub static CHANNELS: Lazy<Mutex<HashMap<ChannelId, ChannelState>>> =
-    Lazy::new(|| Mutex::new(HashMap::new()));
+pub static CHANNELS: Lazy<Mutex<ChannelRegistry>> =
+    Lazy::new(|| Mutex::new(ChannelRegistry::default()));
+
+/// Global store of the channels Foxglove clients can publish to, and the latest value
+/// received on each
+pub static RX_CHANNELS: Lazy<Mutex<RxRegistry>> = Lazy::new(|| Mutex::new(RxRegistry::default()));
+
+/// Global store of the parameters tunable from Foxglove, and their current values
+pub static PARAMS: Lazy<Mutex<ParamRegistry>> = Lazy::new(|| Mutex::new(ParamRegistry::default()));
+
+/// Global store for the configuration foxdbg-rs was initialised with, or `None` while
+/// foxdbg-rs is not initialised
+pub static CONFIG: Mutex<Option<Config>> = Mutex::new(None);
 
 /// Global store for the Mcap handle to keep the connection alive
 pub static MCAP_STATE: Lazy<McapState> = Lazy::new(McapState::new);
 
+/// Global store for metadata and attachments written into every new recording file
+pub static SESSION_RECORDS: Mutex<SessionRecords> = Mutex::new(SessionRecords::new());
+
+/// Global store for the in-memory blackbox recorder
+pub static BLACKBOX_STATE: Lazy<BlackboxState> = Lazy::new(BlackboxState::new);
+
+/// Global store for the WebSocket server handle so the server can be stopped on shutdown
+pub static SERVER_STATE: Lazy<ServerState> = Lazy::new(ServerState::new);
+
 #[derive(Debug)]
 pub struct ChannelState {
     pub channel_type: foxdbg_channel_type_t,
     pub channel_info: ChannelInfo,
     pub channel_topic: String,
+    pub rate_limiter: RateLimiter,
+}
+
+/// Information set on a channel with `foxdbg_write_channel_info`.
+#[derive(Debug, Default)]
+pub struct ChannelInfo {
+    pub options: ChannelOptions,
+    /// Dimensions of the images written to an image channel, which are required before
+    /// the first write.
+    pub image_info: Option<foxdbg_image_info_t>,
+}
+
+/// The state of every channel, indexed both by channel id and by topic.
+///
+/// Looking channels up by id is the cheapest way to write to them, while the topic index
+/// supports the topic-keyed API of the original foxdbg library.
+#[derive(Debug, Default)]
+pub struct ChannelRegistry {
+    channels: HashMap<ChannelId, ChannelState>,
+    topics: HashMap<String, ChannelId>,
+}
+
+impl ChannelRegistry {
+    pub fn insert(&mut self, channel_id: ChannelId, state: ChannelState) {
+        self.topics.insert(state.channel_topic.clone(), channel_id);
+        self.channels.insert(channel_id, state);
+    }
+
+    /// Removes a channel from both indexes, returning its state.
+    pub fn remove(&mut self, channel_id: &ChannelId) -> Option<ChannelState> {
+        let state = self.channels.remove(channel_id)?;
+        self.topics.remove(&state.channel_topic);
+        Some(state)
+    }
+
+    pub fn topic_id(&self, topic: &str) -> Option<ChannelId> {
+        self.topics.get(topic).copied()
+    }
+
+    pub fn get(&self, channel_id: &ChannelId) -> Option<&ChannelState> {
+        self.channels.get(channel_id)
+    }
+
+    pub fn get_mut(&mut self, channel_id: &ChannelId) -> Option<&mut ChannelState> {
+        self.channels.get_mut(channel_id)
+    }
+}
+
+#[derive(Debug)]
+pub struct RxChannelState {
+    pub channel_type: foxdbg_channel_type_t,
+    pub channel_topic: String,
+    /// The latest value received on the channel, if any.
+    pub latest: Option<RxValue>,
+    /// Whether `latest` was received since the channel was last read.
+    pub is_new: bool,
+}
+
+/// A value received from a Foxglove client, decoded into the type of its rx channel.
+#[derive(Debug, Clone, Copy)]
+pub enum RxValue {
+    Boolean(bool),
+    Float(f32),
+    Integer(i32),
+    Pose(foxdbg_pose_t),
+}
+
+/// The state of every rx channel, indexed both by id and by topic.
+///
+/// Rx channel ids are assigned here rather than by foxglove, since no foxglove channel
+/// exists for them, and are independent of the ids of the channels written to.
+#[derive(Debug, Default)]
+pub struct RxRegistry {
+    channels: HashMap<u64, RxChannelState>,
+    topics: HashMap<String, u64>,
+    next_id: u64,
+}
+
+impl RxRegistry {
+    /// Adds a channel under a new id, which is returned.
+    pub fn insert(&mut self, state: RxChannelState) -> u64 {
+        self.next_id += 1;
+        self.topics
+            .insert(state.channel_topic.clone(), self.next_id);
+        self.channels.insert(self.next_id, state);
+        self.next_id
+    }
+
+    pub fn topic_id(&self, topic: &str) -> Option<u64> {
+        self.topics.get(topic).copied()
+    }
+
+    pub fn get(&self, channel_id: u64) -> Option<&RxChannelState> {
+        self.channels.get(&channel_id)
+    }
+
+    pub fn get_mut(&mut self, channel_id: u64) -> Option<&mut RxChannelState> {
+        self.channels.get_mut(&channel_id)
+    }
 }
 
 #[derive(Debug)]
-pub enum ChannelInfo {
-    CompressedImageInfo(foxdbg_image_info_t),
-    NoInfo(),
+pub struct ParamState {
+    pub value: ParamValue,
+    /// The range values set by clients are clamped to. Unused by boolean parameters.
+    pub min: f64,
+    pub max: f64,
+}
+
+/// The state of every parameter, ordered by name so they are listed alphabetically.
+#[derive(Debug, Default)]
+pub struct ParamRegistry {
+    params: BTreeMap<String, ParamState>,
+    /// Values loaded from a file for parameters that were not registered yet.
+    pending: BTreeMap<String, serde_json::Value>,
+}
+
+impl ParamRegistry {
+    pub fn insert(&mut self, name: &str, state: ParamState) {
+        self.params.insert(name.to_owned(), state);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ParamState> {
+        self.params.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut ParamState> {
+        self.params.get_mut(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ParamState)> {
+        self.params
+            .iter()
+            .map(|(name, state)| (name.as_str(), state))
+    }
+
+    pub fn insert_pending(&mut self, name: String, value: serde_json::Value) {
+        self.pending.insert(name, value);
+    }
+
+    pub fn take_pending(&mut self, name: &str) -> Option<serde_json::Value> {
+        self.pending.remove(name)
+    }
+
+    pub fn pending(&self) -> impl Iterator<Item = (&str, &serde_json::Value)> {
+        self.pending
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
 }
 
-/// Manages the state of the MCAP writer handle.
+/// Manages the state of the MCAP recording sink.
 ///
-/// This structure uses a combination of `OnceLock`, `Mutex`, and `Option` to ensure that
-/// the MCAP writer is initialised only once, can be safely accessed from multiple threads,
-/// and is properly closed when the application shuts down.
+/// This structure uses a combination of `Mutex` and `Option` so that the recording sink can
+/// be safely accessed from multiple threads, properly closed when the application shuts
+/// down, and opened again if the application re-initialises.
 ///
-/// - `OnceLock`: Ensures that the `McapWriterHandle` is set only once. This is crucial
-///   because the handle should not be replaced once it's been created.
-/// - `Mutex`: Provides thread-safe mutable access to the handle. This is necessary
-///   because we need to be able to `take()` the handle to close it, which requires
+/// - `Mutex`: Provides thread-safe mutable access to the sink. This is necessary
+///   because we need to be able to `take()` the sink to close it, which requires
 ///   mutable access.
-/// - `Option`: Allows us to `take()` the handle out of the `Mutex` to close it. When
-///   `close()` is called, the `Option` is set to `None`, and the handle is dropped,
-///   which in turn closes the MCAP file.
+/// - `Option`: Allows us to `take()` the sink out of the `Mutex` to close it. When
+///   `close()` is called, the `Option` is set to `None`, the sink is removed from the
+///   foxglove context and its current file is finalised. A later `init()` stores a new
+///   sink in its place, which is how recordings are started and stopped at runtime while
+///   other threads keep logging.
 pub struct McapState {
-    writer: OnceLock<Mutex<Option<McapWriterHandle<BufWriter<File>>>>>,
+    writer: Mutex<Option<Arc<RecordingSink>>>,
 }
 
 impl McapState {
     fn new() -> Self {
         McapState {
-            writer: OnceLock::new(),
+            writer: Mutex::new(None),
+        }
+    }
+
+    /// Registers the sink with the default foxglove context so that it receives every
+    /// channel and message, closing any sink that was previously registered.
+    pub fn init(&self, sink: Arc<RecordingSink>) {
+        Context::get_default().add_sink(sink.clone());
+        let previous = self
+            .writer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .replace(sink);
+        if let Some(previous) = previous {
+            log::warn!("Replacing an MCAP writer that was still open");
+            close_writer(&previous);
+        }
+    }
+
+    /// Writes a metadata record into the current recording file, if recording.
+    pub fn write_metadata(&self, metadata: &mcap::records::Metadata) -> Result<(), FoxgloveError> {
+        match self.current() {
+            Some(sink) => sink.write_metadata(metadata),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes an attachment into the current recording file, if recording.
+    pub fn attach(&self, attachment: &mcap::Attachment) -> Result<(), FoxgloveError> {
+        match self.current() {
+            Some(sink) => sink.attach(attachment),
+            None => Ok(()),
         }
     }
 
-    pub fn init(&self, handle: McapWriterHandle<BufWriter<File>>) {
+    fn current(&self) -> Option<Arc<RecordingSink>> {
         self.writer
-            .set(Mutex::new(Some(handle)))
-            .expect("MCAP writer has already been initialized");
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn close(&self) {
+        let writer = self
+            .writer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(writer) = writer {
+            close_writer(&writer);
+        }
+    }
+
+    /// Writes buffered data of the current file to disk without waiting on locks held by
+    /// other threads, leaving the recording open.
+    ///
+    /// Used after a panic the process may survive. If the recording is in use, nothing is
+    /// written.
+    pub fn flush_on_crash(&self) {
+        let Ok(writer) = self.writer.try_lock() else {
+            return;
+        };
+        if let Some(writer) = writer.as_ref()
+            && let Err(e) = writer.try_flush()
+        {
+            log::error!("Failed to flush MCAP writer: {}", e);
+        }
+    }
+
+    /// Finalises the current file without waiting on locks held by other threads.
+    ///
+    /// Used when the process is about to terminate. If the recording is in use, for example
+    /// because the thread that panicked was writing to it, the file is left as it is. The
+    /// sink stays registered with the foxglove context but discards further messages.
+    pub fn close_on_crash(&self) {
+        let Ok(mut writer) = self.writer.try_lock() else {
+            return;
+        };
+        if let Some(writer) = writer.take()
+            && let Err(e) = writer.try_finish()
+        {
+            log::error!("Failed to close MCAP writer: {}", e);
+        }
+    }
+}
+
+fn close_writer(sink: &RecordingSink) {
+    Context::get_default().remove_sink(foxglove::Sink::id(sink));
+    if let Err(e) = sink.finish() {
+        log::error!("Failed to close MCAP writer: {}", e);
+    }
+}
+
+/// Manages the blackbox sink, if the blackbox is enabled.
+///
+/// The sink is registered with the default foxglove context on `init()` and removed on
+/// `close()`, which discards anything still held in the ring buffer.
+pub struct BlackboxState {
+    sink: Mutex<Option<Arc<BlackboxSink>>>,
+}
+
+impl BlackboxState {
+    fn new() -> Self {
+        BlackboxState {
+            sink: Mutex::new(None),
+        }
+    }
+
+    pub fn init(&self, sink: Arc<BlackboxSink>) {
+        Context::get_default().add_sink(sink.clone());
+        let previous = self
+            .sink
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .replace(sink);
+        if let Some(previous) = previous {
+            Context::get_default().remove_sink(foxglove::Sink::id(previous.as_ref()));
+        }
+    }
+
+    /// Writes the buffered messages to an MCAP file at `path`.
+    ///
+    /// Returns `Ok(false)` if the blackbox is not enabled.
+    pub fn dump(&self, path: &Path) -> Result<bool, FoxgloveError> {
+        let sink = self
+            .sink
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        match sink {
+            Some(sink) => sink.dump(path).map(|_| true),
+            None => Ok(false),
+        }
     }
 
     pub fn close(&self) {
-        if let Some(lock) = self.writer.get() {
-            if let Some(writer) = lock.lock().expect("Failed to lock writer mutex").take() {
-                writer.close().expect("Failed to close MCAP writer");
-            }
+        let sink = self
+            .sink
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(sink) = sink {
+            Context::get_default().remove_sink(foxglove::Sink::id(sink.as_ref()));
+        }
+    }
+}
+
+/// Manages the handle of the running Foxglove WebSocket server.
+///
+/// Dropping a `WebSocketServerHandle` leaves the server running forever, so the handle is
+/// kept here until `stop()` is called. The `Option` allows the handle to be taken out of
+/// the `Mutex` on shutdown and replaced when the server is started again.
+pub struct ServerState {
+    handle: Mutex<Option<WebSocketServerHandle>>,
+    /// Whether the running server advertised the time capability to its clients.
+    broadcasts_time: AtomicBool,
+}
+
+impl ServerState {
+    fn new() -> Self {
+        ServerState {
+            handle: Mutex::new(None),
+            broadcasts_time: AtomicBool::new(false),
+        }
+    }
+
+    pub fn init(&self, handle: WebSocketServerHandle, broadcasts_time: bool) {
+        let previous = self
+            .handle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .replace(handle);
+        self.broadcasts_time
+            .store(broadcasts_time, Ordering::Relaxed);
+        if let Some(previous) = previous {
+            log::warn!("Replacing a WebSocket server that was still running");
+            previous.stop().wait_blocking();
+        }
+    }
+
+    /// Sends the current time to every connected client, so live playback follows the
+    /// application's clock. Does nothing if the server did not advertise the time
+    /// capability when it was started.
+    pub fn broadcast_time(&self, nanos: u64) {
+        if !self.broadcasts_time.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Some(handle) = self
+            .handle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+        {
+            handle.broadcast_time(nanos);
+        }
+    }
+
+    /// Sends new parameter values to the clients subscribed to them, if the server is
+    /// running.
+    pub fn publish_parameter_values(&self, parameters: Vec<Parameter>) {
+        if parameters.is_empty() {
+            return;
+        }
+        if let Some(handle) = self
+            .handle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+        {
+            handle.publish_parameter_values(parameters);
+        }
+    }
+
+    /// Advertises new services to connected clients, if the server is running.
+    pub fn add_services(&self, services: Vec<Service>) -> Result<(), FoxgloveError> {
+        match self
+            .handle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+        {
+            Some(handle) => handle.add_services(services),
+            None => Ok(()),
+        }
+    }
+
+    /// Gracefully stops the server, if one is running.
+    ///
+    /// Connected clients are disconnected and any pending messages are flushed before this
+    /// function returns, after which the port is free to be bound again.
+    pub fn stop(&self) {
+        let handle = self
+            .handle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(handle) = handle {
+            handle.stop().wait_blocking();
         }
     }
 }
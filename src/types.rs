@@ -1,5 +1,5 @@
 #![allow(non_camel_case_types)]
-use std::os::raw::c_char;
+use std::os::raw::{c_char, c_void};
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -82,7 +82,7 @@ pub struct foxdbg_image_info_t {
 }
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum foxdbg_channel_type_t {
     FOXDBG_CHANNEL_TYPE_IMAGE,
     FOXDBG_CHANNEL_TYPE_POINTCLOUD,
@@ -95,3 +95,185 @@ pub enum foxdbg_channel_type_t {
     FOXDBG_CHANNEL_TYPE_INTEGER,
     FOXDBG_CHANNEL_TYPE_BOOLEAN,
 }
+
+/// Write counters of a channel, filled in by `foxdbg_get_channel_stats`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct foxdbg_channel_stats_t {
+    /// Number of writes published to the channel.
+    pub written: u64,
+    /// Number of writes dropped for arriving faster than the channel's target rate.
+    pub dropped: u64,
+}
+
+/// Display options of a channel, set by passing this struct to `foxdbg_write_channel_info`.
+/// Every channel type accepts it, and options that do not apply to a type are ignored.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct foxdbg_channel_options_t {
+    /// Coordinate frame the channel's data is expressed in, used by scene, point cloud,
+    /// image and location channels. NULL selects `world`.
+    pub frame_id: *const c_char,
+    /// Prepended to the topic to form the id of the scene entity a cubes, lines or pose
+    /// channel publishes. NULL adds no prefix, so the entity id is the topic.
+    pub entity_id_prefix: *const c_char,
+    /// How long scene entities are shown after being written, in milliseconds. 0 shows
+    /// them until they are replaced.
+    pub lifetime_ms: u32,
+    /// Whether scene entities move with their frame when the frame moves after they were
+    /// written, rather than staying where they were first shown.
+    pub frame_locked: bool,
+}
+
+/// Result of a foxdbg call. On anything other than `FOXDBG_RESULT_OK`, a description of
+/// the error can be read with `foxdbg_last_error`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum foxdbg_result_t {
+    FOXDBG_RESULT_OK = 0,
+    /// A NULL pointer or otherwise invalid argument was passed in.
+    FOXDBG_RESULT_INVALID_ARGUMENT,
+    /// foxdbg has not been initialised.
+    FOXDBG_RESULT_NOT_INITIALISED,
+    /// The feature was not enabled in the configuration.
+    FOXDBG_RESULT_NOT_ENABLED,
+    /// No channel has the given id or topic.
+    FOXDBG_RESULT_UNKNOWN_CHANNEL,
+    /// The size of the data does not match the channel's type.
+    FOXDBG_RESULT_SIZE_MISMATCH,
+    /// An image was written before its channel info was set.
+    FOXDBG_RESULT_MISSING_CHANNEL_INFO,
+    /// The data could not be encoded.
+    FOXDBG_RESULT_ENCODE_FAILED,
+    /// A file could not be read or written.
+    FOXDBG_RESULT_IO_ERROR,
+    /// The MCAP recording could not be written.
+    FOXDBG_RESULT_RECORDING_FAILED,
+    /// The WebSocket server could not be started.
+    FOXDBG_RESULT_SERVER_FAILED,
+    /// An unexpected internal error occurred.
+    FOXDBG_RESULT_INTERNAL_ERROR,
+    /// The topic is already registered with a different channel type.
+    FOXDBG_RESULT_CHANNEL_CONFLICT,
+    /// No parameter has the given name.
+    FOXDBG_RESULT_UNKNOWN_PARAMETER,
+}
+
+/// Clock passed to `foxdbg_set_clock_callback`. Returns the current time in nanoseconds
+/// since the UNIX epoch, and is called with the `user_data` it was registered with. It may
+/// be called from any thread that writes to foxdbg.
+pub type foxdbg_clock_callback_t = Option<unsafe extern "C" fn(user_data: *mut c_void) -> u64>;
+
+/// Callback passed to `foxdbg_subscribe_rx`, called with each message received on the
+/// subscribed topic. `data` points to `size` bytes laid out as the subscription's type, and
+/// is only valid for the duration of the call. Callbacks run one at a time on a dedicated
+/// dispatch thread, in the order the messages were received.
+pub type foxdbg_rx_callback_t =
+    Option<unsafe extern "C" fn(data: *const c_void, size: usize, user_data: *mut c_void)>;
+
+/// Callback passed to `foxdbg_add_service`, called with each request a Foxglove client sends
+/// to the service. `request` points to `request_size` bytes of JSON followed by a NUL, so
+/// it can be read as a string. On success, the callback writes its JSON response of up to
+/// `response_capacity` bytes to `response`, stores its length in `response_size` and
+/// returns true; an empty response is sent as `{}`. To fail the call, it returns false,
+/// optionally writing an error message to `response` the same way.
+pub type foxdbg_service_callback_t = Option<
+    unsafe extern "C" fn(
+        request: *const c_char,
+        request_size: usize,
+        response: *mut c_char,
+        response_capacity: usize,
+        response_size: *mut usize,
+        user_data: *mut c_void,
+    ) -> bool,
+>;
+
+/// What happens to a message received while the rx dispatch queue is full.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum foxdbg_overflow_policy_t {
+    /// Drop the oldest queued message to make room, so callbacks catch up with the latest.
+    FOXDBG_OVERFLOW_DROP_OLDEST,
+    /// Drop the message just received, so callbacks see an unbroken run of older messages.
+    FOXDBG_OVERFLOW_DROP_NEWEST,
+}
+
+/// Compression applied to chunks in MCAP recordings.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub enum foxdbg_compression_t {
+    FOXDBG_COMPRESSION_NONE,
+    FOXDBG_COMPRESSION_LZ4,
+    FOXDBG_COMPRESSION_ZSTD,
+}
+
+/// Options controlling how MCAP recordings are written.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct foxdbg_mcap_options_t {
+    /// Compression applied to each chunk. Defaults to zstd.
+    pub compression: foxdbg_compression_t,
+    /// Compression level passed to the compressor. 0 selects the compressor's default.
+    pub compression_level: u32,
+    /// Target uncompressed size of each chunk in bytes. 0 selects the default of 768 KiB.
+    pub chunk_size: u64,
+    /// Whether to write message index records after each chunk. Disabling them produces
+    /// smaller files that are slower to seek in.
+    pub emit_message_indexes: bool,
+}
+
+/// Configuration passed to `foxdbg_init_with_config`.
+///
+/// Use `foxdbg_config_default` to obtain a configuration populated with the defaults and
+/// override only the fields you need. String fields may be NULL to select their default.
+///
+/// When either rotation limit is set, recordings are split into a series of files named
+/// `{session}_{index}.mcap`, where the session is the UNIX time recording started.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct foxdbg_config_t {
+    /// Address the WebSocket server binds to. NULL selects `127.0.0.1`.
+    pub host: *const c_char,
+    /// Port the WebSocket server listens on. 0 selects any free port.
+    pub port: u16,
+    /// Server name advertised to Foxglove clients. NULL advertises no name.
+    pub server_name: *const c_char,
+    /// Directory MCAP recordings are written to. NULL selects the working directory.
+    pub recording_dir: *const c_char,
+    /// Whether to start the live WebSocket server.
+    pub enable_server: bool,
+    /// Whether to record to an MCAP file.
+    pub enable_recording: bool,
+    /// Roll over to a new MCAP file once the current one reaches this many bytes.
+    /// 0 disables size-based rotation.
+    pub max_file_size: u64,
+    /// Roll over to a new MCAP file once the current one has been open for this many
+    /// seconds. 0 disables time-based rotation.
+    pub max_file_duration_sec: u32,
+    /// Compression and chunking options for MCAP recordings and blackbox dumps.
+    pub mcap: foxdbg_mcap_options_t,
+    /// Whether to keep the most recent messages of every channel in an in-memory ring
+    /// buffer that can be written out with `foxdbg_dump_blackbox`.
+    pub enable_blackbox: bool,
+    /// Seconds of history kept by the blackbox. 0 removes the time limit.
+    pub blackbox_duration_sec: u32,
+    /// Maximum number of message bytes kept by the blackbox. 0 removes the size limit.
+    pub blackbox_max_bytes: u64,
+    /// Whether to close the recording when the process panics or exits without calling
+    /// `foxdbg_shutdown`.
+    pub enable_crash_safety: bool,
+    /// Whether to install SIGINT and SIGTERM handlers that close the recording before the
+    /// process terminates. These replace any handlers installed by the application.
+    pub install_signal_handlers: bool,
+    /// Write buffered MCAP data to disk at least this often, in milliseconds, bounding the
+    /// data lost if the process is killed. 0 only writes data out as chunks fill up.
+    pub flush_interval_ms: u32,
+    /// Number of received messages queued for `foxdbg_subscribe_rx` callbacks before the
+    /// overflow policy applies. 0 selects the default of 256.
+    pub rx_queue_depth: u32,
+    /// What happens to messages received while the rx dispatch queue is full.
+    pub rx_overflow_policy: foxdbg_overflow_policy_t,
+    /// Milliseconds a service call may take before its client is sent an error. 0 selects
+    /// the default of 5000.
+    pub service_timeout_ms: u32,
+}
@@ -0,0 +1,65 @@
+use crate::error::{Error, Result};
+use std::ffi::c_void;
+use std::sync::{Mutex, OnceLock};
+use std::thread::{self, ThreadId};
+
+/// The opaque pointer an application registers alongside a C callback, handed back to the
+/// callback when it is called.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct UserData(pub(crate) *mut c_void);
+
+// The pointer is never dereferenced by foxdbg-rs, only passed back to the callback, which
+// the application has promised can be called from other threads.
+unsafe impl Send for UserData {}
+
+impl UserData {
+    pub(crate) fn get(self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// A background thread that is started the first time it is needed and then runs for the
+/// rest of the process.
+pub(crate) struct LazyThread {
+    name: &'static str,
+    id: OnceLock<ThreadId>,
+    /// Held while starting the thread, so two first callers do not both start one.
+    starting: Mutex<()>,
+}
+
+impl LazyThread {
+    pub(crate) const fn new(name: &'static str) -> Self {
+        LazyThread {
+            name,
+            id: OnceLock::new(),
+            starting: Mutex::new(()),
+        }
+    }
+
+    /// Starts the thread running `run`, unless it is already running.
+    pub(crate) fn ensure_started(&self, run: fn()) -> Result<()> {
+        if self.id.get().is_some() {
+            return Ok(());
+        }
+
+        let _starting = self
+            .starting
+            .lock()
+            .expect("Failed to lock thread start mutex");
+        if self.id.get().is_none() {
+            let handle = thread::Builder::new()
+                .name(self.name.to_owned())
+                .spawn(run)
+                .map_err(|e| {
+                    Error::Internal(format!("failed to start thread '{}': {}", self.name, e))
+                })?;
+            let _ = self.id.set(handle.thread().id());
+        }
+        Ok(())
+    }
+
+    /// Whether the calling thread is this thread.
+    pub(crate) fn is_current(&self) -> bool {
+        self.id.get() == Some(&thread::current().id())
+    }
+}